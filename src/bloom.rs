@@ -0,0 +1,276 @@
+use wgpu::util::DeviceExt;
+use wgpu::{BindGroup, BindGroupLayout, Device};
+
+use crate::bind_groups::{create_bloom_bind_group, create_bloom_bind_group_layout};
+use crate::hdr::HdrTarget;
+
+/// Levels of the chain, each half the size of the previous one starting at half resolution.
+const MAX_LEVELS: u32 = 6;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct BloomUniform {
+    threshold: f32,
+    knee: f32,
+    filter_radius: f32,
+    _padding: f32,
+}
+
+/// Glow around the parts of the HDR scene brighter than the threshold.
+///
+/// The thresholded scene is downsampled through a chain of half size targets, then each level is
+/// upsampled and added onto the next larger one, so `output` ends up holding the blur of every
+/// level. The levels are separate textures rather than mips, which keeps each pass bound to a
+/// whole texture.
+pub struct Bloom {
+    prefilter_pipeline: wgpu::RenderPipeline,
+    downsample_pipeline: wgpu::RenderPipeline,
+    upsample_pipeline: wgpu::RenderPipeline,
+    bind_group_layout: BindGroupLayout,
+    sampler: wgpu::Sampler,
+    uniform_buffer: wgpu::Buffer,
+    levels: Vec<wgpu::TextureView>,
+    prefilter_bind_group: BindGroup,
+    level_bind_groups: Vec<BindGroup>,
+}
+
+impl Bloom {
+    /// Scale of the glow added to the scene by the composite pass.
+    pub const INTENSITY: f32 = 0.5;
+
+    pub fn new(device: &Device, config: &wgpu::SurfaceConfiguration, hdr: &HdrTarget) -> Bloom {
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Bloom Buffer"),
+            contents: bytemuck::cast_slice(&[BloomUniform {
+                threshold: 1.0,
+                knee: 0.5,
+                filter_radius: 1.0,
+                _padding: 0.0,
+            }]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("bloom_sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let bind_group_layout = create_bloom_bind_group_layout(device);
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Bloom Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Bloom Shader"),
+            source: wgpu::ShaderSource::Wgsl(
+                concat!(include_str!("fullscreen.wgsl"), include_str!("bloom.wgsl")).into(),
+            ),
+        });
+        let additive = wgpu::BlendState {
+            color: wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::One,
+                dst_factor: wgpu::BlendFactor::One,
+                operation: wgpu::BlendOperation::Add,
+            },
+            alpha: wgpu::BlendComponent::REPLACE,
+        };
+        let prefilter_pipeline = create_bloom_pipeline(
+            device,
+            &layout,
+            &shader,
+            "fs_prefilter",
+            wgpu::BlendState::REPLACE,
+        );
+        let downsample_pipeline = create_bloom_pipeline(
+            device,
+            &layout,
+            &shader,
+            "fs_downsample",
+            wgpu::BlendState::REPLACE,
+        );
+        let upsample_pipeline =
+            create_bloom_pipeline(device, &layout, &shader, "fs_upsample", additive);
+
+        let (levels, prefilter_bind_group, level_bind_groups) = create_levels(
+            device,
+            config,
+            hdr,
+            &sampler,
+            &uniform_buffer,
+            &bind_group_layout,
+        );
+
+        Bloom {
+            prefilter_pipeline,
+            downsample_pipeline,
+            upsample_pipeline,
+            bind_group_layout,
+            sampler,
+            uniform_buffer,
+            levels,
+            prefilter_bind_group,
+            level_bind_groups,
+        }
+    }
+
+    /// Recreates the chain for the new surface size, call after recreating `hdr`.
+    pub fn resize(
+        &mut self,
+        device: &Device,
+        config: &wgpu::SurfaceConfiguration,
+        hdr: &HdrTarget,
+    ) {
+        (
+            self.levels,
+            self.prefilter_bind_group,
+            self.level_bind_groups,
+        ) = create_levels(
+            device,
+            config,
+            hdr,
+            &self.sampler,
+            &self.uniform_buffer,
+            &self.bind_group_layout,
+        );
+    }
+
+    /// The accumulated glow at half the surface resolution.
+    pub fn output(&self) -> &wgpu::TextureView {
+        &self.levels[0]
+    }
+
+    pub fn render(&self, encoder: &mut wgpu::CommandEncoder) {
+        draw_level(
+            encoder,
+            &self.levels[0],
+            &self.prefilter_pipeline,
+            &self.prefilter_bind_group,
+            wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+        );
+        for level in 1..self.levels.len() {
+            draw_level(
+                encoder,
+                &self.levels[level],
+                &self.downsample_pipeline,
+                &self.level_bind_groups[level - 1],
+                wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+            );
+        }
+        for level in (0..self.levels.len() - 1).rev() {
+            draw_level(
+                encoder,
+                &self.levels[level],
+                &self.upsample_pipeline,
+                &self.level_bind_groups[level + 1],
+                wgpu::LoadOp::Load,
+            );
+        }
+    }
+}
+
+// Returns the level targets, the bind group reading the scene and one bind group reading each
+// level.
+fn create_levels(
+    device: &Device,
+    config: &wgpu::SurfaceConfiguration,
+    hdr: &HdrTarget,
+    sampler: &wgpu::Sampler,
+    uniform_buffer: &wgpu::Buffer,
+    bind_group_layout: &BindGroupLayout,
+) -> (Vec<wgpu::TextureView>, BindGroup, Vec<BindGroup>) {
+    let levels = (1..=MAX_LEVELS)
+        .map(|level| {
+            device
+                .create_texture(&wgpu::TextureDescriptor {
+                    label: Some("bloom_level"),
+                    size: wgpu::Extent3d {
+                        width: (config.width >> level).max(1),
+                        height: (config.height >> level).max(1),
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: HdrTarget::FORMAT,
+                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                        | wgpu::TextureUsages::TEXTURE_BINDING,
+                    view_formats: &[],
+                })
+                .create_view(&wgpu::TextureViewDescriptor::default())
+        })
+        .collect::<Vec<_>>();
+
+    let prefilter_bind_group = create_bloom_bind_group(
+        device,
+        &hdr.view,
+        sampler,
+        uniform_buffer,
+        bind_group_layout,
+    );
+    let level_bind_groups = levels
+        .iter()
+        .map(|level| {
+            create_bloom_bind_group(device, level, sampler, uniform_buffer, bind_group_layout)
+        })
+        .collect();
+
+    (levels, prefilter_bind_group, level_bind_groups)
+}
+
+fn draw_level(
+    encoder: &mut wgpu::CommandEncoder,
+    target: &wgpu::TextureView,
+    pipeline: &wgpu::RenderPipeline,
+    bind_group: &BindGroup,
+    load: wgpu::LoadOp<wgpu::Color>,
+) {
+    let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some("Bloom Pass"),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view: target,
+            resolve_target: None,
+            ops: wgpu::Operations { load, store: true },
+        })],
+        depth_stencil_attachment: None,
+    });
+    render_pass.set_pipeline(pipeline);
+    render_pass.set_bind_group(0, bind_group, &[]);
+    render_pass.draw(0..3, 0..1);
+}
+
+fn create_bloom_pipeline(
+    device: &Device,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    entry_point: &str,
+    blend: wgpu::BlendState,
+) -> wgpu::RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Bloom Pipeline"),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: "vs_main",
+            buffers: &[],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point,
+            targets: &[Some(wgpu::ColorTargetState {
+                format: HdrTarget::FORMAT,
+                blend: Some(blend),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+    })
+}
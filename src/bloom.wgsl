@@ -0,0 +1,84 @@
+// bloom.wgsl
+// Bloom mip chain: bright parts of the HDR scene are extracted, progressively downsampled, then
+// upsampled and accumulated back up the chain, spreading them into a wide glow.
+
+struct Bloom {
+    threshold: f32,
+    // Width of the soft transition below the threshold.
+    knee: f32,
+    // Upsample tent radius in texels of the level being upsampled.
+    filter_radius: f32,
+}
+
+@group(0) @binding(0)
+var t_source: texture_2d<f32>;
+@group(0) @binding(1)
+var s_source: sampler;
+@group(0) @binding(2)
+var<uniform> bloom: Bloom;
+
+fn source(uv: vec2<f32>, offset: vec2<f32>, texel: vec2<f32>) -> vec3<f32> {
+    return textureSampleLevel(t_source, s_source, uv + offset * texel, 0.0).rgb;
+}
+
+// 13 tap filter from Jimenez's "Next Generation Post Processing in Call of Duty": overlapping
+// bilinear boxes, which avoid the shimmering of a plain 2x2 box downsample.
+fn downsample(uv: vec2<f32>) -> vec3<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(t_source));
+
+    let a = source(uv, vec2<f32>(-2.0, -2.0), texel);
+    let b = source(uv, vec2<f32>(0.0, -2.0), texel);
+    let c = source(uv, vec2<f32>(2.0, -2.0), texel);
+    let d = source(uv, vec2<f32>(-2.0, 0.0), texel);
+    let e = source(uv, vec2<f32>(0.0, 0.0), texel);
+    let f = source(uv, vec2<f32>(2.0, 0.0), texel);
+    let g = source(uv, vec2<f32>(-2.0, 2.0), texel);
+    let h = source(uv, vec2<f32>(0.0, 2.0), texel);
+    let i = source(uv, vec2<f32>(2.0, 2.0), texel);
+    let j = source(uv, vec2<f32>(-1.0, -1.0), texel);
+    let k = source(uv, vec2<f32>(1.0, -1.0), texel);
+    let l = source(uv, vec2<f32>(-1.0, 1.0), texel);
+    let m = source(uv, vec2<f32>(1.0, 1.0), texel);
+
+    return e * 0.125
+        + (a + c + g + i) * 0.03125
+        + (b + d + f + h) * 0.0625
+        + (j + k + l + m) * 0.125;
+}
+
+// Soft knee threshold: full brightness above `threshold`, fading out quadratically over `knee`.
+fn threshold(color: vec3<f32>) -> vec3<f32> {
+    let brightness = max(color.r, max(color.g, color.b));
+    var soft = clamp(brightness - bloom.threshold + bloom.knee, 0.0, 2.0 * bloom.knee);
+    soft = soft * soft / (4.0 * bloom.knee + 0.00001);
+    let contribution = max(soft, brightness - bloom.threshold) / max(brightness, 0.00001);
+    return color * contribution;
+}
+
+@fragment
+fn fs_prefilter(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(threshold(downsample(in.uv)), 1.0);
+}
+
+@fragment
+fn fs_downsample(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(downsample(in.uv), 1.0);
+}
+
+// 3x3 tent filter, blended additively onto the next larger level.
+@fragment
+fn fs_upsample(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let texel = bloom.filter_radius / vec2<f32>(textureDimensions(t_source));
+
+    var color = source(in.uv, vec2<f32>(0.0, 0.0), texel) * 4.0;
+    color += (source(in.uv, vec2<f32>(0.0, -1.0), texel)
+        + source(in.uv, vec2<f32>(-1.0, 0.0), texel)
+        + source(in.uv, vec2<f32>(1.0, 0.0), texel)
+        + source(in.uv, vec2<f32>(0.0, 1.0), texel)) * 2.0;
+    color += source(in.uv, vec2<f32>(-1.0, -1.0), texel)
+        + source(in.uv, vec2<f32>(1.0, -1.0), texel)
+        + source(in.uv, vec2<f32>(-1.0, 1.0), texel)
+        + source(in.uv, vec2<f32>(1.0, 1.0), texel);
+
+    return vec4<f32>(color / 16.0, 1.0);
+}
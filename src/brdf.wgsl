@@ -0,0 +1,86 @@
+// brdf.wgsl
+// Metallic-roughness shading: Cook-Torrance with GGX distribution, Smith geometry and Fresnel-Schlick.
+// Shared by the forward PBR pipeline and the deferred lighting pass.
+
+const PI: f32 = 3.14159265359;
+
+fn distribution_ggx(n_dot_h: f32, roughness: f32) -> f32 {
+    let a = roughness * roughness;
+    let a2 = a * a;
+    let denom = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    return a2 / (PI * denom * denom);
+}
+
+fn geometry_schlick_ggx(n_dot_x: f32, roughness: f32) -> f32 {
+    let r = roughness + 1.0;
+    let k = r * r / 8.0;
+    return n_dot_x / (n_dot_x * (1.0 - k) + k);
+}
+
+fn geometry_smith(n_dot_v: f32, n_dot_l: f32, roughness: f32) -> f32 {
+    return geometry_schlick_ggx(n_dot_v, roughness) * geometry_schlick_ggx(n_dot_l, roughness);
+}
+
+fn fresnel_schlick(cos_theta: f32, f0: vec3<f32>) -> vec3<f32> {
+    return f0 + (1.0 - f0) * pow(clamp(1.0 - cos_theta, 0.0, 1.0), 5.0);
+}
+
+// Fresnel for light arriving from a whole hemisphere, where rough surfaces reflect less at grazing angles.
+fn fresnel_schlick_roughness(cos_theta: f32, f0: vec3<f32>, roughness: f32) -> vec3<f32> {
+    return f0 + (max(vec3<f32>(1.0 - roughness), f0) - f0) * pow(clamp(1.0 - cos_theta, 0.0, 1.0), 5.0);
+}
+
+struct PbrSurface {
+    position: vec3<f32>,
+    // Offsets shadow lookups, the interpolated normal where available, else the shading normal.
+    geometric_normal: vec3<f32>,
+    normal: vec3<f32>,
+    base_color: vec3<f32>,
+    metallic: f32,
+    roughness: f32,
+    occlusion: f32,
+}
+
+// Image based ambient light plus every point light of the fragment's cluster.
+fn pbr_lighting(surface: PbrSurface, frag_coord: vec4<f32>, view_position: vec3<f32>) -> vec3<f32> {
+    let n = surface.normal;
+    let v = normalize(view_position - surface.position);
+    let n_dot_v = max(dot(n, v), 0.0001);
+    let roughness = surface.roughness;
+    let metallic = surface.metallic;
+    let base_color = surface.base_color;
+
+    let f0 = mix(vec3<f32>(0.04), base_color, metallic);
+
+    let f_ambient = fresnel_schlick_roughness(n_dot_v, f0, roughness);
+    let k_d_ambient = (vec3<f32>(1.0) - f_ambient) * (1.0 - metallic);
+    let ambient = k_d_ambient * ambient_diffuse(n) * base_color
+        + ambient_specular(n, v, f_ambient, roughness);
+
+    var lo = vec3<f32>(0.0);
+
+    let cluster = cluster_index(frag_coord, surface.position);
+    for (var i = 0u; i < cluster_light_count(cluster); i++) {
+        let light = cluster_light(cluster, i);
+        let to_light = light.position - surface.position;
+        let distance = length(to_light);
+        let l = to_light / distance;
+        let h = normalize(v + l);
+        let n_dot_l = max(dot(n, l), 0.0);
+        let n_dot_h = max(dot(n, h), 0.0);
+
+        let d = distribution_ggx(n_dot_h, roughness);
+        let g = geometry_smith(n_dot_v, n_dot_l, roughness);
+        let f = fresnel_schlick(max(dot(h, v), 0.0), f0);
+
+        let specular = d * g * f / (4.0 * n_dot_v * n_dot_l + 0.0001);
+        let k_d = (vec3<f32>(1.0) - f) * (1.0 - metallic);
+
+        let radiance = light.color * range_attenuation(distance, light.range);
+        let shadow = point_shadow(light, surface.position, surface.geometric_normal);
+
+        lo += (k_d * base_color / PI + specular) * radiance * n_dot_l * shadow;
+    }
+
+    return ambient * surface.occlusion * ambient_occlusion(frag_coord) + lo;
+}
@@ -2,8 +2,6 @@ mod camera_controller;
 
 pub use camera_controller::CameraController;
 
-use cgmath::InnerSpace;
-
 #[derive(Debug)]
 pub struct Camera {
     eye: cgmath::Point3<f32>,
@@ -14,6 +12,7 @@ pub struct Camera {
     znear: f32,
     zfar: f32,
     speed: f32,
+    jitter: cgmath::Vector2<f32>,
 }
 
 #[rustfmt::skip]
@@ -50,13 +49,51 @@ impl Camera {
             znear,
             zfar,
             speed: 0.2,
+            jitter: cgmath::Vector2::new(0.0, 0.0),
         }
     }
-    fn build_view_projection_matrix(&self) -> cgmath::Matrix4<f32> {
-        let view = cgmath::Matrix4::look_at_rh(self.eye, self.target, self.up);
+    pub fn view_matrix(&self) -> cgmath::Matrix4<f32> {
+        cgmath::Matrix4::look_at_rh(self.eye, self.target, self.up)
+    }
+
+    pub fn projection_matrix(&self) -> cgmath::Matrix4<f32> {
         let proj = cgmath::perspective(cgmath::Deg(self.fovy), self.aspect, self.znear, self.zfar);
 
-        OPENGL_TO_WGPU_MATRIX * proj * view
+        OPENGL_TO_WGPU_MATRIX * proj
+    }
+
+    pub fn znear(&self) -> f32 {
+        self.znear
+    }
+
+    pub fn zfar(&self) -> f32 {
+        self.zfar
+    }
+
+    pub fn eye(&self) -> cgmath::Point3<f32> {
+        self.eye
+    }
+
+    pub fn direction(&self) -> cgmath::Vector3<f32> {
+        use cgmath::InnerSpace;
+        (self.target - self.eye).normalize()
+    }
+
+    /// Sub-pixel offset of the projection in normalized device coordinates, used by temporal
+    /// anti-aliasing to sample a different point of each pixel every frame.
+    pub fn set_jitter(&mut self, jitter: cgmath::Vector2<f32>) {
+        self.jitter = jitter;
+    }
+
+    pub fn build_view_projection_matrix(&self) -> cgmath::Matrix4<f32> {
+        // Translating clip space by the jitter times w shifts every projected point by the same
+        // amount in normalized device coordinates.
+        let jitter = cgmath::Matrix4::from_translation(self.jitter.extend(0.0));
+        jitter * self.build_unjittered_view_projection_matrix()
+    }
+
+    fn build_unjittered_view_projection_matrix(&self) -> cgmath::Matrix4<f32> {
+        self.projection_matrix() * self.view_matrix()
     }
 }
 
@@ -64,6 +101,10 @@ impl Camera {
 #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct CameraUniform {
     view_proj: [[f32; 4]; 4],
+    view_position: [f32; 4],
+    inv_view_proj: [[f32; 4]; 4],
+    unjittered_view_proj: [[f32; 4]; 4],
+    prev_view_proj: [[f32; 4]; 4],
 }
 
 impl CameraUniform {
@@ -71,10 +112,23 @@ impl CameraUniform {
         use cgmath::SquareMatrix;
         Self {
             view_proj: cgmath::Matrix4::identity().into(),
+            view_position: [0.0; 4],
+            inv_view_proj: cgmath::Matrix4::identity().into(),
+            unjittered_view_proj: cgmath::Matrix4::identity().into(),
+            prev_view_proj: cgmath::Matrix4::identity().into(),
         }
     }
 
     pub fn update_view_proj(&mut self, camera: &Camera) {
-        self.view_proj = camera.build_view_projection_matrix().into();
+        use cgmath::SquareMatrix;
+        let view_proj = camera.build_view_projection_matrix();
+        self.view_proj = view_proj.into();
+        // Lets fullscreen passes turn clip space positions back into world space view rays.
+        self.inv_view_proj = view_proj.invert().unwrap().into();
+        self.view_position = camera.eye.to_homogeneous().into();
+        // Motion vectors compare where surfaces are now against last frame, both without jitter
+        // so they only hold real movement. Call once per frame to keep the previous one valid.
+        self.prev_view_proj = self.unjittered_view_proj;
+        self.unjittered_view_proj = camera.build_unjittered_view_projection_matrix().into();
     }
 }
@@ -0,0 +1,142 @@
+use cgmath::SquareMatrix;
+use wgpu::util::DeviceExt;
+use wgpu::{BindGroup, Device, Queue};
+
+use crate::camera::Camera;
+
+/// Froxel grid dimensions: screen tiles across, screen tiles down and depth slices.
+pub const CLUSTER_GRID: [u32; 3] = [16, 9, 24];
+/// Must match `MAX_LIGHTS_PER_CLUSTER` in `cluster.wgsl` and `lights.wgsl`.
+pub const MAX_LIGHTS_PER_CLUSTER: u32 = 128;
+const CLUSTER_COUNT: u32 = CLUSTER_GRID[0] * CLUSTER_GRID[1] * CLUSTER_GRID[2];
+const WORKGROUP_SIZE: u32 = 4;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct ClusterUniform {
+    view: [[f32; 4]; 4],
+    inv_proj: [[f32; 4]; 4],
+    grid: [u32; 3],
+    enabled: u32,
+    screen_size: [f32; 2],
+    z_near: f32,
+    z_far: f32,
+}
+
+/// Clustered forward lighting: a compute pass splits the view frustum into a grid of froxels and
+/// lists the lights overlapping each one, so fragments only evaluate the lights of their cluster.
+///
+/// Each cluster owns a fixed slot of `MAX_LIGHTS_PER_CLUSTER` indices, lights beyond that are
+/// dropped for the cluster. When disabled the fragment shaders fall back to looping every light.
+pub struct LightClusters {
+    pub uniform_buffer: wgpu::Buffer,
+    pub light_counts: wgpu::Buffer,
+    pub light_indices: wgpu::Buffer,
+    pipeline: wgpu::ComputePipeline,
+    bind_group: BindGroup,
+}
+
+impl LightClusters {
+    pub fn new(device: &Device, light_buffer: &wgpu::Buffer) -> LightClusters {
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Cluster Buffer"),
+            contents: bytemuck::cast_slice(&[ClusterUniform {
+                view: cgmath::Matrix4::identity().into(),
+                inv_proj: cgmath::Matrix4::identity().into(),
+                grid: CLUSTER_GRID,
+                enabled: 0,
+                screen_size: [1.0, 1.0],
+                z_near: 0.1,
+                z_far: 100.0,
+            }]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let light_counts = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Cluster Light Counts Buffer"),
+            size: CLUSTER_COUNT as wgpu::BufferAddress * 4,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+        let light_indices = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Cluster Light Indices Buffer"),
+            size: (CLUSTER_COUNT * MAX_LIGHTS_PER_CLUSTER) as wgpu::BufferAddress * 4,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Cluster Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("cluster.wgsl").into()),
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Cluster Pipeline"),
+            layout: None,
+            module: &shader,
+            entry_point: "main",
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: light_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: light_counts.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: light_indices.as_entire_binding(),
+                },
+            ],
+            label: Some("cluster_bind_group"),
+        });
+
+        LightClusters {
+            uniform_buffer,
+            light_counts,
+            light_indices,
+            pipeline,
+            bind_group,
+        }
+    }
+
+    /// Uploads the camera the grid is built from. With `enabled` false the shaders evaluate every
+    /// light and `build` can be skipped.
+    pub fn update(
+        &self,
+        queue: &Queue,
+        camera: &Camera,
+        config: &wgpu::SurfaceConfiguration,
+        enabled: bool,
+    ) {
+        let uniform = ClusterUniform {
+            view: camera.view_matrix().into(),
+            inv_proj: camera.projection_matrix().invert().unwrap().into(),
+            grid: CLUSTER_GRID,
+            enabled: enabled as u32,
+            screen_size: [config.width as f32, config.height as f32],
+            z_near: camera.znear(),
+            z_far: camera.zfar(),
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+    }
+
+    pub fn build(&self, encoder: &mut wgpu::CommandEncoder) {
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Light Cluster Pass"),
+        });
+        compute_pass.set_pipeline(&self.pipeline);
+        compute_pass.set_bind_group(0, &self.bind_group, &[]);
+        compute_pass.dispatch_workgroups(
+            CLUSTER_GRID[0].div_ceil(WORKGROUP_SIZE),
+            CLUSTER_GRID[1].div_ceil(WORKGROUP_SIZE),
+            CLUSTER_GRID[2].div_ceil(WORKGROUP_SIZE),
+        );
+    }
+}
@@ -0,0 +1,88 @@
+// cluster.wgsl
+// Bins lights into the froxel grid: one invocation per cluster tests every light sphere against
+// the cluster's view space bounding box and records the overlapping light indices.
+
+struct Clusters {
+    view: mat4x4<f32>,
+    inv_proj: mat4x4<f32>,
+    grid: vec3<u32>,
+    enabled: u32,
+    screen_size: vec2<f32>,
+    z_near: f32,
+    z_far: f32,
+}
+@group(0) @binding(0)
+var<uniform> clusters: Clusters;
+
+struct Light {
+    position: vec3<f32>,
+    range: f32,
+    color: vec3<f32>,
+    shadow_index: i32,
+}
+@group(0) @binding(1)
+var<storage, read> lights: array<Light>;
+@group(0) @binding(2)
+var<storage, read_write> cluster_light_counts: array<u32>;
+@group(0) @binding(3)
+var<storage, read_write> cluster_light_indices: array<u32>;
+
+const MAX_LIGHTS_PER_CLUSTER: u32 = 128u;
+
+// Point on the view ray through `ndc` at the given distance in front of the camera.
+fn view_point(ndc: vec2<f32>, depth: f32) -> vec3<f32> {
+    let far = clusters.inv_proj * vec4<f32>(ndc, 1.0, 1.0);
+    let ray = far.xyz / far.w;
+    return ray * (depth / -ray.z);
+}
+
+@compute @workgroup_size(4, 4, 4)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (any(id >= clusters.grid)) {
+        return;
+    }
+    let cluster = id.x + id.y * clusters.grid.x + id.z * clusters.grid.x * clusters.grid.y;
+
+    // Tiles are numbered from the top left like framebuffer coordinates.
+    let grid = vec2<f32>(clusters.grid.xy);
+    let tile_min = vec2<f32>(id.xy) / grid;
+    let tile_max = vec2<f32>(id.xy + 1u) / grid;
+    let ndc_min = vec2<f32>(tile_min.x * 2.0 - 1.0, 1.0 - tile_max.y * 2.0);
+    let ndc_max = vec2<f32>(tile_max.x * 2.0 - 1.0, 1.0 - tile_min.y * 2.0);
+
+    // Depth slices are spaced exponentially so clusters stay roughly cubic.
+    let depth_ratio = clusters.z_far / clusters.z_near;
+    let near = clusters.z_near * pow(depth_ratio, f32(id.z) / f32(clusters.grid.z));
+    let far = clusters.z_near * pow(depth_ratio, f32(id.z + 1u) / f32(clusters.grid.z));
+
+    var corners = array<vec3<f32>, 8>(
+        view_point(ndc_min, near),
+        view_point(vec2<f32>(ndc_max.x, ndc_min.y), near),
+        view_point(vec2<f32>(ndc_min.x, ndc_max.y), near),
+        view_point(ndc_max, near),
+        view_point(ndc_min, far),
+        view_point(vec2<f32>(ndc_max.x, ndc_min.y), far),
+        view_point(vec2<f32>(ndc_min.x, ndc_max.y), far),
+        view_point(ndc_max, far),
+    );
+    var aabb_min = corners[0];
+    var aabb_max = corners[0];
+    for (var i = 1; i < 8; i++) {
+        aabb_min = min(aabb_min, corners[i]);
+        aabb_max = max(aabb_max, corners[i]);
+    }
+
+    var count = 0u;
+    let offset = cluster * MAX_LIGHTS_PER_CLUSTER;
+    for (var i = 0u; i < arrayLength(&lights) && count < MAX_LIGHTS_PER_CLUSTER; i++) {
+        let light = lights[i];
+        let center = (clusters.view * vec4<f32>(light.position, 1.0)).xyz;
+        let closest = clamp(center, aabb_min, aabb_max);
+        let to_closest = closest - center;
+        if (dot(to_closest, to_closest) <= light.range * light.range) {
+            cluster_light_indices[offset + count] = i;
+            count++;
+        }
+    }
+    cluster_light_counts[cluster] = count;
+}
@@ -0,0 +1,105 @@
+// composite.wgsl
+// Resolves the HDR scene to the surface: adds the bloom glow, applies the manual or adapted exposure
+// and maps the result into displayable range with the selected tonemapping operator.
+
+// Must match the order of `Tonemapping` in `hdr.rs`.
+const TONEMAP_NONE: u32 = 0u;
+const TONEMAP_REINHARD: u32 = 1u;
+const TONEMAP_ACES: u32 = 2u;
+const TONEMAP_AGX: u32 = 3u;
+
+struct Composite {
+    bloom_intensity: f32,
+    // Linear scale, 2 to the power of the exposure value. Compensation on top of the adapted
+    // exposure when `auto_exposure` is set.
+    exposure: f32,
+    tonemapping: u32,
+    auto_exposure: u32,
+}
+
+// Written by exposure.wgsl.
+struct Exposure {
+    adapted_ev: f32,
+    exposure: f32,
+}
+
+@group(0) @binding(0)
+var t_scene: texture_2d<f32>;
+@group(0) @binding(1)
+var t_bloom: texture_2d<f32>;
+@group(0) @binding(2)
+var s_composite: sampler;
+@group(0) @binding(3)
+var<uniform> composite: Composite;
+@group(0) @binding(4)
+var<storage, read> adapted: Exposure;
+
+fn tonemap_reinhard(color: vec3<f32>) -> vec3<f32> {
+    return color / (1.0 + color);
+}
+
+// Stephen Hill's fit of the ACES reference rendering and output transforms.
+fn tonemap_aces(color: vec3<f32>) -> vec3<f32> {
+    let input = mat3x3<f32>(
+        0.59719, 0.07600, 0.02840,
+        0.35458, 0.90834, 0.13383,
+        0.04823, 0.01566, 0.83777,
+    );
+    let output = mat3x3<f32>(
+        1.60475, -0.10208, -0.00327,
+        -0.53108, 1.10813, -0.07276,
+        -0.07367, -0.00605, 1.07602,
+    );
+    let v = input * color;
+    let fitted = (v * (v + 0.0245786) - 0.000090537) / (v * (0.983729 * v + 0.4329510) + 0.238081);
+    return clamp(output * fitted, vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+// Minimal AgX with the default look, after Benjamin Wrensch's polynomial fit of the contrast curve.
+fn tonemap_agx(color: vec3<f32>) -> vec3<f32> {
+    let inset = mat3x3<f32>(
+        0.842479062253094, 0.0423282422610123, 0.0423756549057051,
+        0.0784335999999992, 0.878468636469772, 0.0784336,
+        0.0792237451477643, 0.0791661274605434, 0.879142973793104,
+    );
+    let outset = mat3x3<f32>(
+        1.19687900512017, -0.0528968517574562, -0.0529716355144438,
+        -0.0980208811401368, 1.15190312990417, -0.0980434501171241,
+        -0.0990297440797205, -0.0989611768448433, 1.15107367264116,
+    );
+    let min_ev = -12.47393;
+    let max_ev = 4.026069;
+
+    var x = clamp(log2(max(inset * color, vec3<f32>(1e-10))), vec3<f32>(min_ev), vec3<f32>(max_ev));
+    x = (x - min_ev) / (max_ev - min_ev);
+
+    let x2 = x * x;
+    let x4 = x2 * x2;
+    x = 15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x
+        - 0.00232;
+
+    // The curve produces display encoded values, the surface expects linear ones.
+    return pow(max(outset * x, vec3<f32>(0.0)), vec3<f32>(2.2));
+}
+
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let scene = textureSampleLevel(t_scene, s_composite, in.uv, 0.0).rgb;
+    let bloom = textureSampleLevel(t_bloom, s_composite, in.uv, 0.0).rgb;
+    var exposure = composite.exposure;
+    if (composite.auto_exposure != 0u) {
+        exposure *= adapted.exposure;
+    }
+    let color = (scene + bloom * composite.bloom_intensity) * exposure;
+
+    var mapped = color;
+    if (composite.tonemapping == TONEMAP_REINHARD) {
+        mapped = tonemap_reinhard(color);
+    } else if (composite.tonemapping == TONEMAP_ACES) {
+        mapped = tonemap_aces(color);
+    } else if (composite.tonemapping == TONEMAP_AGX) {
+        mapped = tonemap_agx(color);
+    }
+
+    return vec4<f32>(mapped, 1.0);
+}
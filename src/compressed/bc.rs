@@ -0,0 +1,493 @@
+// CPU decoders for the BC1-BC5 and BC7 block formats, for devices without
+// `Features::TEXTURE_COMPRESSION_BC`. Every block covers 4x4 texels and decodes to RGBA8 in row
+// order, with the channels a GPU would return for the format.
+
+type Block = [[u8; 4]; 16];
+
+/// Decodes one layer of one mip level to tightly packed RGBA8, or `None` for the BC formats
+/// without a CPU decoder (the signed and HDR ones).
+pub fn decode(
+    format: wgpu::TextureFormat,
+    data: &[u8],
+    width: u32,
+    height: u32,
+) -> Option<Vec<u8>> {
+    use wgpu::TextureFormat::*;
+    let (block_size, decode_block): (usize, fn(&[u8], &mut Block)) = match format {
+        Bc1RgbaUnorm | Bc1RgbaUnormSrgb => (8, decode_bc1),
+        Bc2RgbaUnorm | Bc2RgbaUnormSrgb => (16, decode_bc2),
+        Bc3RgbaUnorm | Bc3RgbaUnormSrgb => (16, decode_bc3),
+        Bc4RUnorm => (8, decode_bc4),
+        Bc5RgUnorm => (16, decode_bc5),
+        Bc7RgbaUnorm | Bc7RgbaUnormSrgb => (16, decode_bc7),
+        _ => return None,
+    };
+
+    let blocks_wide = (width as usize).div_ceil(4);
+    let blocks_high = (height as usize).div_ceil(4);
+    let mut rgba = vec![0; width as usize * height as usize * 4];
+    let mut texels = [[0; 4]; 16];
+    for (i, block) in data
+        .chunks_exact(block_size)
+        .take(blocks_wide * blocks_high)
+        .enumerate()
+    {
+        decode_block(block, &mut texels);
+        let (block_x, block_y) = (i % blocks_wide * 4, i / blocks_wide * 4);
+        for (j, texel) in texels.iter().enumerate() {
+            let (x, y) = (block_x + j % 4, block_y + j / 4);
+            // Blocks on the right and bottom edges overhang smaller images.
+            if x < width as usize && y < height as usize {
+                let offset = (y * width as usize + x) * 4;
+                rgba[offset..offset + 4].copy_from_slice(texel);
+            }
+        }
+    }
+    Some(rgba)
+}
+
+fn rgb565(color: u16) -> [u8; 4] {
+    let r = (color >> 11 & 31) as u8;
+    let g = (color >> 5 & 63) as u8;
+    let b = (color & 31) as u8;
+    [r << 3 | r >> 2, g << 2 | g >> 4, b << 3 | b >> 2, 255]
+}
+
+fn mix(a: [u8; 4], b: [u8; 4], f: impl Fn(u32, u32) -> u32) -> [u8; 4] {
+    [0, 1, 2, 3].map(|c| f(a[c] as u32, b[c] as u32) as u8)
+}
+
+// BC1 blocks are two RGB565 endpoints followed by 2 bit indices. When the first endpoint isn't
+// greater than the second the block has a single midpoint and a transparent black, which BC2 and
+// BC3 don't use.
+fn decode_bc1_colors(block: &[u8], punch_through: bool, out: &mut Block) {
+    let color0 = u16::from_le_bytes([block[0], block[1]]);
+    let color1 = u16::from_le_bytes([block[2], block[3]]);
+    let (c0, c1) = (rgb565(color0), rgb565(color1));
+    let palette = if color0 > color1 || !punch_through {
+        [
+            c0,
+            c1,
+            mix(c0, c1, |a, b| (2 * a + b) / 3),
+            mix(c0, c1, |a, b| (a + 2 * b) / 3),
+        ]
+    } else {
+        [c0, c1, mix(c0, c1, |a, b| (a + b) / 2), [0; 4]]
+    };
+    let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+    for (i, texel) in out.iter_mut().enumerate() {
+        *texel = palette[(indices >> (2 * i) & 3) as usize];
+    }
+}
+
+fn decode_bc1(block: &[u8], out: &mut Block) {
+    decode_bc1_colors(block, true, out);
+}
+
+fn decode_bc2(block: &[u8], out: &mut Block) {
+    decode_bc1_colors(&block[8..], false, out);
+    let alpha = u64::from_le_bytes(block[..8].try_into().unwrap());
+    for (i, texel) in out.iter_mut().enumerate() {
+        texel[3] = (alpha >> (4 * i) & 15) as u8 * 17;
+    }
+}
+
+// The single channel blocks of BC3 alpha, BC4 and BC5: two 8 bit endpoints followed by 3 bit
+// indices. With the first endpoint greater there are six interpolated values, otherwise four
+// plus 0 and 255.
+fn decode_channel(block: &[u8], channel: usize, out: &mut Block) {
+    let (a0, a1) = (block[0] as u32, block[1] as u32);
+    let mut palette = [a0, a1, 0, 0, 0, 0, 0, 255];
+    if a0 > a1 {
+        for (i, value) in palette.iter_mut().enumerate().skip(2) {
+            *value = ((8 - i as u32) * a0 + (i as u32 - 1) * a1) / 7;
+        }
+    } else {
+        for (i, value) in palette.iter_mut().enumerate().take(6).skip(2) {
+            *value = ((6 - i as u32) * a0 + (i as u32 - 1) * a1) / 5;
+        }
+    }
+    let mut indices = [0; 8];
+    indices[..6].copy_from_slice(&block[2..8]);
+    let indices = u64::from_le_bytes(indices);
+    for (i, texel) in out.iter_mut().enumerate() {
+        texel[channel] = palette[(indices >> (3 * i) & 7) as usize] as u8;
+    }
+}
+
+fn decode_bc3(block: &[u8], out: &mut Block) {
+    decode_bc1_colors(&block[8..], false, out);
+    decode_channel(&block[..8], 3, out);
+}
+
+fn decode_bc4(block: &[u8], out: &mut Block) {
+    *out = [[0, 0, 0, 255]; 16];
+    decode_channel(block, 0, out);
+}
+
+fn decode_bc5(block: &[u8], out: &mut Block) {
+    *out = [[0, 0, 0, 255]; 16];
+    decode_channel(&block[..8], 0, out);
+    decode_channel(&block[8..], 1, out);
+}
+
+struct Bc7Mode {
+    subsets: usize,
+    partition_bits: u32,
+    rotation_bits: u32,
+    index_selection_bits: u32,
+    color_bits: u32,
+    alpha_bits: u32,
+    endpoint_pbits: bool,
+    shared_pbits: bool,
+    index_bits: u32,
+    // Modes 4 and 5 index color and alpha separately.
+    secondary_index_bits: u32,
+}
+
+#[rustfmt::skip]
+const BC7_MODES: [Bc7Mode; 8] = [
+    Bc7Mode { subsets: 3, partition_bits: 4, rotation_bits: 0, index_selection_bits: 0, color_bits: 4, alpha_bits: 0, endpoint_pbits: true, shared_pbits: false, index_bits: 3, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 2, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 6, alpha_bits: 0, endpoint_pbits: false, shared_pbits: true, index_bits: 3, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 3, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 5, alpha_bits: 0, endpoint_pbits: false, shared_pbits: false, index_bits: 2, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 2, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 7, alpha_bits: 0, endpoint_pbits: true, shared_pbits: false, index_bits: 2, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 1, partition_bits: 0, rotation_bits: 2, index_selection_bits: 1, color_bits: 5, alpha_bits: 6, endpoint_pbits: false, shared_pbits: false, index_bits: 2, secondary_index_bits: 3 },
+    Bc7Mode { subsets: 1, partition_bits: 0, rotation_bits: 2, index_selection_bits: 0, color_bits: 7, alpha_bits: 8, endpoint_pbits: false, shared_pbits: false, index_bits: 2, secondary_index_bits: 2 },
+    Bc7Mode { subsets: 1, partition_bits: 0, rotation_bits: 0, index_selection_bits: 0, color_bits: 7, alpha_bits: 7, endpoint_pbits: true, shared_pbits: false, index_bits: 4, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 2, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 5, alpha_bits: 5, endpoint_pbits: true, shared_pbits: false, index_bits: 2, secondary_index_bits: 0 },
+];
+
+// Subset of each texel for the two subset partitions, one bit per texel.
+#[rustfmt::skip]
+const BC7_PARTITIONS_2: [u16; 64] = [
+    0xcccc, 0x8888, 0xeeee, 0xecc8, 0xc880, 0xfeec, 0xfec8, 0xec80,
+    0xc800, 0xffec, 0xfe80, 0xe800, 0xffe8, 0xff00, 0xfff0, 0xf000,
+    0xf710, 0x008e, 0x7100, 0x08ce, 0x008c, 0x7310, 0x3100, 0x8cce,
+    0x088c, 0x3110, 0x6666, 0x366c, 0x17e8, 0x0ff0, 0x718e, 0x399c,
+    0xaaaa, 0xf0f0, 0x5a5a, 0x33cc, 0x3c3c, 0x55aa, 0x9696, 0xa55a,
+    0x73ce, 0x13c8, 0x324c, 0x3bdc, 0x6996, 0xc33c, 0x9966, 0x0660,
+    0x0272, 0x04e4, 0x4e40, 0x2720, 0xc936, 0x936c, 0x39c6, 0x639c,
+    0x9336, 0x9cc6, 0x817e, 0xe718, 0xccf0, 0x0fcc, 0x7744, 0xee22,
+];
+
+// Subset of each texel for the three subset partitions, two bits per texel.
+#[rustfmt::skip]
+const BC7_PARTITIONS_3: [u32; 64] = [
+    0xaa685050, 0x6a5a5040, 0x5a5a4200, 0x5450a0a8, 0xa5a50000, 0xa0a05050, 0x5555a0a0, 0x5a5a5050,
+    0xaa550000, 0xaa555500, 0xaaaa5500, 0x90909090, 0x94949494, 0xa4a4a4a4, 0xa9a59450, 0x2a0a4250,
+    0xa5945040, 0x0a425054, 0xa5a5a500, 0x55a0a0a0, 0xa8a85454, 0x6a6a4040, 0xa4a45000, 0x1a1a0500,
+    0x0050a4a4, 0xaaa59090, 0x14696914, 0x69691400, 0xa08585a0, 0xaa821414, 0x50a4a450, 0x6a5a0200,
+    0xa9a58000, 0x5090a0a8, 0xa8a09050, 0x24242424, 0x00aa5500, 0x24924924, 0x24499224, 0x50a50a50,
+    0x500aa550, 0xaaaa4444, 0x66660000, 0xa5a0a5a0, 0x50a050a0, 0x69286928, 0x44aaaa44, 0x66666600,
+    0xaa444444, 0x54a854a8, 0x95809580, 0x96969600, 0xa85454a8, 0x80959580, 0xaa141414, 0x96960000,
+    0xaaaa1414, 0xa05050a0, 0xa0a5a5a0, 0x96000000, 0x40804080, 0xa9a8a9a8, 0xaaaaaa44, 0x2a4a5254,
+];
+
+// The texel whose index has an implied zero top bit, for the second subset of two subset
+// partitions and the second and third subsets of three subset partitions. The first subset's is
+// always texel 0.
+#[rustfmt::skip]
+const BC7_ANCHORS_2: [u8; 64] = [
+    15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15,
+    15,  2,  8,  2,  2,  8,  8, 15,  2,  8,  2,  2,  8,  8,  2,  2,
+    15, 15,  6,  8,  2,  8, 15, 15,  2,  8,  2,  2,  2, 15, 15,  6,
+     6,  2,  6,  8, 15, 15,  2,  2, 15, 15, 15, 15, 15,  2,  2, 15,
+];
+#[rustfmt::skip]
+const BC7_ANCHORS_3: [[u8; 64]; 2] = [
+    [
+         3,  3, 15, 15,  8,  3, 15, 15,  8,  8,  6,  6,  6,  5,  3,  3,
+         3,  3,  8, 15,  3,  3,  6, 10,  5,  8,  8,  6,  8,  5, 15, 15,
+         8, 15,  3,  5,  6, 10,  8, 15, 15,  3, 15,  5, 15, 15, 15, 15,
+         3, 15,  5,  5,  5,  8,  5, 10,  5, 10,  8, 13, 15, 12,  3,  3,
+    ],
+    [
+        15,  8,  8,  3, 15, 15,  3,  8, 15, 15, 15, 15, 15, 15, 15,  8,
+        15,  8, 15,  3, 15,  8, 15,  8,  3, 15,  6, 10, 15, 15, 10,  8,
+        15,  3, 15, 10, 10,  8,  9, 10,  6, 15,  8, 15,  3,  6,  6,  8,
+        15,  3, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15,  3, 15, 15,  8,
+    ],
+];
+
+const BC7_WEIGHTS_2: [u32; 4] = [0, 21, 43, 64];
+const BC7_WEIGHTS_3: [u32; 8] = [0, 9, 18, 27, 37, 46, 55, 64];
+const BC7_WEIGHTS_4: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+struct Bits(u128);
+
+impl Bits {
+    fn read(&mut self, count: u32) -> u32 {
+        let value = (self.0 & ((1 << count) - 1)) as u32;
+        self.0 >>= count;
+        value
+    }
+}
+
+fn bc7_weight(index_bits: u32, index: u32) -> u32 {
+    match index_bits {
+        2 => BC7_WEIGHTS_2[index as usize],
+        3 => BC7_WEIGHTS_3[index as usize],
+        _ => BC7_WEIGHTS_4[index as usize],
+    }
+}
+
+fn decode_bc7(block: &[u8], out: &mut Block) {
+    let mut bits = Bits(u128::from_le_bytes(block.try_into().unwrap()));
+    // The mode is the number of zero bits before the first one.
+    let Some(mode) = (0..8).find(|_| bits.read(1) == 1) else {
+        // Reserved, decodes to transparent black.
+        *out = [[0; 4]; 16];
+        return;
+    };
+    let mode = &BC7_MODES[mode];
+    let partition = bits.read(mode.partition_bits) as usize;
+    let rotation = bits.read(mode.rotation_bits);
+    let index_selection = bits.read(mode.index_selection_bits);
+
+    // Endpoints are stored channel by channel, each channel subset by subset.
+    let mut endpoints = [[[0u32; 4]; 2]; 3];
+    for channel in 0..4 {
+        let channel_bits = if channel < 3 {
+            mode.color_bits
+        } else {
+            mode.alpha_bits
+        };
+        for subset in endpoints.iter_mut().take(mode.subsets) {
+            for endpoint in subset.iter_mut() {
+                endpoint[channel] = bits.read(channel_bits);
+            }
+        }
+    }
+    let mut pbits = [[0; 2]; 3];
+    for subset in pbits.iter_mut().take(mode.subsets) {
+        if mode.endpoint_pbits {
+            *subset = [bits.read(1), bits.read(1)];
+        } else if mode.shared_pbits {
+            *subset = [bits.read(1); 2];
+        }
+    }
+    let has_pbits = mode.endpoint_pbits || mode.shared_pbits;
+    for (subset, subset_pbits) in endpoints.iter_mut().zip(pbits) {
+        for (endpoint, pbit) in subset.iter_mut().zip(subset_pbits) {
+            for (channel, value) in endpoint.iter_mut().enumerate() {
+                let mut channel_bits = if channel < 3 {
+                    mode.color_bits
+                } else {
+                    mode.alpha_bits
+                };
+                if channel_bits == 0 {
+                    *value = 255;
+                    continue;
+                }
+                if has_pbits {
+                    *value = *value << 1 | pbit;
+                    channel_bits += 1;
+                }
+                // Widen to 8 bits by repeating the top bits.
+                *value <<= 8 - channel_bits;
+                *value |= *value >> channel_bits;
+            }
+        }
+    }
+
+    let subset_of = |texel: usize| match mode.subsets {
+        1 => 0,
+        2 => (BC7_PARTITIONS_2[partition] >> texel & 1) as usize,
+        _ => (BC7_PARTITIONS_3[partition] >> (2 * texel) & 3) as usize,
+    };
+    let is_anchor = |texel: usize| match mode.subsets {
+        _ if texel == 0 => true,
+        1 => false,
+        2 => texel == BC7_ANCHORS_2[partition] as usize,
+        _ => {
+            texel == BC7_ANCHORS_3[0][partition] as usize
+                || texel == BC7_ANCHORS_3[1][partition] as usize
+        }
+    };
+    let mut indices = [0; 16];
+    for (texel, index) in indices.iter_mut().enumerate() {
+        *index = bits.read(mode.index_bits - is_anchor(texel) as u32);
+    }
+    let mut secondary_indices = [0; 16];
+    if mode.secondary_index_bits > 0 {
+        for (texel, index) in secondary_indices.iter_mut().enumerate() {
+            *index = bits.read(mode.secondary_index_bits - (texel == 0) as u32);
+        }
+    }
+
+    for (texel, out) in out.iter_mut().enumerate() {
+        let [e0, e1] = endpoints[subset_of(texel)];
+        let mut color_weight = bc7_weight(mode.index_bits, indices[texel]);
+        let mut alpha_weight = color_weight;
+        if mode.secondary_index_bits > 0 {
+            alpha_weight = bc7_weight(mode.secondary_index_bits, secondary_indices[texel]);
+            if index_selection == 1 {
+                color_weight = alpha_weight;
+                alpha_weight = bc7_weight(mode.index_bits, indices[texel]);
+            }
+        }
+        for channel in 0..4 {
+            let weight = if channel < 3 {
+                color_weight
+            } else {
+                alpha_weight
+            };
+            out[channel] = (((64 - weight) * e0[channel] + weight * e1[channel] + 32) >> 6) as u8;
+        }
+        match rotation {
+            1 => out.swap(0, 3),
+            2 => out.swap(1, 3),
+            3 => out.swap(2, 3),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wgpu::TextureFormat;
+
+    fn texels(format: TextureFormat, block: &[u8]) -> Vec<[u8; 4]> {
+        decode(format, block, 4, 4)
+            .unwrap()
+            .chunks_exact(4)
+            .map(|texel| texel.try_into().unwrap())
+            .collect()
+    }
+
+    // Red and blue endpoints with the first four texels using indices 0, 1, 2 and 3.
+    const BC1_BLOCK: [u8; 8] = [0x00, 0xf8, 0x1f, 0x00, 0b1110_0100, 0, 0, 0];
+
+    #[test]
+    fn decodes_bc1() {
+        let texels = texels(TextureFormat::Bc1RgbaUnorm, &BC1_BLOCK);
+        assert_eq!(
+            texels[..4],
+            [
+                [255, 0, 0, 255],
+                [0, 0, 255, 255],
+                [170, 0, 85, 255],
+                [85, 0, 170, 255]
+            ]
+        );
+        assert!(texels[4..].iter().all(|&texel| texel == [255, 0, 0, 255]));
+    }
+
+    #[test]
+    fn decodes_bc1_punch_through_alpha() {
+        // With the endpoints swapped index 2 is the midpoint and 3 transparent black.
+        let block = [0x1f, 0x00, 0x00, 0xf8, 0b1110_0100, 0, 0, 0];
+        let texels = texels(TextureFormat::Bc1RgbaUnorm, &block);
+        assert_eq!(
+            texels[..4],
+            [
+                [0, 0, 255, 255],
+                [255, 0, 0, 255],
+                [127, 0, 127, 255],
+                [0, 0, 0, 0]
+            ]
+        );
+    }
+
+    #[test]
+    fn decodes_bc2() {
+        let mut block = [0; 16];
+        block[0] = 0x8f;
+        block[8..].copy_from_slice(&BC1_BLOCK);
+        let texels = texels(TextureFormat::Bc2RgbaUnorm, &block);
+        assert_eq!(texels[0], [255, 0, 0, 255]);
+        assert_eq!(texels[1], [0, 0, 255, 136]);
+        assert_eq!(texels[2], [170, 0, 85, 0]);
+    }
+
+    #[test]
+    fn decodes_bc3() {
+        // Alpha endpoints 255 and 0 with the first texels using indices 0, 1 and 2. BC3 colors
+        // never use BC1's transparent black.
+        let mut block = [255, 0, 0b1000_1000, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        block[8..].copy_from_slice(&[0x1f, 0x00, 0x00, 0xf8, 0b1110_0100, 0, 0, 0]);
+        let texels = texels(TextureFormat::Bc3RgbaUnorm, &block);
+        assert_eq!(texels[0], [0, 0, 255, 255]);
+        assert_eq!(texels[1], [255, 0, 0, 0]);
+        assert_eq!(texels[2], [85, 0, 170, 218]);
+        assert_eq!(texels[3], [170, 0, 85, 255]);
+    }
+
+    #[test]
+    fn decodes_bc4() {
+        // With the first endpoint not greater, indices 6 and 7 are 0 and 255.
+        let indices: u64 = 2 | 6 << 3 | 7 << 6 | 5 << 9;
+        let mut block = [0, 255, 0, 0, 0, 0, 0, 0];
+        block[2..].copy_from_slice(&indices.to_le_bytes()[..6]);
+        let texels = texels(TextureFormat::Bc4RUnorm, &block);
+        assert_eq!(
+            texels[..5],
+            [
+                [51, 0, 0, 255],
+                [0, 0, 0, 255],
+                [255, 0, 0, 255],
+                [204, 0, 0, 255],
+                [0, 0, 0, 255]
+            ]
+        );
+    }
+
+    #[test]
+    fn decodes_bc5() {
+        let block = [10, 20, 0, 0, 0, 0, 0, 0, 200, 100, 1, 0, 0, 0, 0, 0];
+        let texels = texels(TextureFormat::Bc5RgUnorm, &block);
+        assert_eq!(texels[0], [10, 100, 0, 255]);
+        assert_eq!(texels[1], [10, 200, 0, 255]);
+    }
+
+    #[test]
+    fn decodes_bc7_mode_6() {
+        // Mode 6 is one subset with 7 bit RGBA endpoints, a p-bit each and 4 bit indices.
+        let fields: [(u128, u32); 14] = [
+            (0b100_0000, 7),
+            (0x7f, 7),
+            (0, 7),
+            (0, 7),
+            (0, 7),
+            (0, 7),
+            (0x7f, 7),
+            (0x7f, 7),
+            (0x7f, 7),
+            (1, 1),
+            (0, 1),
+            // The anchor texel's index drops its top bit.
+            (0, 3),
+            (15, 4),
+            (8, 4),
+        ];
+        let mut bits = 0u128;
+        let mut offset = 0;
+        for (value, count) in fields {
+            bits |= value << offset;
+            offset += count;
+        }
+        let texels = texels(TextureFormat::Bc7RgbaUnorm, &bits.to_le_bytes());
+        assert_eq!(texels[0], [255, 1, 1, 255]);
+        assert_eq!(texels[1], [0, 0, 254, 254]);
+        assert_eq!(texels[2], [120, 0, 135, 254]);
+        assert_eq!(texels[3], [255, 1, 1, 255]);
+    }
+
+    #[test]
+    fn decodes_reserved_bc7_mode_as_transparent_black() {
+        let texels = texels(TextureFormat::Bc7RgbaUnorm, &[0; 16]);
+        assert!(texels.iter().all(|&texel| texel == [0; 4]));
+    }
+
+    #[test]
+    fn crops_blocks_overhanging_the_image() {
+        let rgba = decode(TextureFormat::Bc1RgbaUnorm, &BC1_BLOCK, 3, 1).unwrap();
+        assert_eq!(rgba, [255, 0, 0, 255, 0, 0, 255, 255, 170, 0, 85, 255]);
+    }
+
+    #[test]
+    fn has_no_decoder_for_signed_formats() {
+        assert!(decode(TextureFormat::Bc4RSnorm, &[0; 8], 4, 4).is_none());
+    }
+}
@@ -0,0 +1,285 @@
+use wgpu::{Extent3d, TextureFormat};
+
+use super::{layer_byte_size, read_u32, too_large, truncated, unsupported, CompressedImage};
+use crate::error::ResourceError;
+
+pub const MAGIC: &[u8] = b"DDS ";
+
+// The magic and the 124 byte DDS_HEADER, followed by a DDS_HEADER_DXT10 when the pixel format's
+// four character code is "DX10".
+const HEADER_SIZE: usize = 128;
+const DX10_HEADER_SIZE: usize = 20;
+
+const HEADER_MIPMAPCOUNT: u32 = 0x2_0000;
+const PIXEL_FORMAT_FOURCC: u32 = 0x4;
+const PIXEL_FORMAT_RGB: u32 = 0x40;
+const CAPS2_CUBEMAP: u32 = 0x200;
+const CAPS2_VOLUME: u32 = 0x20_0000;
+const DX10_MISC_TEXTURECUBE: u32 = 0x4;
+const DX10_DIMENSION_TEXTURE3D: u32 = 4;
+
+pub fn parse(path: &str, bytes: &[u8], linear: bool) -> Result<CompressedImage, ResourceError> {
+    if bytes.len() < HEADER_SIZE {
+        return Err(truncated(path));
+    }
+    let field = |offset: usize| read_u32(bytes, offset).unwrap();
+    let (flags, height, width) = (field(8), field(12), field(16));
+    // Writers don't always clear the mip count when the flag says there is none.
+    let mip_level_count = if flags & HEADER_MIPMAPCOUNT != 0 {
+        field(28).max(1)
+    } else {
+        1
+    };
+    let (pixel_format_flags, fourcc, bit_count) = (field(80), field(84), field(88));
+    let masks = [field(92), field(96), field(100), field(104)];
+    let caps2 = field(112);
+
+    if caps2 & CAPS2_VOLUME != 0 {
+        return Err(unsupported(path, "DDS volume textures"));
+    }
+    let (format, layers, cube, data_offset) = if pixel_format_flags & PIXEL_FORMAT_FOURCC != 0
+        && fourcc == u32::from_le_bytes(*b"DX10")
+    {
+        if bytes.len() < HEADER_SIZE + DX10_HEADER_SIZE {
+            return Err(truncated(path));
+        }
+        let [dxgi_format, dimension, misc, array_size] =
+            [0, 4, 8, 12].map(|offset| field(HEADER_SIZE + offset));
+        if dimension == DX10_DIMENSION_TEXTURE3D {
+            return Err(unsupported(path, "DDS volume textures"));
+        }
+        let format = dxgi_format_to_wgpu(dxgi_format)
+            .ok_or_else(|| unsupported(path, format!("DXGI format {dxgi_format}")))?;
+        let cube = misc & DX10_MISC_TEXTURECUBE != 0;
+        let layers = array_size
+            .max(1)
+            .checked_mul(if cube { 6 } else { 1 })
+            .ok_or_else(|| too_large(path))?;
+        (format, layers, cube, HEADER_SIZE + DX10_HEADER_SIZE)
+    } else {
+        // Older files leave sRGB unspecified.
+        let format = legacy_format_to_wgpu(pixel_format_flags, fourcc, bit_count, masks)
+            .ok_or_else(|| unsupported(path, "DDS pixel format"))?;
+        let format = if linear {
+            format
+        } else {
+            format.add_srgb_suffix()
+        };
+        let cube = caps2 & CAPS2_CUBEMAP != 0;
+        (format, if cube { 6 } else { 1 }, cube, HEADER_SIZE)
+    };
+
+    let size = Extent3d {
+        width,
+        height,
+        depth_or_array_layers: layers,
+    };
+    if mip_level_count > size.max_mips(wgpu::TextureDimension::D2) {
+        return Err(ResourceError::decode(
+            path,
+            format!("{mip_level_count} mip levels is more than a {width}x{height} texture has"),
+        ));
+    }
+    // DDS stores each layer with its whole mip chain before the next layer, regroup them by level.
+    let mut levels = vec![Vec::new(); mip_level_count as usize];
+    let mut offset = data_offset;
+    for _ in 0..layers {
+        for (mip_level, level) in levels.iter_mut().enumerate() {
+            let level_size = size.mip_level_size(mip_level as u32, wgpu::TextureDimension::D2);
+            let length = layer_byte_size(format, level_size.width, level_size.height)
+                .ok_or_else(|| too_large(path))?;
+            let data = offset
+                .checked_add(length)
+                .and_then(|end| bytes.get(offset..end))
+                .ok_or_else(|| truncated(path))?;
+            level.extend_from_slice(data);
+            offset += length;
+        }
+    }
+
+    Ok(CompressedImage {
+        format,
+        size,
+        cube,
+        levels,
+        generate_mipmaps: false,
+    })
+}
+
+fn legacy_format_to_wgpu(
+    pixel_format_flags: u32,
+    fourcc: u32,
+    bit_count: u32,
+    masks: [u32; 4],
+) -> Option<TextureFormat> {
+    use TextureFormat::*;
+    if pixel_format_flags & PIXEL_FORMAT_FOURCC != 0 {
+        return Some(match &fourcc.to_le_bytes() {
+            b"DXT1" => Bc1RgbaUnorm,
+            b"DXT2" | b"DXT3" => Bc2RgbaUnorm,
+            b"DXT4" | b"DXT5" => Bc3RgbaUnorm,
+            b"ATI1" | b"BC4U" => Bc4RUnorm,
+            b"BC4S" => Bc4RSnorm,
+            b"ATI2" | b"BC5U" => Bc5RgUnorm,
+            b"BC5S" => Bc5RgSnorm,
+            // D3DFMT_A16B16G16R16F and D3DFMT_A32B32G32R32F.
+            [113, 0, 0, 0] => Rgba16Float,
+            [116, 0, 0, 0] => Rgba32Float,
+            _ => return None,
+        });
+    }
+    if pixel_format_flags & PIXEL_FORMAT_RGB != 0 && bit_count == 32 {
+        return match masks {
+            [0xff, 0xff00, 0xff_0000, _] => Some(Rgba8Unorm),
+            [0xff_0000, 0xff00, 0xff, _] => Some(Bgra8Unorm),
+            _ => None,
+        };
+    }
+    None
+}
+
+fn dxgi_format_to_wgpu(dxgi_format: u32) -> Option<TextureFormat> {
+    use TextureFormat::*;
+    Some(match dxgi_format {
+        2 => Rgba32Float,
+        10 => Rgba16Float,
+        11 => Rgba16Unorm,
+        28 => Rgba8Unorm,
+        29 => Rgba8UnormSrgb,
+        87 => Bgra8Unorm,
+        91 => Bgra8UnormSrgb,
+        71 => Bc1RgbaUnorm,
+        72 => Bc1RgbaUnormSrgb,
+        74 => Bc2RgbaUnorm,
+        75 => Bc2RgbaUnormSrgb,
+        77 => Bc3RgbaUnorm,
+        78 => Bc3RgbaUnormSrgb,
+        80 => Bc4RUnorm,
+        81 => Bc4RSnorm,
+        83 => Bc5RgUnorm,
+        84 => Bc5RgSnorm,
+        95 => Bc6hRgbUfloat,
+        96 => Bc6hRgbFloat,
+        98 => Bc7RgbaUnorm,
+        99 => Bc7RgbaUnormSrgb,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER_CAPS: u32 = 0x1;
+
+    /// A DDS header, the DX10 extension when `dx10` is given, then `data`.
+    fn dds(
+        flags: u32,
+        (width, height, mip_level_count): (u32, u32, u32),
+        fourcc: &[u8; 4],
+        dx10: Option<[u32; 4]>,
+        data: &[u8],
+    ) -> Vec<u8> {
+        let mut bytes = vec![0; HEADER_SIZE];
+        let mut field = |offset: usize, value: u32| {
+            bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+        };
+        field(4, 124);
+        field(8, flags);
+        field(12, height);
+        field(16, width);
+        field(28, mip_level_count);
+        field(80, PIXEL_FORMAT_FOURCC);
+        field(84, u32::from_le_bytes(*fourcc));
+        bytes[..4].copy_from_slice(MAGIC);
+        if let Some(dx10) = dx10 {
+            for value in dx10.into_iter().chain([0]) {
+                bytes.extend(value.to_le_bytes());
+            }
+        }
+        bytes.extend(data);
+        bytes
+    }
+
+    #[test]
+    fn regroups_layers_by_mip_level() {
+        // Two layers of an 8x8 BC1 texture, each with its 4, 1 and 1 blocks.
+        let layer: Vec<u8> = (0..6).flat_map(|block| [block; 8]).collect();
+        let data = [layer.clone(), layer.iter().map(|b| b + 10).collect()].concat();
+        let bytes = dds(
+            HEADER_CAPS | HEADER_MIPMAPCOUNT,
+            (8, 8, 3),
+            b"DX10",
+            Some([71, 3, 0, 2]),
+            &data,
+        );
+        let image = parse("test.dds", &bytes, true).unwrap();
+        assert_eq!(image.format, TextureFormat::Bc1RgbaUnorm);
+        assert_eq!(image.size.depth_or_array_layers, 2);
+        assert_eq!(image.levels.len(), 3);
+        assert_eq!(image.levels[0], [&data[..32], &data[48..80]].concat());
+        assert_eq!(image.levels[1], [[4; 8], [14; 8]].concat());
+        assert_eq!(image.levels[2], [[5; 8], [15; 8]].concat());
+    }
+
+    #[test]
+    fn ignores_mip_count_without_its_flag() {
+        let bytes = dds(HEADER_CAPS, (4, 4, 3), b"DXT1", None, &[0; 8]);
+        let image = parse("test.dds", &bytes, true).unwrap();
+        assert_eq!(image.levels.len(), 1);
+    }
+
+    #[test]
+    fn legacy_formats_follow_linear() {
+        let bytes = dds(HEADER_CAPS, (4, 4, 1), b"DXT5", None, &[0; 16]);
+        let linear = parse("test.dds", &bytes, true).unwrap();
+        let srgb = parse("test.dds", &bytes, false).unwrap();
+        assert_eq!(linear.format, TextureFormat::Bc3RgbaUnorm);
+        assert_eq!(srgb.format, TextureFormat::Bc3RgbaUnormSrgb);
+    }
+
+    #[test]
+    fn parses_cube_maps() {
+        let bytes = dds(
+            HEADER_CAPS,
+            (4, 4, 1),
+            b"DX10",
+            Some([98, 3, DX10_MISC_TEXTURECUBE, 1]),
+            &[0; 6 * 16],
+        );
+        let image = parse("test.dds", &bytes, true).unwrap();
+        assert!(image.cube);
+        assert_eq!(image.size.depth_or_array_layers, 6);
+    }
+
+    #[test]
+    fn rejects_bad_headers() {
+        let parse = |flags, size, fourcc, dx10, data: &[u8]| {
+            parse("test.dds", &dds(flags, size, fourcc, dx10, data), true)
+        };
+        // Truncated data, more mip levels than a 4x4 texture has and a cube array too large to
+        // count.
+        assert!(matches!(
+            parse(HEADER_CAPS, (4, 4, 1), b"DXT1", None, &[0; 7]),
+            Err(ResourceError::Decode { .. })
+        ));
+        assert!(matches!(
+            parse(HEADER_MIPMAPCOUNT, (4, 4, 4), b"DXT1", None, &[0; 64]),
+            Err(ResourceError::Decode { .. })
+        ));
+        assert!(matches!(
+            parse(
+                HEADER_CAPS,
+                (4, 4, 1),
+                b"DX10",
+                Some([71, 3, DX10_MISC_TEXTURECUBE, u32::MAX]),
+                &[]
+            ),
+            Err(ResourceError::Decode { .. })
+        ));
+        assert!(matches!(
+            parse(HEADER_CAPS, (4, 4, 1), b"ETC2", None, &[]),
+            Err(ResourceError::UnsupportedFormat { .. })
+        ));
+    }
+}
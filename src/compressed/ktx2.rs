@@ -0,0 +1,254 @@
+use wgpu::{AstcBlock, AstcChannel, Extent3d, TextureFormat};
+
+use super::{
+    layer_byte_size, read_u32, read_u64, too_large, truncated, unsupported, CompressedImage,
+};
+use crate::error::ResourceError;
+
+pub const IDENTIFIER: [u8; 12] = [
+    0xab, b'K', b'T', b'X', b' ', b'2', b'0', 0xbb, b'\r', b'\n', 0x1a, b'\n',
+];
+
+// The header is nine u32 fields after the identifier, followed by the data format descriptor,
+// key/value and supercompression offsets, then one (offset, length, uncompressed length) u64
+// triple per level.
+const HEADER_SIZE: usize = 80;
+const LEVEL_INDEX_ENTRY_SIZE: usize = 24;
+
+pub fn parse(path: &str, bytes: &[u8]) -> Result<CompressedImage, ResourceError> {
+    if bytes.len() < HEADER_SIZE {
+        return Err(truncated(path));
+    }
+    let field = |i: usize| read_u32(bytes, IDENTIFIER.len() + 4 * i).unwrap();
+    let [vk_format, _type_size, width, height, depth, layer_count, face_count, level_count, supercompression] =
+        [0, 1, 2, 3, 4, 5, 6, 7, 8].map(field);
+
+    if supercompression != 0 {
+        return Err(unsupported(
+            path,
+            format!("KTX2 supercompression scheme {supercompression}"),
+        ));
+    }
+    if depth > 0 {
+        return Err(unsupported(path, "3D KTX2 textures"));
+    }
+    let format = vk_format_to_wgpu(vk_format)
+        .ok_or_else(|| unsupported(path, format!("VkFormat {vk_format}")))?;
+
+    let size = Extent3d {
+        width,
+        height: height.max(1),
+        depth_or_array_layers: layer_count
+            .max(1)
+            .checked_mul(face_count.max(1))
+            .ok_or_else(|| too_large(path))?,
+    };
+    if level_count > size.max_mips(wgpu::TextureDimension::D2) {
+        return Err(ResourceError::decode(
+            path,
+            format!("{level_count} mip levels is more than a {width}x{height} texture has"),
+        ));
+    }
+    // A level count of 0 asks the loader to generate the mip chain, only the base level is stored.
+    let mut levels = Vec::new();
+    for mip_level in 0..level_count.max(1) {
+        let entry = HEADER_SIZE + mip_level as usize * LEVEL_INDEX_ENTRY_SIZE;
+        let (Some(offset), Some(length)) = (read_u64(bytes, entry), read_u64(bytes, entry + 8))
+        else {
+            return Err(truncated(path));
+        };
+        let level_size = size.mip_level_size(mip_level, wgpu::TextureDimension::D2);
+        let expected = layer_byte_size(format, level_size.width, level_size.height)
+            .and_then(|layer| layer.checked_mul(size.depth_or_array_layers as usize))
+            .ok_or_else(|| too_large(path))?;
+        let end = offset.checked_add(length).ok_or_else(|| truncated(path))?;
+        let level = usize::try_from(offset)
+            .ok()
+            .zip(usize::try_from(end).ok())
+            .and_then(|(offset, end)| bytes.get(offset..end))
+            .filter(|level| level.len() >= expected)
+            .ok_or_else(|| truncated(path))?;
+        // Layers are stored one after another, each with its cube faces, the order wgpu expects.
+        levels.push(level[..expected].to_vec());
+    }
+
+    Ok(CompressedImage {
+        format,
+        size,
+        cube: face_count == 6,
+        levels,
+        generate_mipmaps: level_count == 0,
+    })
+}
+
+fn vk_format_to_wgpu(vk_format: u32) -> Option<TextureFormat> {
+    use TextureFormat::*;
+    Some(match vk_format {
+        37 => Rgba8Unorm,
+        43 => Rgba8UnormSrgb,
+        44 => Bgra8Unorm,
+        50 => Bgra8UnormSrgb,
+        91 => Rgba16Unorm,
+        97 => Rgba16Float,
+        109 => Rgba32Float,
+        // The RGB variants of BC1 decode with an opaque alpha, which the RGBA ones do as well
+        // unless a block uses its transparent black.
+        131 | 133 => Bc1RgbaUnorm,
+        132 | 134 => Bc1RgbaUnormSrgb,
+        135 => Bc2RgbaUnorm,
+        136 => Bc2RgbaUnormSrgb,
+        137 => Bc3RgbaUnorm,
+        138 => Bc3RgbaUnormSrgb,
+        139 => Bc4RUnorm,
+        140 => Bc4RSnorm,
+        141 => Bc5RgUnorm,
+        142 => Bc5RgSnorm,
+        143 => Bc6hRgbUfloat,
+        144 => Bc6hRgbFloat,
+        145 => Bc7RgbaUnorm,
+        146 => Bc7RgbaUnormSrgb,
+        147 => Etc2Rgb8Unorm,
+        148 => Etc2Rgb8UnormSrgb,
+        149 => Etc2Rgb8A1Unorm,
+        150 => Etc2Rgb8A1UnormSrgb,
+        151 => Etc2Rgba8Unorm,
+        152 => Etc2Rgba8UnormSrgb,
+        153 => EacR11Unorm,
+        154 => EacR11Snorm,
+        155 => EacRg11Unorm,
+        156 => EacRg11Snorm,
+        // The LDR ASTC formats, a linear and an sRGB one per block size.
+        157..=184 => {
+            let block = [
+                AstcBlock::B4x4,
+                AstcBlock::B5x4,
+                AstcBlock::B5x5,
+                AstcBlock::B6x5,
+                AstcBlock::B6x6,
+                AstcBlock::B8x5,
+                AstcBlock::B8x6,
+                AstcBlock::B8x8,
+                AstcBlock::B10x5,
+                AstcBlock::B10x6,
+                AstcBlock::B10x8,
+                AstcBlock::B10x10,
+                AstcBlock::B12x10,
+                AstcBlock::B12x12,
+            ][(vk_format - 157) as usize / 2];
+            let channel = if vk_format % 2 == 1 {
+                AstcChannel::Unorm
+            } else {
+                AstcChannel::UnormSrgb
+            };
+            Astc { block, channel }
+        }
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RGBA8_UNORM: u32 = 37;
+    const BC1_RGBA_UNORM: u32 = 133;
+
+    /// A KTX2 file with `levels` stored one after another behind the level index.
+    fn ktx2(header: [u32; 9], levels: &[Vec<u8>]) -> Vec<u8> {
+        let mut bytes = IDENTIFIER.to_vec();
+        for field in header {
+            bytes.extend(field.to_le_bytes());
+        }
+        bytes.resize(HEADER_SIZE, 0);
+        let mut offset = (HEADER_SIZE + levels.len() * LEVEL_INDEX_ENTRY_SIZE) as u64;
+        for level in levels {
+            let length = level.len() as u64;
+            for value in [offset, length, length] {
+                bytes.extend(value.to_le_bytes());
+            }
+            offset += length;
+        }
+        for level in levels {
+            bytes.extend(level);
+        }
+        bytes
+    }
+
+    #[test]
+    fn parses_mip_chain() {
+        let levels = [vec![1; 4 * 2 * 4], vec![2; 2 * 4], vec![3; 4]];
+        let bytes = ktx2([RGBA8_UNORM, 1, 4, 2, 0, 0, 1, 3, 0], &levels);
+        let image = parse("test.ktx2", &bytes).unwrap();
+        assert_eq!(image.format, TextureFormat::Rgba8Unorm);
+        assert_eq!(
+            image.size,
+            Extent3d {
+                width: 4,
+                height: 2,
+                depth_or_array_layers: 1
+            }
+        );
+        assert!(!image.cube && !image.generate_mipmaps);
+        assert_eq!(image.levels, levels);
+    }
+
+    #[test]
+    fn parses_cube_map_layers() {
+        let bytes = ktx2([BC1_RGBA_UNORM, 1, 4, 4, 0, 2, 6, 1, 0], &[vec![0; 12 * 8]]);
+        let image = parse("test.ktx2", &bytes).unwrap();
+        assert_eq!(image.format, TextureFormat::Bc1RgbaUnorm);
+        assert_eq!(image.size.depth_or_array_layers, 12);
+        assert!(image.cube);
+    }
+
+    #[test]
+    fn level_count_0_asks_for_generated_mipmaps() {
+        let bytes = ktx2([RGBA8_UNORM, 1, 4, 4, 0, 0, 1, 0, 0], &[vec![0; 4 * 4 * 4]]);
+        let image = parse("test.ktx2", &bytes).unwrap();
+        assert!(image.generate_mipmaps);
+        assert_eq!(image.levels.len(), 1);
+    }
+
+    #[test]
+    fn rejects_bad_headers() {
+        let parse = |header, levels: &[Vec<u8>]| parse("test.ktx2", &ktx2(header, levels));
+        assert!(matches!(
+            parse([RGBA8_UNORM, 1, 4, 4, 0, 0, 1, 1, 0], &[vec![0; 63]]),
+            Err(ResourceError::Decode { .. })
+        ));
+        assert!(matches!(
+            parse([RGBA8_UNORM, 1, 4, 4, 0, 0, 1, 4, 0], &[]),
+            Err(ResourceError::Decode { .. })
+        ));
+        assert!(matches!(
+            parse([RGBA8_UNORM, 1, 4, 4, 0, u32::MAX, 6, 1, 0], &[]),
+            Err(ResourceError::Decode { .. })
+        ));
+        assert!(matches!(
+            parse([RGBA8_UNORM, 1, 4, 4, 1, 0, 1, 1, 0], &[]),
+            Err(ResourceError::UnsupportedFormat { .. })
+        ));
+        assert!(matches!(
+            parse([RGBA8_UNORM, 1, 4, 4, 0, 0, 1, 1, 1], &[]),
+            Err(ResourceError::UnsupportedFormat { .. })
+        ));
+    }
+
+    #[test]
+    fn rejects_truncated_headers() {
+        assert!(matches!(
+            parse("test.ktx2", &IDENTIFIER),
+            Err(ResourceError::Decode { .. })
+        ));
+    }
+
+    #[test]
+    fn rejects_level_ranges_past_the_end() {
+        let mut bytes = ktx2([RGBA8_UNORM, 1, 1, 1, 0, 0, 1, 1, 0], &[vec![0; 4]]);
+        bytes[HEADER_SIZE..HEADER_SIZE + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(matches!(
+            parse("test.ktx2", &bytes),
+            Err(ResourceError::Decode { .. })
+        ));
+    }
+}
@@ -0,0 +1,110 @@
+use wgpu::{Extent3d, TextureDimension, TextureFormat};
+
+use crate::error::ResourceError;
+
+mod bc;
+mod dds;
+mod ktx2;
+
+/// A texture read from a KTX2 or DDS file, with its mip chain and array layers still in the GPU
+/// format the file stores them in.
+pub struct CompressedImage {
+    pub format: TextureFormat,
+    /// Size of the full resolution level, `depth_or_array_layers` counts every layer and cube face.
+    pub size: Extent3d,
+    /// The layers are cube faces, six per cube in +X, -X, +Y, -Y, +Z, -Z order.
+    pub cube: bool,
+    /// Mip levels from full resolution down, each holding all of its layers one after another.
+    pub levels: Vec<Vec<u8>>,
+    /// The file only stores the full resolution level and leaves the rest of the mip chain to the
+    /// loader.
+    pub generate_mipmaps: bool,
+}
+
+impl CompressedImage {
+    /// Whether `bytes` start like a KTX2 or DDS file.
+    pub fn is_container(bytes: &[u8]) -> bool {
+        bytes.starts_with(&ktx2::IDENTIFIER) || bytes.starts_with(dds::MAGIC)
+    }
+
+    /// Reads a KTX2 or DDS file. Older DDS files don't say whether their colors are sRGB, `linear`
+    /// decides for them.
+    pub fn parse(path: &str, bytes: &[u8], linear: bool) -> Result<CompressedImage, ResourceError> {
+        if bytes.starts_with(&ktx2::IDENTIFIER) {
+            ktx2::parse(path, bytes)
+        } else {
+            dds::parse(path, bytes, linear)
+        }
+    }
+
+    /// Size of a mip level in texels, not rounded up to whole blocks.
+    pub fn level_size(&self, mip_level: u32) -> Extent3d {
+        self.size.mip_level_size(mip_level, TextureDimension::D2)
+    }
+
+    /// Decodes the image to RGBA8, for devices without the feature its format needs. `None` when
+    /// the format has no CPU decoder.
+    pub fn decompress(&self) -> Option<CompressedImage> {
+        let format = if self.format.is_srgb() {
+            TextureFormat::Rgba8UnormSrgb
+        } else {
+            TextureFormat::Rgba8Unorm
+        };
+        let mut levels = Vec::with_capacity(self.levels.len());
+        for (mip_level, level) in self.levels.iter().enumerate() {
+            let size = self.level_size(mip_level as u32);
+            let layer_size = layer_byte_size(self.format, size.width, size.height)?;
+            let mut decoded = Vec::new();
+            for layer in level.chunks_exact(layer_size) {
+                decoded.extend(bc::decode(self.format, layer, size.width, size.height)?);
+            }
+            levels.push(decoded);
+        }
+        Some(CompressedImage {
+            format,
+            size: self.size,
+            cube: self.cube,
+            levels,
+            generate_mipmaps: self.generate_mipmaps,
+        })
+    }
+}
+
+/// Bytes in one layer of a `width` by `height` level, in whole blocks. `None` when that doesn't
+/// fit in memory.
+fn layer_byte_size(format: TextureFormat, width: u32, height: u32) -> Option<usize> {
+    let (block_width, block_height) = format.block_dimensions();
+    let block_size = format.block_size(None).unwrap_or(4);
+    let blocks_wide = width.div_ceil(block_width) as usize;
+    let blocks_high = height.div_ceil(block_height) as usize;
+    blocks_wide
+        .checked_mul(blocks_high)?
+        .checked_mul(block_size as usize)
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        bytes.get(offset..offset + 4)?.try_into().unwrap(),
+    ))
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(
+        bytes.get(offset..offset + 8)?.try_into().unwrap(),
+    ))
+}
+
+fn truncated(path: &str) -> ResourceError {
+    ResourceError::decode(path, "the file is truncated")
+}
+
+fn too_large(path: &str) -> ResourceError {
+    ResourceError::decode(path, "the texture is too large")
+}
+
+fn unsupported(path: &str, format: impl Into<String>) -> ResourceError {
+    ResourceError::UnsupportedFormat {
+        path: path.to_string(),
+        format: format.into(),
+    }
+}
@@ -0,0 +1,287 @@
+use wgpu::{BindGroup, BindGroupLayout, Device};
+
+use crate::bind_groups::{create_gbuffer_bind_group, create_gbuffer_bind_group_layout};
+use crate::graphics_context::create_render_pipeline;
+use crate::hdr::HdrTarget;
+use crate::instance::InstanceRaw;
+use crate::object;
+use crate::object::Vertex;
+use crate::render_pass::MaterialBinding;
+use crate::taa::Taa;
+use crate::texture::{DepthTexture, Texture};
+
+/// Render targets filled by the deferred geometry pass. Depth comes from the shared depth texture.
+pub struct GBuffer {
+    pub albedo: wgpu::TextureView,
+    pub normal: wgpu::TextureView,
+    pub material: wgpu::TextureView,
+    pub emissive: wgpu::TextureView,
+}
+
+impl GBuffer {
+    pub const ALBEDO_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+    pub const NORMAL_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+    pub const MATERIAL_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+    pub const EMISSIVE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+    pub fn new(device: &Device, config: &wgpu::SurfaceConfiguration) -> GBuffer {
+        let create_target = |format, label| {
+            device
+                .create_texture(&wgpu::TextureDescriptor {
+                    label: Some(label),
+                    size: wgpu::Extent3d {
+                        width: config.width,
+                        height: config.height,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format,
+                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                        | wgpu::TextureUsages::TEXTURE_BINDING,
+                    view_formats: &[],
+                })
+                .create_view(&wgpu::TextureViewDescriptor::default())
+        };
+
+        GBuffer {
+            albedo: create_target(Self::ALBEDO_FORMAT, "gbuffer_albedo"),
+            normal: create_target(Self::NORMAL_FORMAT, "gbuffer_normal"),
+            material: create_target(Self::MATERIAL_FORMAT, "gbuffer_material"),
+            emissive: create_target(Self::EMISSIVE_FORMAT, "gbuffer_emissive"),
+        }
+    }
+}
+
+/// Deferred alternative to the forward `RenderPass`: the geometry pass draws models through
+/// `DrawModel` into the G-buffer, then a fullscreen lighting pass shades each covered pixel once.
+///
+/// Lighting always uses the metallic-roughness model.
+pub struct DeferredPass {
+    geometry_pipeline: wgpu::RenderPipeline,
+    packed_geometry_pipeline: wgpu::RenderPipeline,
+    lighting_pipeline: wgpu::RenderPipeline,
+    gbuffer_bind_group_layout: BindGroupLayout,
+    gbuffer: GBuffer,
+    gbuffer_bind_group: BindGroup,
+}
+
+impl DeferredPass {
+    /// `geometry_bind_group_layouts` are the forward material, camera and light layouts, and
+    /// `lighting_bind_group_layouts` the camera, light and environment layouts. Models with packed
+    /// materials draw with `packed_material_bind_group_layout` in place of the material layout.
+    pub fn new(
+        device: &Device,
+        config: &wgpu::SurfaceConfiguration,
+        depth_texture: &DepthTexture,
+        geometry_bind_group_layouts: &[&BindGroupLayout],
+        packed_material_bind_group_layout: &BindGroupLayout,
+        lighting_bind_group_layouts: &[&BindGroupLayout],
+    ) -> DeferredPass {
+        let geometry_pipeline = create_geometry_pipeline(
+            device,
+            geometry_bind_group_layouts,
+            MaterialBinding::PerMaterial,
+        );
+        let mut packed_layouts = geometry_bind_group_layouts.to_vec();
+        packed_layouts[0] = packed_material_bind_group_layout;
+        let packed_geometry_pipeline =
+            create_geometry_pipeline(device, &packed_layouts, MaterialBinding::Packed);
+
+        let gbuffer_bind_group_layout = create_gbuffer_bind_group_layout(device);
+        let lighting_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Deferred Lighting Pipeline Layout"),
+            bind_group_layouts: &[&[&gbuffer_bind_group_layout], lighting_bind_group_layouts]
+                .concat(),
+            push_constant_ranges: &[],
+        });
+        let lighting_pipeline = create_render_pipeline(
+            device,
+            &lighting_layout,
+            &[HdrTarget::FORMAT],
+            None,
+            &[],
+            wgpu::ShaderModuleDescriptor {
+                label: Some("Deferred Lighting Shader"),
+                source: wgpu::ShaderSource::Wgsl(
+                    concat!(
+                        include_str!("lights.wgsl"),
+                        include_str!("environment/ambient.wgsl"),
+                        include_str!("brdf.wgsl"),
+                        include_str!("fullscreen.wgsl"),
+                        include_str!("deferred.wgsl")
+                    )
+                    .into(),
+                ),
+            },
+        );
+
+        let gbuffer = GBuffer::new(device, config);
+        let gbuffer_bind_group = create_gbuffer_bind_group(
+            device,
+            &gbuffer,
+            &depth_texture.depth_view,
+            &gbuffer_bind_group_layout,
+        );
+
+        DeferredPass {
+            geometry_pipeline,
+            packed_geometry_pipeline,
+            lighting_pipeline,
+            gbuffer_bind_group_layout,
+            gbuffer,
+            gbuffer_bind_group,
+        }
+    }
+
+    /// Recreates the G-buffer to match the surface, call after recreating the depth texture.
+    pub fn resize(
+        &mut self,
+        device: &Device,
+        config: &wgpu::SurfaceConfiguration,
+        depth_texture: &DepthTexture,
+    ) {
+        self.gbuffer = GBuffer::new(device, config);
+        self.gbuffer_bind_group = create_gbuffer_bind_group(
+            device,
+            &self.gbuffer,
+            &depth_texture.depth_view,
+            &self.gbuffer_bind_group_layout,
+        );
+    }
+
+    /// Starts the geometry pass, clearing the G-buffer, `velocity_view` and `depth_view`. Draw
+    /// models into it with `DrawModel`, binding their materials as `materials`.
+    pub fn begin_geometry_pass<'a>(
+        &'a self,
+        encoder: &'a mut wgpu::CommandEncoder,
+        materials: MaterialBinding,
+        velocity_view: &'a wgpu::TextureView,
+        depth_view: &'a wgpu::TextureView,
+    ) -> wgpu::RenderPass<'a> {
+        let target = |view| {
+            Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: true,
+                },
+            })
+        };
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Geometry Pass"),
+            color_attachments: &[
+                target(&self.gbuffer.albedo),
+                target(&self.gbuffer.normal),
+                target(&self.gbuffer.material),
+                target(&self.gbuffer.emissive),
+                target(velocity_view),
+            ],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: depth_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: true,
+                }),
+                stencil_ops: None,
+            }),
+        });
+        render_pass.set_pipeline(match materials {
+            MaterialBinding::PerMaterial => &self.geometry_pipeline,
+            MaterialBinding::Packed => &self.packed_geometry_pipeline,
+        });
+        render_pass
+    }
+
+    /// Shades the G-buffer into `render_pass`, leaving pixels without geometry untouched.
+    pub fn draw_lighting<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        camera_bind_group: &'a BindGroup,
+        light_bind_group: &'a BindGroup,
+        environment_bind_group: &'a BindGroup,
+    ) {
+        render_pass.set_pipeline(&self.lighting_pipeline);
+        render_pass.set_bind_group(0, &self.gbuffer_bind_group, &[]);
+        render_pass.set_bind_group(1, camera_bind_group, &[]);
+        render_pass.set_bind_group(2, light_bind_group, &[]);
+        render_pass.set_bind_group(3, environment_bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+fn create_geometry_pipeline(
+    device: &Device,
+    bind_group_layouts: &[&BindGroupLayout],
+    materials: MaterialBinding,
+) -> wgpu::RenderPipeline {
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Geometry Pipeline Layout"),
+        bind_group_layouts,
+        push_constant_ranges: &[],
+    });
+    let source = match materials {
+        MaterialBinding::PerMaterial => concat!(
+            include_str!("model.wgsl"),
+            include_str!("material.wgsl"),
+            include_str!("gbuffer.wgsl")
+        ),
+        MaterialBinding::Packed => concat!(
+            include_str!("model.wgsl"),
+            include_str!("material_packed.wgsl"),
+            include_str!("gbuffer.wgsl")
+        ),
+    };
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Geometry Shader"),
+        source: wgpu::ShaderSource::Wgsl(source.into()),
+    });
+
+    let target = |format| {
+        Some(wgpu::ColorTargetState {
+            format,
+            blend: None,
+            write_mask: wgpu::ColorWrites::ALL,
+        })
+    };
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Geometry Pipeline"),
+        layout: Some(&layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: "vs_main",
+            buffers: &[object::ModelVertex::desc(), InstanceRaw::desc()],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: "fs_main",
+            targets: &[
+                target(GBuffer::ALBEDO_FORMAT),
+                target(GBuffer::NORMAL_FORMAT),
+                target(GBuffer::MATERIAL_FORMAT),
+                target(GBuffer::EMISSIVE_FORMAT),
+                target(Taa::VELOCITY_FORMAT),
+            ],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: Some(wgpu::Face::Back),
+            polygon_mode: wgpu::PolygonMode::Fill,
+            unclipped_depth: false,
+            conservative: false,
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: Texture::DEPTH_FORMAT,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+    })
+}
@@ -0,0 +1,57 @@
+// deferred.wgsl
+// Lighting pass of the deferred renderer: shades every covered pixel of the G-buffer with a
+// fullscreen triangle, reconstructing positions from depth.
+
+struct Camera {
+    view_proj: mat4x4<f32>,
+    view_position: vec4<f32>,
+    inv_view_proj: mat4x4<f32>,
+    unjittered_view_proj: mat4x4<f32>,
+    prev_view_proj: mat4x4<f32>,
+}
+@group(1) @binding(0)
+var<uniform> camera: Camera;
+
+@group(0) @binding(0)
+var t_albedo: texture_2d<f32>;
+@group(0) @binding(1)
+var t_normal: texture_2d<f32>;
+@group(0) @binding(2)
+var t_material: texture_2d<f32>;
+@group(0) @binding(3)
+var t_emissive: texture_2d<f32>;
+@group(0) @binding(4)
+var t_depth: texture_depth_2d;
+
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let frag_coord = in.position;
+    let texel = vec2<i32>(frag_coord.xy);
+    let depth = textureLoad(t_depth, texel, 0);
+    if (depth >= 1.0) {
+        // Nothing was drawn here, leave it for the skybox.
+        discard;
+    }
+
+    let size = vec2<f32>(textureDimensions(t_depth));
+    let uv = frag_coord.xy / size;
+    let ndc = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, depth, 1.0);
+    let world = camera.inv_view_proj * ndc;
+
+    let material = textureLoad(t_material, texel, 0);
+    let normal = normalize(textureLoad(t_normal, texel, 0).xyz);
+
+    var surface: PbrSurface;
+    surface.position = world.xyz / world.w;
+    surface.geometric_normal = normal;
+    surface.normal = normal;
+    surface.base_color = textureLoad(t_albedo, texel, 0).rgb;
+    surface.metallic = material.r;
+    surface.roughness = material.g;
+    surface.occlusion = material.b;
+
+    let emissive = textureLoad(t_emissive, texel, 0).rgb;
+    let color = pbr_lighting(surface, frag_coord, camera.view_position.xyz) + emissive;
+
+    return vec4<f32>(color, 1.0);
+}
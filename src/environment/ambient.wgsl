@@ -0,0 +1,39 @@
+// ambient.wgsl
+// Image based ambient lighting from the precomputed environment maps, shared by the forward shading
+// pipelines and the deferred lighting pass. Screen-space ambient occlusion is bound alongside.
+
+@group(3) @binding(0)
+var t_irradiance: texture_cube<f32>;
+@group(3) @binding(1)
+var t_prefiltered: texture_cube<f32>;
+@group(3) @binding(2)
+var t_brdf_lut: texture_2d<f32>;
+@group(3) @binding(3)
+var s_environment: sampler;
+@group(3) @binding(4)
+var t_ambient_occlusion: texture_2d<f32>;
+
+// Mip count of the prefiltered map minus one, the lod holding the roughest reflections.
+const PREFILTERED_MAX_LOD: f32 = 4.0;
+
+// Fraction of ambient light reaching the pixel, 1 everywhere while SSAO is disabled.
+fn ambient_occlusion(frag_coord: vec4<f32>) -> f32 {
+    return textureLoad(t_ambient_occlusion, vec2<i32>(frag_coord.xy), 0).r;
+}
+
+fn ambient_diffuse(normal: vec3<f32>) -> vec3<f32> {
+    return textureSample(t_irradiance, s_environment, normal).rgb;
+}
+
+// Split sum approximation of the specular environment light for the given Fresnel reflectance.
+fn ambient_specular(normal: vec3<f32>, view: vec3<f32>, f: vec3<f32>, roughness: f32) -> vec3<f32> {
+    let reflected = reflect(-view, normal);
+    let prefiltered = textureSampleLevel(
+        t_prefiltered,
+        s_environment,
+        reflected,
+        roughness * PREFILTERED_MAX_LOD,
+    ).rgb;
+    let brdf = textureSample(t_brdf_lut, s_environment, vec2<f32>(max(dot(normal, view), 0.0), roughness)).rg;
+    return prefiltered * (f * brdf.x + brdf.y);
+}
@@ -0,0 +1,24 @@
+// cube_downsample.wgsl
+// Box filters one cube mip level into the next.
+
+@group(0) @binding(0)
+var src: texture_2d_array<f32>;
+@group(0) @binding(1)
+var dst: texture_storage_2d_array<rgba16float, write>;
+
+@compute @workgroup_size(8, 8, 1)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = textureDimensions(dst).x;
+    if (id.x >= size || id.y >= size) {
+        return;
+    }
+
+    let texel = vec2<i32>(id.xy) * 2;
+    let face = i32(id.z);
+    let color = textureLoad(src, texel, face, 0)
+        + textureLoad(src, texel + vec2<i32>(1, 0), face, 0)
+        + textureLoad(src, texel + vec2<i32>(0, 1), face, 0)
+        + textureLoad(src, texel + vec2<i32>(1, 1), face, 0);
+
+    textureStore(dst, vec2<i32>(id.xy), face, color * 0.25);
+}
@@ -0,0 +1,46 @@
+// equirect_to_cube.wgsl
+// Projects an equirectangular HDR image onto the six faces of a cube map.
+
+@group(0) @binding(0)
+var src: texture_2d<f32>;
+@group(0) @binding(1)
+var dst: texture_storage_2d_array<rgba16float, write>;
+
+// Rgba32Float isn't filterable everywhere, so filter by hand, wrapping around horizontally.
+fn load_wrapped(texel: vec2<i32>, size: vec2<i32>) -> vec4<f32> {
+    let x = (texel.x % size.x + size.x) % size.x;
+    let y = clamp(texel.y, 0, size.y - 1);
+    return textureLoad(src, vec2<i32>(x, y), 0);
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = textureDimensions(dst).x;
+    if (id.x >= size || id.y >= size) {
+        return;
+    }
+
+    let direction = cube_direction(id.xy, id.z, size);
+    let uv = vec2<f32>(
+        atan2(direction.z, direction.x) / (2.0 * PI) + 0.5,
+        acos(clamp(direction.y, -1.0, 1.0)) / PI,
+    );
+
+    let src_size = vec2<i32>(textureDimensions(src));
+    let position = uv * vec2<f32>(src_size) - 0.5;
+    let base = vec2<i32>(floor(position));
+    let t = fract(position);
+    let top = mix(
+        load_wrapped(base, src_size),
+        load_wrapped(base + vec2<i32>(1, 0), src_size),
+        t.x,
+    );
+    let bottom = mix(
+        load_wrapped(base + vec2<i32>(0, 1), src_size),
+        load_wrapped(base + vec2<i32>(1, 1), src_size),
+        t.x,
+    );
+    let color = mix(top, bottom, t.y);
+
+    textureStore(dst, vec2<i32>(id.xy), i32(id.z), vec4<f32>(color.rgb, 1.0));
+}
@@ -0,0 +1,42 @@
+// irradiance.wgsl
+// Convolves the environment with a cosine lobe, giving the diffuse light arriving from every direction.
+
+@group(0) @binding(0)
+var environment: texture_cube<f32>;
+@group(0) @binding(1)
+var environment_sampler: sampler;
+@group(0) @binding(2)
+var dst: texture_storage_2d_array<rgba16float, write>;
+
+const SAMPLE_DELTA: f32 = 0.025;
+
+@compute @workgroup_size(8, 8, 1)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = textureDimensions(dst).x;
+    if (id.x >= size || id.y >= size) {
+        return;
+    }
+
+    let normal = cube_direction(id.xy, id.z, size);
+    let up_hint = select(vec3<f32>(0.0, 1.0, 0.0), vec3<f32>(1.0, 0.0, 0.0), abs(normal.y) > 0.999);
+    let right = normalize(cross(up_hint, normal));
+    let up = cross(normal, right);
+
+    // Read a mip whose texels roughly match the sample spacing to avoid aliasing.
+    let level = max(log2(f32(textureDimensions(environment).x) * SAMPLE_DELTA), 0.0);
+
+    var irradiance = vec3<f32>(0.0);
+    var sample_count = 0.0;
+    for (var phi = 0.0; phi < 2.0 * PI; phi += SAMPLE_DELTA) {
+        for (var theta = 0.0; theta < 0.5 * PI; theta += SAMPLE_DELTA) {
+            let tangent_sample = vec3<f32>(sin(theta) * cos(phi), sin(theta) * sin(phi), cos(theta));
+            let sample_direction = tangent_sample.x * right + tangent_sample.y * up + tangent_sample.z * normal;
+            let radiance = textureSampleLevel(environment, environment_sampler, sample_direction, level).rgb;
+            irradiance += radiance * cos(theta) * sin(theta);
+            sample_count += 1.0;
+        }
+    }
+    irradiance = PI * irradiance / sample_count;
+
+    textureStore(dst, vec2<i32>(id.xy), i32(id.z), vec4<f32>(irradiance, 1.0));
+}
@@ -0,0 +1,337 @@
+use wgpu::util::DeviceExt;
+use wgpu::{BindGroup, Device, Queue};
+
+use crate::texture::{CubeTexture, Texture};
+
+const WORKGROUP_SIZE: u32 = 8;
+
+/// Image based lighting precomputed from an HDR environment.
+///
+/// The equirectangular source is projected onto `environment_map`, which is then convolved into a
+/// diffuse `irradiance_map` and a `prefiltered_map` holding specular reflections of increasing
+/// roughness in its mip levels. `brdf_lut` stores the scale and bias applied to F0 by the split sum
+/// approximation.
+pub struct Environment {
+    pub environment_map: CubeTexture,
+    pub irradiance_map: CubeTexture,
+    pub prefiltered_map: CubeTexture,
+    pub brdf_lut: Texture,
+}
+
+impl Environment {
+    pub const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+    pub const ENVIRONMENT_SIZE: u32 = 512;
+    pub const ENVIRONMENT_MIP_LEVELS: u32 = 6;
+    pub const IRRADIANCE_SIZE: u32 = 32;
+    pub const PREFILTERED_SIZE: u32 = 128;
+    /// Must match `PREFILTERED_MAX_LOD` in `ambient.wgsl`.
+    pub const PREFILTERED_MIP_LEVELS: u32 = 5;
+    pub const BRDF_LUT_SIZE: u32 = 256;
+
+    /// Builds the environment from linear RGBA pixels laid out as an equirectangular projection.
+    pub fn from_equirectangular(
+        device: &Device,
+        queue: &Queue,
+        width: u32,
+        height: u32,
+        pixels: &[[f32; 4]],
+    ) -> Environment {
+        let source = device.create_texture_with_data(
+            queue,
+            &wgpu::TextureDescriptor {
+                label: Some("equirectangular_texture"),
+                size: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba32Float,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            },
+            bytemuck::cast_slice(pixels),
+        );
+        let source_view = source.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let usage = wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::STORAGE_BINDING;
+        let environment_map = CubeTexture::new(
+            device,
+            Self::ENVIRONMENT_SIZE,
+            Self::FORMAT,
+            Self::ENVIRONMENT_MIP_LEVELS,
+            usage,
+            Some("environment_map"),
+        );
+        let irradiance_map = CubeTexture::new(
+            device,
+            Self::IRRADIANCE_SIZE,
+            Self::FORMAT,
+            1,
+            usage,
+            Some("irradiance_map"),
+        );
+        let prefiltered_map = CubeTexture::new(
+            device,
+            Self::PREFILTERED_SIZE,
+            Self::FORMAT,
+            Self::PREFILTERED_MIP_LEVELS,
+            usage,
+            Some("prefiltered_map"),
+        );
+        let brdf_lut = create_brdf_lut_texture(device);
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Environment Encoder"),
+        });
+
+        // Project the equirectangular image onto the cube. Rgba32Float is only loadable, not
+        // filterable, which the layout derived from the shader can't express.
+        let equirect_layout = create_equirect_bind_group_layout(device);
+        let pipeline = create_compute_pipeline(
+            device,
+            "Equirect To Cube",
+            include_str!("equirect_to_cube.wgsl"),
+            Some(&equirect_layout),
+        );
+        let target = environment_map.face_array_view(0);
+        let bind_group = create_bind_group(
+            device,
+            &pipeline,
+            &[
+                wgpu::BindingResource::TextureView(&source_view),
+                wgpu::BindingResource::TextureView(&target),
+            ],
+        );
+        dispatch(
+            &mut encoder,
+            &pipeline,
+            &bind_group,
+            Self::ENVIRONMENT_SIZE,
+            6,
+        );
+
+        // Fill the environment mip chain so the convolutions can read pre-filtered texels.
+        let pipeline = create_compute_pipeline(
+            device,
+            "Cube Downsample",
+            include_str!("cube_downsample.wgsl"),
+            None,
+        );
+        for mip_level in 1..Self::ENVIRONMENT_MIP_LEVELS {
+            let source = environment_map.face_array_view(mip_level - 1);
+            let target = environment_map.face_array_view(mip_level);
+            let bind_group = create_bind_group(
+                device,
+                &pipeline,
+                &[
+                    wgpu::BindingResource::TextureView(&source),
+                    wgpu::BindingResource::TextureView(&target),
+                ],
+            );
+            dispatch(
+                &mut encoder,
+                &pipeline,
+                &bind_group,
+                Self::ENVIRONMENT_SIZE >> mip_level,
+                6,
+            );
+        }
+
+        let pipeline =
+            create_compute_pipeline(device, "Irradiance", include_str!("irradiance.wgsl"), None);
+        let target = irradiance_map.face_array_view(0);
+        let bind_group = create_bind_group(
+            device,
+            &pipeline,
+            &[
+                wgpu::BindingResource::TextureView(&environment_map.view),
+                wgpu::BindingResource::Sampler(&environment_map.sampler),
+                wgpu::BindingResource::TextureView(&target),
+            ],
+        );
+        dispatch(
+            &mut encoder,
+            &pipeline,
+            &bind_group,
+            Self::IRRADIANCE_SIZE,
+            6,
+        );
+
+        // One roughness per mip, from mirror-like at level 0 to fully rough at the last level.
+        let pipeline =
+            create_compute_pipeline(device, "Prefilter", include_str!("prefilter.wgsl"), None);
+        for mip_level in 0..Self::PREFILTERED_MIP_LEVELS {
+            let roughness = mip_level as f32 / (Self::PREFILTERED_MIP_LEVELS - 1) as f32;
+            let params = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Prefilter Params Buffer"),
+                contents: bytemuck::cast_slice(&[roughness, 0.0, 0.0, 0.0]),
+                usage: wgpu::BufferUsages::UNIFORM,
+            });
+            let target = prefiltered_map.face_array_view(mip_level);
+            let bind_group = create_bind_group(
+                device,
+                &pipeline,
+                &[
+                    wgpu::BindingResource::TextureView(&environment_map.view),
+                    wgpu::BindingResource::Sampler(&environment_map.sampler),
+                    wgpu::BindingResource::TextureView(&target),
+                    params.as_entire_binding(),
+                ],
+            );
+            dispatch(
+                &mut encoder,
+                &pipeline,
+                &bind_group,
+                Self::PREFILTERED_SIZE >> mip_level,
+                6,
+            );
+        }
+
+        let pipeline =
+            create_compute_pipeline(device, "BRDF LUT", include_str!("brdf_lut.wgsl"), None);
+        let bind_group = create_bind_group(
+            device,
+            &pipeline,
+            &[wgpu::BindingResource::TextureView(&brdf_lut.view)],
+        );
+        dispatch(&mut encoder, &pipeline, &bind_group, Self::BRDF_LUT_SIZE, 1);
+
+        queue.submit(std::iter::once(encoder.finish()));
+
+        Environment {
+            environment_map,
+            irradiance_map,
+            prefiltered_map,
+            brdf_lut,
+        }
+    }
+}
+
+fn create_brdf_lut_texture(device: &Device) -> Texture {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("brdf_lut"),
+        size: wgpu::Extent3d {
+            width: Environment::BRDF_LUT_SIZE,
+            height: Environment::BRDF_LUT_SIZE,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: Environment::FORMAT,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::STORAGE_BINDING,
+        view_formats: &[],
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+        label: Some("brdf_lut_sampler"),
+        address_mode_u: wgpu::AddressMode::ClampToEdge,
+        address_mode_v: wgpu::AddressMode::ClampToEdge,
+        address_mode_w: wgpu::AddressMode::ClampToEdge,
+        mag_filter: wgpu::FilterMode::Linear,
+        min_filter: wgpu::FilterMode::Linear,
+        mipmap_filter: wgpu::FilterMode::Nearest,
+        ..Default::default()
+    });
+
+    Texture {
+        size: texture.size(),
+        texture,
+        view,
+        sampler: std::sync::Arc::new(sampler),
+    }
+}
+
+fn create_equirect_bind_group_layout(device: &Device) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::StorageTexture {
+                    access: wgpu::StorageTextureAccess::WriteOnly,
+                    format: Environment::FORMAT,
+                    view_dimension: wgpu::TextureViewDimension::D2Array,
+                },
+                count: None,
+            },
+        ],
+        label: Some("equirect_bind_group_layout"),
+    })
+}
+
+// The compute passes share the cube helpers and, unless given one, derive their layout from the
+// shader.
+fn create_compute_pipeline(
+    device: &Device,
+    label: &str,
+    source: &str,
+    bind_group_layout: Option<&wgpu::BindGroupLayout>,
+) -> wgpu::ComputePipeline {
+    let layout = bind_group_layout.map(|bind_group_layout| {
+        device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(label),
+            bind_group_layouts: &[bind_group_layout],
+            push_constant_ranges: &[],
+        })
+    });
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some(label),
+        source: wgpu::ShaderSource::Wgsl(format!("{}{}", include_str!("cube.wgsl"), source).into()),
+    });
+
+    device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+        label: Some(label),
+        layout: layout.as_ref(),
+        module: &shader,
+        entry_point: "main",
+    })
+}
+
+fn create_bind_group(
+    device: &Device,
+    pipeline: &wgpu::ComputePipeline,
+    resources: &[wgpu::BindingResource],
+) -> BindGroup {
+    let entries = resources
+        .iter()
+        .enumerate()
+        .map(|(binding, resource)| wgpu::BindGroupEntry {
+            binding: binding as u32,
+            resource: resource.clone(),
+        })
+        .collect::<Vec<_>>();
+
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: None,
+        layout: &pipeline.get_bind_group_layout(0),
+        entries: &entries,
+    })
+}
+
+fn dispatch(
+    encoder: &mut wgpu::CommandEncoder,
+    pipeline: &wgpu::ComputePipeline,
+    bind_group: &BindGroup,
+    size: u32,
+    layers: u32,
+) {
+    let workgroups = size.div_ceil(WORKGROUP_SIZE);
+    let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: None });
+    compute_pass.set_pipeline(pipeline);
+    compute_pass.set_bind_group(0, bind_group, &[]);
+    compute_pass.dispatch_workgroups(workgroups, workgroups, layers);
+}
@@ -0,0 +1,62 @@
+// prefilter.wgsl
+// Pre-convolves the environment with the GGX lobe for one roughness, one roughness per mip level.
+
+struct PrefilterParams {
+    roughness: f32,
+}
+
+@group(0) @binding(0)
+var environment: texture_cube<f32>;
+@group(0) @binding(1)
+var environment_sampler: sampler;
+@group(0) @binding(2)
+var dst: texture_storage_2d_array<rgba16float, write>;
+@group(0) @binding(3)
+var<uniform> params: PrefilterParams;
+
+const SAMPLE_COUNT: u32 = 1024u;
+
+fn distribution_ggx(n_dot_h: f32, roughness: f32) -> f32 {
+    let a = roughness * roughness;
+    let a2 = a * a;
+    let denom = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    return a2 / (PI * denom * denom);
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = textureDimensions(dst).x;
+    if (id.x >= size || id.y >= size) {
+        return;
+    }
+
+    // Assume the viewer looks straight down the normal, as in the split sum approximation.
+    let n = cube_direction(id.xy, id.z, size);
+    let v = n;
+
+    let environment_size = f32(textureDimensions(environment).x);
+    let texel_solid_angle = 4.0 * PI / (6.0 * environment_size * environment_size);
+
+    var color = vec3<f32>(0.0);
+    var total_weight = 0.0;
+    for (var i = 0u; i < SAMPLE_COUNT; i++) {
+        let xi = hammersley(i, SAMPLE_COUNT);
+        let h = importance_sample_ggx(xi, n, params.roughness);
+        let l = normalize(2.0 * dot(v, h) * h - v);
+
+        let n_dot_l = dot(n, l);
+        if (n_dot_l > 0.0) {
+            // Sample a blurrier mip for unlikely directions to avoid bright speckles.
+            let n_dot_h = max(dot(n, h), 0.0);
+            let h_dot_v = max(dot(h, v), 0.0);
+            let pdf = distribution_ggx(n_dot_h, params.roughness) * n_dot_h / (4.0 * h_dot_v) + 0.0001;
+            let sample_solid_angle = 1.0 / (f32(SAMPLE_COUNT) * pdf + 0.0001);
+            let level = select(0.5 * log2(sample_solid_angle / texel_solid_angle), 0.0, params.roughness == 0.0);
+
+            color += textureSampleLevel(environment, environment_sampler, l, max(level, 0.0)).rgb * n_dot_l;
+            total_weight += n_dot_l;
+        }
+    }
+
+    textureStore(dst, vec2<i32>(id.xy), i32(id.z), vec4<f32>(color / total_weight, 1.0));
+}
@@ -0,0 +1,81 @@
+use std::fmt;
+
+/// Why a model, texture or other resource couldn't be loaded. Every error names the file it
+/// comes from, relative to the resource directory, so callers can report it or fall back to a
+/// default.
+#[derive(Debug)]
+pub enum ResourceError {
+    /// The file doesn't exist or couldn't be read.
+    MissingFile {
+        path: String,
+        source: std::io::Error,
+    },
+    /// The file was read but its contents are malformed.
+    Decode {
+        path: String,
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    /// The file is valid but uses a format or feature the loader doesn't handle.
+    UnsupportedFormat { path: String, format: String },
+    /// Something the loader needs, like the texture coordinates of a mesh, is absent.
+    MissingAttribute { path: String, attribute: String },
+}
+
+impl ResourceError {
+    pub fn decode(
+        path: &str,
+        source: impl Into<Box<dyn std::error::Error + Send + Sync>>,
+    ) -> ResourceError {
+        ResourceError::Decode {
+            path: path.to_string(),
+            source: source.into(),
+        }
+    }
+
+    pub fn missing_attribute(path: &str, attribute: impl Into<String>) -> ResourceError {
+        ResourceError::MissingAttribute {
+            path: path.to_string(),
+            attribute: attribute.into(),
+        }
+    }
+
+    /// Sorts an `image` error into an unsupported format or a decoding failure.
+    pub fn from_image(path: &str, error: image::ImageError) -> ResourceError {
+        match error {
+            image::ImageError::Unsupported(error) => ResourceError::UnsupportedFormat {
+                path: path.to_string(),
+                format: error.to_string(),
+            },
+            error => ResourceError::decode(path, error),
+        }
+    }
+}
+
+impl fmt::Display for ResourceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResourceError::MissingFile { path, source } => {
+                write!(f, "couldn't read {path}: {source}")
+            }
+            ResourceError::Decode { path, source } => write!(f, "couldn't decode {path}: {source}"),
+            ResourceError::UnsupportedFormat { path, format } => {
+                write!(f, "unsupported format in {path}: {format}")
+            }
+            ResourceError::MissingAttribute { path, attribute } => {
+                write!(f, "{path} has no {attribute}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ResourceError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ResourceError::MissingFile { source, .. } => Some(source),
+            ResourceError::Decode { source, .. } => Some(source.as_ref()),
+            ResourceError::UnsupportedFormat { .. } | ResourceError::MissingAttribute { .. } => {
+                None
+            }
+        }
+    }
+}
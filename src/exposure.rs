@@ -0,0 +1,176 @@
+use std::time::Instant;
+
+use wgpu::util::DeviceExt;
+use wgpu::{BindGroup, BindGroupLayout, Device, Queue};
+
+use crate::bind_groups::{create_exposure_bind_group, create_exposure_bind_group_layout};
+use crate::hdr::HdrTarget;
+
+/// Must match `HISTOGRAM_BINS` in `exposure.wgsl`.
+const HISTOGRAM_BINS: u32 = 256;
+const WORKGROUP_SIZE: u32 = 16;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct ExposureUniform {
+    min_log_luminance: f32,
+    log_luminance_range: f32,
+    min_ev: f32,
+    max_ev: f32,
+    speed_up: f32,
+    speed_down: f32,
+    delta_time: f32,
+    _padding: f32,
+}
+
+/// Adapts the exposure to the brightness of the HDR scene, like an eye or a camera would.
+///
+/// A compute pass bins the log luminance of every pixel into a histogram and a second one
+/// averages it, ignoring the darkest bin, then eases the adapted luminance towards that average.
+/// The result stays on the GPU in `exposure_buffer`, where the composite pass reads it.
+pub struct AutoExposure {
+    pub exposure_buffer: wgpu::Buffer,
+    histogram_pipeline: wgpu::ComputePipeline,
+    average_pipeline: wgpu::ComputePipeline,
+    bind_group_layout: BindGroupLayout,
+    uniform_buffer: wgpu::Buffer,
+    histogram_buffer: wgpu::Buffer,
+    bind_group: BindGroup,
+    size: [u32; 2],
+    last_update: Instant,
+}
+
+impl AutoExposure {
+    /// Lowest and highest scene luminance the histogram resolves, in stops.
+    const MIN_LOG_LUMINANCE: f32 = -10.0;
+    const MAX_LOG_LUMINANCE: f32 = 6.0;
+    /// The adapted luminance is clamped to this range, so very dark or very bright scenes are
+    /// left under or over exposed instead of being pulled to middle grey.
+    const MIN_EV: f32 = -6.0;
+    const MAX_EV: f32 = 4.0;
+    /// Adaptation rates when the scene gets brighter and darker. Like the eye, adjusting to
+    /// brightness is quicker than adjusting to the dark.
+    const SPEED_UP: f32 = 3.0;
+    const SPEED_DOWN: f32 = 1.0;
+
+    pub fn new(
+        device: &Device,
+        config: &wgpu::SurfaceConfiguration,
+        hdr: &HdrTarget,
+    ) -> AutoExposure {
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Exposure Buffer"),
+            size: std::mem::size_of::<ExposureUniform>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let histogram_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Luminance Histogram Buffer"),
+            size: HISTOGRAM_BINS as wgpu::BufferAddress * 4,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+        // Starts adapted to a luminance of 1 rather than black, so the first frames don't flash.
+        let exposure_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Adapted Exposure Buffer"),
+            contents: bytemuck::cast_slice(&[0.0f32, 0.18]),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+
+        let bind_group_layout = create_exposure_bind_group_layout(device);
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Exposure Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Exposure Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("exposure.wgsl").into()),
+        });
+        let histogram_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Luminance Histogram Pipeline"),
+            layout: Some(&layout),
+            module: &shader,
+            entry_point: "build_histogram",
+        });
+        let average_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Luminance Average Pipeline"),
+            layout: Some(&layout),
+            module: &shader,
+            entry_point: "average_histogram",
+        });
+        let bind_group = create_exposure_bind_group(
+            device,
+            &hdr.view,
+            &uniform_buffer,
+            &histogram_buffer,
+            &exposure_buffer,
+            &bind_group_layout,
+        );
+
+        AutoExposure {
+            exposure_buffer,
+            histogram_pipeline,
+            average_pipeline,
+            bind_group_layout,
+            uniform_buffer,
+            histogram_buffer,
+            bind_group,
+            size: [config.width, config.height],
+            last_update: Instant::now(),
+        }
+    }
+
+    /// Rebinds the scene, call after recreating `hdr`.
+    pub fn resize(
+        &mut self,
+        device: &Device,
+        config: &wgpu::SurfaceConfiguration,
+        hdr: &HdrTarget,
+    ) {
+        self.size = [config.width, config.height];
+        self.bind_group = create_exposure_bind_group(
+            device,
+            &hdr.view,
+            &self.uniform_buffer,
+            &self.histogram_buffer,
+            &self.exposure_buffer,
+            &self.bind_group_layout,
+        );
+    }
+
+    /// Uploads the time since the previous update, which sets how far this frame adapts.
+    pub fn update(&mut self, queue: &Queue) {
+        let now = Instant::now();
+        let delta_time = now.duration_since(self.last_update).as_secs_f32();
+        self.last_update = now;
+
+        let uniform = ExposureUniform {
+            min_log_luminance: Self::MIN_LOG_LUMINANCE,
+            log_luminance_range: Self::MAX_LOG_LUMINANCE - Self::MIN_LOG_LUMINANCE,
+            min_ev: Self::MIN_EV,
+            max_ev: Self::MAX_EV,
+            speed_up: Self::SPEED_UP,
+            speed_down: Self::SPEED_DOWN,
+            delta_time,
+            _padding: 0.0,
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+    }
+
+    /// Measures the HDR target, call after the scene is drawn and before compositing.
+    pub fn render(&self, encoder: &mut wgpu::CommandEncoder) {
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Auto Exposure Pass"),
+        });
+        compute_pass.set_bind_group(0, &self.bind_group, &[]);
+        compute_pass.set_pipeline(&self.histogram_pipeline);
+        compute_pass.dispatch_workgroups(
+            self.size[0].div_ceil(WORKGROUP_SIZE),
+            self.size[1].div_ceil(WORKGROUP_SIZE),
+            1,
+        );
+        compute_pass.set_pipeline(&self.average_pipeline);
+        compute_pass.dispatch_workgroups(1, 1, 1);
+    }
+}
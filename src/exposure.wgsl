@@ -0,0 +1,99 @@
+// exposure.wgsl
+// Automatic exposure: bins the log luminance of every HDR pixel into a histogram, then a single
+// workgroup averages it and moves the adapted luminance towards the average over time.
+
+const HISTOGRAM_BINS: u32 = 256u;
+// Middle grey, the average luminance is exposed to land here.
+const KEY_VALUE: f32 = 0.18;
+
+struct Settings {
+    // Luminance range covered by the histogram, in log2 units.
+    min_log_luminance: f32,
+    log_luminance_range: f32,
+    // Clamps on the adapted luminance, in log2 units.
+    min_ev: f32,
+    max_ev: f32,
+    // Adaptation rates towards brighter and darker scenes.
+    speed_up: f32,
+    speed_down: f32,
+    delta_time: f32,
+}
+
+struct Exposure {
+    // log2 of the luminance the eye has adapted to.
+    adapted_ev: f32,
+    // Linear scale applied by the composite pass.
+    exposure: f32,
+}
+
+@group(0) @binding(0)
+var<uniform> settings: Settings;
+@group(0) @binding(1)
+var t_scene: texture_2d<f32>;
+@group(0) @binding(2)
+var<storage, read_write> histogram: array<atomic<u32>, HISTOGRAM_BINS>;
+@group(0) @binding(3)
+var<storage, read_write> exposure: Exposure;
+
+var<workgroup> local_bins: array<atomic<u32>, HISTOGRAM_BINS>;
+var<workgroup> weighted: array<f32, HISTOGRAM_BINS>;
+
+// Bin 0 collects pixels too dark to matter, so they can be left out of the average.
+fn luminance_bin(color: vec3<f32>) -> u32 {
+    let luminance = dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+    if (luminance < 0.0001) {
+        return 0u;
+    }
+    let t = saturate((log2(luminance) - settings.min_log_luminance) / settings.log_luminance_range);
+    return u32(t * 254.0 + 1.0);
+}
+
+@compute @workgroup_size(16, 16)
+fn build_histogram(
+    @builtin(global_invocation_id) id: vec3<u32>,
+    @builtin(local_invocation_index) index: u32,
+) {
+    atomicStore(&local_bins[index], 0u);
+    workgroupBarrier();
+
+    let size = textureDimensions(t_scene);
+    if (all(id.xy < size)) {
+        let color = textureLoad(t_scene, vec2<i32>(id.xy), 0).rgb;
+        atomicAdd(&local_bins[luminance_bin(color)], 1u);
+    }
+    workgroupBarrier();
+
+    atomicAdd(&histogram[index], atomicLoad(&local_bins[index]));
+}
+
+@compute @workgroup_size(256)
+fn average_histogram(@builtin(local_invocation_index) index: u32) {
+    // Reading the bin also clears it for the next frame.
+    let count = atomicExchange(&histogram[index], 0u);
+    weighted[index] = f32(count) * f32(index);
+    workgroupBarrier();
+
+    for (var stride = HISTOGRAM_BINS / 2u; stride > 0u; stride = stride / 2u) {
+        if (index < stride) {
+            weighted[index] += weighted[index + stride];
+        }
+        workgroupBarrier();
+    }
+
+    if (index == 0u) {
+        let size = textureDimensions(t_scene);
+        let lit_pixels = max(f32(size.x * size.y) - f32(count), 1.0);
+        let average_bin = weighted[0] / lit_pixels - 1.0;
+        let average_ev = average_bin / 254.0 * settings.log_luminance_range
+            + settings.min_log_luminance;
+        let target_ev = clamp(average_ev, settings.min_ev, settings.max_ev);
+
+        var speed = settings.speed_down;
+        if (target_ev > exposure.adapted_ev) {
+            speed = settings.speed_up;
+        }
+        let blend = 1.0 - exp(-settings.delta_time * speed);
+        exposure.adapted_ev += (target_ev - exposure.adapted_ev) * blend;
+        exposure.exposure = KEY_VALUE / exp2(exposure.adapted_ev);
+    }
+}
@@ -0,0 +1,230 @@
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use wgpu::{Device, Queue};
+
+const REPORT_INTERVAL: u32 = 120;
+// Frames of timestamps that can be waiting to be read back at once. Reading them a frame or two
+// after they were submitted keeps the CPU from waiting for the GPU.
+const READBACK_FRAMES: usize = 3;
+
+/// Averages frame cost over `REPORT_INTERVAL` frames while it is enabled, off by default.
+///
+/// With `Features::TIMESTAMP_QUERY` the GPU time between consecutive `mark` calls is measured,
+/// so each marked section of the frame is reported on its own. Without it only the CPU frame time
+/// is reported, which is capped by the present mode. The CPU time spent encoding between the first
+/// mark and `resolve` is reported either way, the cost of recording the frame's draw calls.
+pub struct FrameTimer {
+    enabled: bool,
+    sections: Vec<&'static str>,
+    queries: Option<TimestampQueries>,
+    gpu_totals: Vec<f64>,
+    gpu_frames: u32,
+    frame_start: Instant,
+    frame_total: f64,
+    encode_start: Instant,
+    encode_total: f64,
+    frames: u32,
+}
+
+struct TimestampQueries {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    readbacks: Vec<Readback>,
+    // The readback the current frame copies its timestamps into, if it was free.
+    next: usize,
+    copied: bool,
+    period: f32,
+}
+
+struct Readback {
+    buffer: wgpu::Buffer,
+    // Set while the buffer is being mapped, to whether mapping succeeded once it is done.
+    mapping: Option<Arc<Mutex<Option<bool>>>>,
+}
+
+impl FrameTimer {
+    /// `sections` names the spans between consecutive marks, so a frame needs one more mark than
+    /// there are sections.
+    pub fn new(device: &Device, queue: &Queue, sections: &[&'static str]) -> FrameTimer {
+        let query_count = sections.len() as u32 + 1;
+        let queries = device
+            .features()
+            .contains(wgpu::Features::TIMESTAMP_QUERY)
+            .then(|| {
+                let size = query_count as wgpu::BufferAddress * 8;
+                TimestampQueries {
+                    query_set: device.create_query_set(&wgpu::QuerySetDescriptor {
+                        label: Some("Frame Timer Queries"),
+                        ty: wgpu::QueryType::Timestamp,
+                        count: query_count,
+                    }),
+                    resolve_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                        label: Some("Frame Timer Resolve Buffer"),
+                        size,
+                        usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+                        mapped_at_creation: false,
+                    }),
+                    readbacks: (0..READBACK_FRAMES)
+                        .map(|_| Readback {
+                            buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                                label: Some("Frame Timer Readback Buffer"),
+                                size,
+                                usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                                mapped_at_creation: false,
+                            }),
+                            mapping: None,
+                        })
+                        .collect(),
+                    next: 0,
+                    copied: false,
+                    period: queue.get_timestamp_period(),
+                }
+            });
+
+        FrameTimer {
+            enabled: false,
+            sections: sections.to_vec(),
+            queries,
+            gpu_totals: vec![0.0; sections.len()],
+            gpu_frames: 0,
+            frame_start: Instant::now(),
+            frame_total: 0.0,
+            encode_start: Instant::now(),
+            encode_total: 0.0,
+            frames: 0,
+        }
+    }
+
+    /// Starts or stops timing, starting over from the next frame.
+    pub fn toggle(&mut self) {
+        self.enabled = !self.enabled;
+        self.reset();
+    }
+
+    /// Records a GPU timestamp, `index` counts the marks made so far this frame.
+    pub fn mark(&mut self, encoder: &mut wgpu::CommandEncoder, index: u32) {
+        if !self.enabled {
+            return;
+        }
+        if index == 0 {
+            self.encode_start = Instant::now();
+        }
+        if let Some(queries) = &self.queries {
+            encoder.write_timestamp(&queries.query_set, index);
+        }
+    }
+
+    /// Copies this frame's timestamps out, call after the last mark and before submitting. They
+    /// are dropped if every readback buffer is still waiting to be read.
+    pub fn resolve(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        if !self.enabled {
+            return;
+        }
+        self.encode_total += self.encode_start.elapsed().as_secs_f64() * 1000.0;
+        if let Some(queries) = &mut self.queries {
+            let readback = &queries.readbacks[queries.next];
+            if readback.mapping.is_some() {
+                return;
+            }
+            let count = self.sections.len() as u32 + 1;
+            encoder.resolve_query_set(&queries.query_set, 0..count, &queries.resolve_buffer, 0);
+            encoder.copy_buffer_to_buffer(
+                &queries.resolve_buffer,
+                0,
+                &readback.buffer,
+                0,
+                queries.resolve_buffer.size(),
+            );
+            queries.copied = true;
+        }
+    }
+
+    /// Accumulates the submitted frame, returning the averages every `REPORT_INTERVAL` frames.
+    ///
+    /// Starts reading back this frame's timestamps and adds up those of earlier frames that have
+    /// arrived, without waiting for the GPU.
+    pub fn end_frame(&mut self, device: &Device, label: &str) -> Option<String> {
+        if let Some(queries) = &mut self.queries {
+            if queries.copied {
+                let done = Arc::new(Mutex::new(None));
+                let result = done.clone();
+                queries.readbacks[queries.next]
+                    .buffer
+                    .slice(..)
+                    .map_async(wgpu::MapMode::Read, move |mapped| {
+                        *result.lock().unwrap() = Some(mapped.is_ok())
+                    });
+                queries.readbacks[queries.next].mapping = Some(done);
+                queries.next = (queries.next + 1) % READBACK_FRAMES;
+                queries.copied = false;
+            }
+            if queries
+                .readbacks
+                .iter()
+                .any(|readback| readback.mapping.is_some())
+            {
+                device.poll(wgpu::Maintain::Poll);
+            }
+            for readback in &mut queries.readbacks {
+                let Some(mapped) = readback
+                    .mapping
+                    .as_ref()
+                    .and_then(|done| *done.lock().unwrap())
+                else {
+                    continue;
+                };
+                readback.mapping = None;
+                if !mapped {
+                    continue;
+                }
+                {
+                    let timestamps: Vec<u64> =
+                        bytemuck::cast_slice(&readback.buffer.slice(..).get_mapped_range())
+                            .to_vec();
+                    for (total, pair) in self.gpu_totals.iter_mut().zip(timestamps.windows(2)) {
+                        let nanoseconds =
+                            pair[1].wrapping_sub(pair[0]) as f64 * queries.period as f64;
+                        *total += nanoseconds / 1_000_000.0;
+                    }
+                }
+                readback.buffer.unmap();
+                self.gpu_frames += 1;
+            }
+        }
+        if !self.enabled {
+            return None;
+        }
+
+        let now = Instant::now();
+        self.frame_total += (now - self.frame_start).as_secs_f64() * 1000.0;
+        self.frame_start = now;
+        self.frames += 1;
+        if self.frames < REPORT_INTERVAL {
+            return None;
+        }
+
+        let frames = self.frames as f64;
+        let mut report = format!(
+            "{label}: frame {:.2} ms, encoding {:.3} ms",
+            self.frame_total / frames,
+            self.encode_total / frames
+        );
+        if self.queries.is_some() && self.gpu_frames > 0 {
+            for (section, total) in self.sections.iter().zip(&self.gpu_totals) {
+                report += &format!(", {section} {:.3} ms", total / self.gpu_frames as f64);
+            }
+        }
+        self.reset();
+        Some(report)
+    }
+
+    fn reset(&mut self) {
+        self.gpu_totals.iter_mut().for_each(|total| *total = 0.0);
+        self.gpu_frames = 0;
+        self.frame_start = Instant::now();
+        self.frame_total = 0.0;
+        self.encode_total = 0.0;
+        self.frames = 0;
+    }
+}
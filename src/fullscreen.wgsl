@@ -0,0 +1,17 @@
+// fullscreen.wgsl
+// Vertex stage of the screen-space passes: one triangle covering the whole viewport.
+
+struct FullscreenOutput {
+    @builtin(position) position: vec4<f32>,
+    // Texture coordinates of the target, top-left origin.
+    @location(0) uv: vec2<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> FullscreenOutput {
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    var out: FullscreenOutput;
+    out.position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    out.uv = vec2<f32>(uv.x, 1.0 - uv.y);
+    return out;
+}
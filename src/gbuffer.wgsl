@@ -0,0 +1,24 @@
+// gbuffer.wgsl
+// Geometry pass of the deferred renderer: stores the sampled material of the nearest surface.
+
+struct GBufferOutput {
+    @location(0) albedo: vec4<f32>,
+    @location(1) normal: vec4<f32>,
+    // Metallic, roughness and occlusion.
+    @location(2) material: vec4<f32>,
+    @location(3) emissive: vec4<f32>,
+    @location(4) velocity: vec2<f32>,
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> GBufferOutput {
+    let sample = sample_material(in);
+
+    var out: GBufferOutput;
+    out.albedo = vec4<f32>(sample.base_color.rgb, 1.0);
+    out.normal = vec4<f32>(sample.normal, 0.0);
+    out.material = vec4<f32>(sample.metallic, sample.roughness, sample.occlusion, 1.0);
+    out.emissive = vec4<f32>(sample.emissive, 1.0);
+    out.velocity = motion_vector(in.current_position, in.previous_position);
+    return out;
+}
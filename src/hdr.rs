@@ -0,0 +1,211 @@
+use wgpu::util::DeviceExt;
+use wgpu::{BindGroup, BindGroupLayout, Device, Queue};
+
+use crate::bind_groups::{create_composite_bind_group, create_composite_bind_group_layout};
+use crate::bloom::Bloom;
+use crate::exposure::AutoExposure;
+use crate::graphics_context::create_render_pipeline;
+
+/// Floating point color target the scene is drawn into, so emissive surfaces and bright lights
+/// keep values above 1 for bloom.
+pub struct HdrTarget {
+    pub texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+}
+
+impl HdrTarget {
+    pub const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+    pub fn new(device: &Device, config: &wgpu::SurfaceConfiguration) -> HdrTarget {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("hdr_target"),
+            size: wgpu::Extent3d {
+                width: config.width,
+                height: config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: Self::FORMAT,
+            // Temporal anti-aliasing copies its resolved frame back in.
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        HdrTarget { texture, view }
+    }
+}
+
+/// Operator mapping exposed HDR colors into the displayable range. The discriminants are the
+/// `TONEMAP_*` constants of `composite.wgsl`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Tonemapping {
+    /// Clamps at 1, for comparing against the other operators.
+    None,
+    Reinhard,
+    AcesFilmic,
+    Agx,
+}
+
+impl Tonemapping {
+    pub fn next(self) -> Tonemapping {
+        match self {
+            Tonemapping::None => Tonemapping::Reinhard,
+            Tonemapping::Reinhard => Tonemapping::AcesFilmic,
+            Tonemapping::AcesFilmic => Tonemapping::Agx,
+            Tonemapping::Agx => Tonemapping::None,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct CompositeUniform {
+    bloom_intensity: f32,
+    exposure: f32,
+    tonemapping: u32,
+    auto_exposure: u32,
+}
+
+/// Writes the HDR target to the surface: adds the bloom on top, scales by the exposure and
+/// tonemaps. The exposure is either set by hand or adapted by `AutoExposure`.
+pub struct CompositePass {
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: BindGroupLayout,
+    sampler: wgpu::Sampler,
+    uniform_buffer: wgpu::Buffer,
+    bind_group: BindGroup,
+}
+
+impl CompositePass {
+    pub fn new(
+        device: &Device,
+        config: &wgpu::SurfaceConfiguration,
+        hdr: &HdrTarget,
+        bloom: &Bloom,
+        auto_exposure: &AutoExposure,
+    ) -> CompositePass {
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Composite Buffer"),
+            contents: bytemuck::cast_slice(&[CompositeUniform {
+                bloom_intensity: 0.0,
+                exposure: 1.0,
+                tonemapping: Tonemapping::None as u32,
+                auto_exposure: 0,
+            }]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("composite_sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let bind_group_layout = create_composite_bind_group_layout(device);
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Composite Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = create_render_pipeline(
+            device,
+            &layout,
+            &[config.format],
+            None,
+            &[],
+            wgpu::ShaderModuleDescriptor {
+                label: Some("Composite Shader"),
+                source: wgpu::ShaderSource::Wgsl(
+                    concat!(
+                        include_str!("fullscreen.wgsl"),
+                        include_str!("composite.wgsl")
+                    )
+                    .into(),
+                ),
+            },
+        );
+        let bind_group = create_composite_bind_group(
+            device,
+            &hdr.view,
+            bloom.output(),
+            &sampler,
+            &uniform_buffer,
+            &auto_exposure.exposure_buffer,
+            &bind_group_layout,
+        );
+
+        CompositePass {
+            pipeline,
+            bind_group_layout,
+            sampler,
+            uniform_buffer,
+            bind_group,
+        }
+    }
+
+    /// Rebinds the targets, call after resizing `hdr` and `bloom`.
+    pub fn resize(
+        &mut self,
+        device: &Device,
+        hdr: &HdrTarget,
+        bloom: &Bloom,
+        auto_exposure: &AutoExposure,
+    ) {
+        self.bind_group = create_composite_bind_group(
+            device,
+            &hdr.view,
+            bloom.output(),
+            &self.sampler,
+            &self.uniform_buffer,
+            &auto_exposure.exposure_buffer,
+            &self.bind_group_layout,
+        );
+    }
+
+    /// `bloom_intensity` scales the bloom added to the scene, 0 while bloom is disabled.
+    /// `exposure` is in stops, each one doubling the brightness before tonemapping. With
+    /// `auto_exposure` it compensates the adapted exposure instead of replacing it.
+    pub fn update(
+        &self,
+        queue: &Queue,
+        bloom_intensity: f32,
+        exposure: f32,
+        auto_exposure: bool,
+        tonemapping: Tonemapping,
+    ) {
+        let uniform = CompositeUniform {
+            bloom_intensity,
+            exposure: exposure.exp2(),
+            tonemapping: tonemapping as u32,
+            auto_exposure: auto_exposure as u32,
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+    }
+
+    pub fn draw(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Composite Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
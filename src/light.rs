@@ -1,19 +1,78 @@
+pub const MAX_SHADOW_CASTERS: usize = 4;
+
 #[repr(C)]
 #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct LightUniform {
     pub(crate) position: [f32; 3],
-    _padding: u32,
+    pub(crate) range: f32,
     color: [f32; 3],
-    _padding2: u32,
+    pub(crate) shadow_index: i32,
 }
 
 impl LightUniform {
-    pub fn new(position: [f32; 3], color: [f32; 3]) -> Self {
+    pub fn new(position: [f32; 3], color: [f32; 3], range: f32) -> Self {
         Self {
             position,
-            _padding: 0,
+            range,
             color,
-            _padding2: 0
+            shadow_index: -1,
+        }
+    }
+
+    pub fn casts_shadow(&self) -> bool {
+        self.shadow_index >= 0
+    }
+}
+
+/// Hands out shadow map slots to the first `MAX_SHADOW_CASTERS` lights, the rest are unshadowed.
+pub fn assign_shadow_casters(lights: &mut [LightUniform]) {
+    for (i, light) in lights.iter_mut().enumerate() {
+        light.shadow_index = if i < MAX_SHADOW_CASTERS { i as i32 } else { -1 };
+    }
+}
+
+/// Small lights of varying hue spread evenly over a disk around the origin, for scenes with many
+/// lights. None of them cast shadows.
+pub fn light_field(count: usize, radius: f32, height: f32, range: f32) -> Vec<LightUniform> {
+    const GOLDEN_ANGLE: f32 = 2.399_963;
+    (0..count)
+        .map(|i| {
+            let angle = i as f32 * GOLDEN_ANGLE;
+            let distance = radius * ((i as f32 + 0.5) / count as f32).sqrt();
+            let color = [0.0, 1.0, 2.0]
+                .map(|phase: f32| 0.5 + 0.5 * (angle + phase * std::f32::consts::TAU / 3.0).cos());
+            LightUniform::new(
+                [distance * angle.cos(), height, distance * angle.sin()],
+                color,
+                range,
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spreads_a_light_field_over_the_disk() {
+        let lights = light_field(64, 4.0, 1.5, 2.0);
+        assert_eq!(lights.len(), 64);
+        for light in &lights {
+            let [x, y, z] = light.position;
+            assert!(x.hypot(z) <= 4.0);
+            assert_eq!(y, 1.5);
+            assert_eq!(light.range, 2.0);
+            assert!(!light.casts_shadow());
+            assert!(light.color.iter().all(|value| (0.0..=1.0).contains(value)));
+        }
+        // Evenly spread: no two lights crowd each other.
+        for (i, a) in lights.iter().enumerate() {
+            for b in &lights[i + 1..] {
+                let distance = (a.position[0] - b.position[0]).hypot(a.position[2] - b.position[2]);
+                assert!(distance > 0.3, "{:?} and {:?}", a.position, b.position);
+            }
         }
+        assert!(light_field(0, 4.0, 1.5, 2.0).is_empty());
     }
 }
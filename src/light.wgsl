@@ -3,16 +3,22 @@
 
 struct Camera {
     view_proj: mat4x4<f32>,
+    view_position: vec4<f32>,
+    inv_view_proj: mat4x4<f32>,
+    unjittered_view_proj: mat4x4<f32>,
+    prev_view_proj: mat4x4<f32>,
 }
 @group(0) @binding(0)
 var<uniform> camera: Camera;
 
 struct Light {
     position: vec3<f32>,
+    range: f32,
     color: vec3<f32>,
+    shadow_index: i32,
 }
 @group(1) @binding(0)
-var<uniform> light: Light;
+var<storage, read> lights: array<Light>;
 
 struct VertexInput {
     @location(0) position: vec3<f32>,
@@ -21,22 +27,44 @@ struct VertexInput {
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) color: vec3<f32>,
+    @location(1) current_position: vec4<f32>,
+    @location(2) previous_position: vec4<f32>,
 };
 
 @vertex
 fn vs_main(
     model: VertexInput,
+    @builtin(instance_index) light_index: u32,
 ) -> VertexOutput {
-    let scale = 0.25;
+    let light = lights[light_index];
+    // Keep gizmos of short range lights from swallowing the area they light.
+    let scale = min(0.25, light.range * 0.02);
+    let world_position = vec4<f32>(model.position * scale + light.position, 1.0);
     var out: VertexOutput;
-    out.clip_position = camera.view_proj * vec4<f32>(model.position * scale + light.position, 1.0);
+    out.clip_position = camera.view_proj * world_position;
     out.color = light.color;
+    // Light positions aren't kept from the previous frame, so the gizmos only carry the camera's
+    // motion and rely on the history clamp for their own.
+    out.current_position = camera.unjittered_view_proj * world_position;
+    out.previous_position = camera.prev_view_proj * world_position;
     return out;
 }
 
 // Fragment shader
 
+// Far above any lit surface, so the bloom threshold turns the gizmos into glowing bulbs.
+const GIZMO_INTENSITY: f32 = 8.0;
+
+struct FragmentOutput {
+    @location(0) color: vec4<f32>,
+    @location(1) velocity: vec2<f32>,
+}
+
 @fragment
-fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return vec4<f32>(in.color, 1.0);
+fn fs_main(in: VertexOutput) -> FragmentOutput {
+    var out: FragmentOutput;
+    out.color = vec4<f32>(in.color * GIZMO_INTENSITY, 1.0);
+    out.velocity = (in.current_position.xy / in.current_position.w
+        - in.previous_position.xy / in.previous_position.w) * vec2<f32>(0.5, -0.5);
+    return out;
 }
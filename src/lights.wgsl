@@ -0,0 +1,106 @@
+// lights.wgsl
+// Point lights and their cube-map shadows, shared by the forward shading pipelines.
+
+struct Light {
+    position: vec3<f32>,
+    range: f32,
+    color: vec3<f32>,
+    shadow_index: i32,
+}
+@group(2) @binding(0)
+var<storage, read> lights: array<Light>;
+@group(2) @binding(1)
+var t_shadow: texture_depth_cube_array;
+@group(2) @binding(2)
+var s_shadow: sampler_comparison;
+
+struct Clusters {
+    view: mat4x4<f32>,
+    inv_proj: mat4x4<f32>,
+    grid: vec3<u32>,
+    enabled: u32,
+    screen_size: vec2<f32>,
+    z_near: f32,
+    z_far: f32,
+}
+@group(2) @binding(3)
+var<uniform> clusters: Clusters;
+@group(2) @binding(4)
+var<storage, read> cluster_light_counts: array<u32>;
+@group(2) @binding(5)
+var<storage, read> cluster_light_indices: array<u32>;
+
+const MAX_LIGHTS_PER_CLUSTER: u32 = 128u;
+
+// Smoothly fades a light out as it reaches its range, so culling lights at their range is seamless.
+fn range_attenuation(distance: f32, range: f32) -> f32 {
+    let ratio = distance / range;
+    let window = clamp(1.0 - ratio * ratio * ratio * ratio, 0.0, 1.0);
+    return window * window;
+}
+
+// Froxel containing a fragment, from its framebuffer position and its distance along the view axis.
+fn cluster_index(frag_coord: vec4<f32>, world_position: vec3<f32>) -> u32 {
+    let depth = -(clusters.view * vec4<f32>(world_position, 1.0)).z;
+    let slice = log(depth / clusters.z_near) / log(clusters.z_far / clusters.z_near);
+    let z = u32(clamp(slice * f32(clusters.grid.z), 0.0, f32(clusters.grid.z - 1u)));
+    let tile = frag_coord.xy / clusters.screen_size * vec2<f32>(clusters.grid.xy);
+    let x = min(u32(tile.x), clusters.grid.x - 1u);
+    let y = min(u32(tile.y), clusters.grid.y - 1u);
+    return x + y * clusters.grid.x + z * clusters.grid.x * clusters.grid.y;
+}
+
+// Number of lights to evaluate for a cluster, every light when clustering is disabled.
+fn cluster_light_count(cluster: u32) -> u32 {
+    if (clusters.enabled == 0u) {
+        return arrayLength(&lights);
+    }
+    return cluster_light_counts[cluster];
+}
+
+fn cluster_light(cluster: u32, i: u32) -> Light {
+    if (clusters.enabled == 0u) {
+        return lights[i];
+    }
+    return lights[cluster_light_indices[cluster * MAX_LIGHTS_PER_CLUSTER + i]];
+}
+
+const SHADOW_SAMPLES: u32 = 20u;
+const SHADOW_OFFSETS: array<vec3<f32>, 20> = array<vec3<f32>, 20>(
+    vec3<f32>(1.0, 1.0, 1.0), vec3<f32>(1.0, -1.0, 1.0), vec3<f32>(-1.0, -1.0, 1.0), vec3<f32>(-1.0, 1.0, 1.0),
+    vec3<f32>(1.0, 1.0, -1.0), vec3<f32>(1.0, -1.0, -1.0), vec3<f32>(-1.0, -1.0, -1.0), vec3<f32>(-1.0, 1.0, -1.0),
+    vec3<f32>(1.0, 1.0, 0.0), vec3<f32>(1.0, -1.0, 0.0), vec3<f32>(-1.0, -1.0, 0.0), vec3<f32>(-1.0, 1.0, 0.0),
+    vec3<f32>(1.0, 0.0, 1.0), vec3<f32>(-1.0, 0.0, 1.0), vec3<f32>(1.0, 0.0, -1.0), vec3<f32>(-1.0, 0.0, -1.0),
+    vec3<f32>(0.0, 1.0, 1.0), vec3<f32>(0.0, -1.0, 1.0), vec3<f32>(0.0, -1.0, -1.0), vec3<f32>(0.0, 1.0, -1.0),
+);
+
+// Percentage closer filtering over a small disk around the light-to-fragment direction.
+fn point_shadow(light: Light, world_position: vec3<f32>, world_normal: vec3<f32>) -> f32 {
+    if (light.shadow_index < 0) {
+        return 1.0;
+    }
+
+    let light_to_fragment = world_position - light.position;
+    let distance = length(light_to_fragment);
+    if (distance >= light.range) {
+        return 1.0;
+    }
+
+    let slope = 1.0 - max(dot(world_normal, -light_to_fragment / distance), 0.0);
+    let bias = (0.02 + 0.08 * slope) / light.range;
+    let reference = distance / light.range - bias;
+    let disk_radius = 0.02 * distance;
+
+    var offsets = SHADOW_OFFSETS;
+    var lit = 0.0;
+    for (var i = 0u; i < SHADOW_SAMPLES; i++) {
+        lit += textureSampleCompareLevel(
+            t_shadow,
+            s_shadow,
+            light_to_fragment + offsets[i] * disk_radius,
+            light.shadow_index,
+            reference,
+        );
+    }
+    return lit / f32(SHADOW_SAMPLES);
+}
@@ -6,28 +6,68 @@ use camera::{Camera, CameraController, CameraEvent, CameraUniform};
 use cgmath::{InnerSpace, Rotation3, Zero};
 use wgpu::util::DeviceExt;
 use winit::event::VirtualKeyCode;
-use crate::bind_groups::{create_light_bind_group, create_light_bind_group_layout};
+use crate::bind_groups::{
+    create_environment_bind_group, create_environment_bind_group_layout,
+    create_gradient_sky_bind_group, create_gradient_sky_bind_group_layout, create_light_bind_group,
+    create_light_bind_group_layout, create_packed_material_bind_group_layout,
+    create_texture_bind_group, create_texture_bind_group_layout,
+};
 
 use crate::graphics_context::{create_render_pipeline, GraphicsContext};
 use crate::instance::Instance;
-use crate::light::LightUniform;
-use crate::object::{DrawLight, DrawModel, Vertex};
-use crate::render_pass::RenderPass;
-use crate::texture::Texture;
+use crate::cluster::LightClusters;
+use crate::exposure::AutoExposure;
+use crate::bloom::Bloom;
+use crate::frame_timer::FrameTimer;
+use crate::hdr::{CompositePass, HdrTarget, Tonemapping};
+use crate::light::{assign_shadow_casters, light_field, LightUniform};
+use crate::object::{
+    DrawLight, DrawModel, Material, MaterialTextures, MaterialUniform, PackedMaterials, Vertex,
+};
+use crate::outline::Outline;
+use crate::post::PostStack;
+use crate::deferred::DeferredPass;
+use crate::render_pass::{MaterialBinding, RenderPass, RenderPath, ShadingModel};
+use crate::shadow::PointShadowMaps;
+use crate::skybox::{GradientSky, SkyMode, SkyboxPass};
+use crate::ssao::SsaoPass;
+use crate::taa::Taa;
+use crate::procedural::{Pattern, PatternPipeline};
+use crate::texture::{Texture, TextureCache, TextureHelpers, TextureOptions};
 use crate::window::{Window, WindowEvents};
 
 mod bind_groups;
+mod bloom;
 mod camera;
+mod cluster;
+mod compressed;
+mod deferred;
+mod environment;
+mod error;
+mod exposure;
+mod frame_timer;
 mod graphics_context;
+mod hdr;
 mod instance;
 mod object;
+mod outline;
+mod post;
+mod procedural;
 mod render_pass;
 mod resources;
+mod shadow;
+mod skybox;
+mod ssao;
+mod taa;
 mod texture;
 mod window;
 mod light;
 
 fn main() {
+    // Messages from this crate, like textures replaced by fallbacks and the frame timings toggled
+    // with F, show unless RUST_LOG says otherwise.
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("wgpu_demo=info"))
+        .init();
     let window = Window::new();
     let mut context = GraphicsContext::new(&window);
 
@@ -57,16 +97,37 @@ fn main() {
     let camera_bind_group =
         create_camera_bind_group(&context.device, &camera_buffer, &camera_bind_group_layout);
 
-    let mut light_uniform = LightUniform::new([2.0, 2.0, 2.0], [1.0, 1.0, 1.0]);
+    let mut lights = vec![
+        LightUniform::new([2.0, 2.0, 2.0], [1.0, 1.0, 1.0], 30.0),
+        LightUniform::new([-7.0, 3.0, -4.0], [0.9, 0.5, 0.2], 20.0),
+    ];
+    assign_shadow_casters(&mut lights);
+    lights.extend(light_field(256, 16.0, 1.2, 2.5));
     let light_buffer = context
         .device
         .create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Light Buffer"),
-            contents: bytemuck::cast_slice(&[light_uniform]),
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            contents: bytemuck::cast_slice(&lights),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
         });
+    let shadow_maps = PointShadowMaps::new(&context.device);
+    let light_clusters = LightClusters::new(&context.device, &light_buffer);
+    let mut clustered_lighting = true;
     let light_bind_group_layout = create_light_bind_group_layout(&context.device);
-    let light_bind_group = create_light_bind_group(&context.device, &light_buffer, &light_bind_group_layout);
+    let light_bind_group = create_light_bind_group(
+        &context.device,
+        &light_buffer,
+        &shadow_maps,
+        &light_clusters,
+        &light_bind_group_layout,
+    );
+    let mut frame_timer = FrameTimer::new(
+        &context.device,
+        &context.queue,
+        &["shadows", "light clustering", "geometry", "ssao", "scene", "post"],
+    );
+    // Targets of the scene pass: the lit color and the motion vectors for TAA.
+    let scene_formats = [HdrTarget::FORMAT, Taa::VELOCITY_FORMAT];
     let light_render_pipeline = {
         let layout = &context.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: Some("Light Pipeline Layout"),
@@ -80,30 +141,309 @@ fn main() {
         create_render_pipeline(
             &context.device,
             layout,
-            context.config.format,
+            &scene_formats,
             Some(Texture::DEPTH_FORMAT),
             &[object::ModelVertex::desc()],
             shader,
         )
     };
     let texture_bind_group_layout = create_bind_group_layout(&context.device);
+    let mut helpers = TextureHelpers::new();
 
+    let environment = pollster::block_on(resources::load_environment(
+        "sky.hdr",
+        &context.device,
+        &context.queue,
+    ))
+    .unwrap();
     let mut depth_texture =
         Texture::create_depth_texture(&context.device, &context.config, "depth_texture");
 
-    let pass = RenderPass::new(
+    let mut ssao_pass = SsaoPass::new(
+        &context.device,
+        &context.config,
+        &depth_texture,
+        &[
+            &texture_bind_group_layout,
+            &camera_bind_group_layout,
+            &light_bind_group_layout,
+        ],
+    );
+    let mut ssao_enabled = true;
+
+    let environment_bind_group_layout = create_environment_bind_group_layout(&context.device);
+    let mut environment_bind_group = create_environment_bind_group(
+        &context.device,
+        &environment,
+        &ssao_pass.occlusion,
+        &environment_bind_group_layout,
+    );
+
+    let skybox_bind_group_layout =
+        create_texture_bind_group_layout(&context.device, wgpu::TextureViewDimension::Cube);
+    let environment_sky_bind_group = create_texture_bind_group(
+        &context.device,
+        &environment.environment_map.view,
+        &environment.environment_map.sampler,
+        &skybox_bind_group_layout,
+    );
+    let cubemap_sky = pollster::block_on(resources::load_cube_texture(
+        &[
+            "skybox/posx.png",
+            "skybox/negx.png",
+            "skybox/posy.png",
+            "skybox/negy.png",
+            "skybox/posz.png",
+            "skybox/negz.png",
+        ],
+        &context.device,
+        &context.queue,
+        &mut helpers,
+    ))
+    .unwrap();
+    let cubemap_sky_bind_group = create_texture_bind_group(
+        &context.device,
+        &cubemap_sky.view,
+        &cubemap_sky.sampler,
+        &skybox_bind_group_layout,
+    );
+    let gradient_sky_buffer =
+        context
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Gradient Sky Buffer"),
+                contents: bytemuck::cast_slice(&[GradientSky::default()]),
+                usage: wgpu::BufferUsages::UNIFORM,
+            });
+    let gradient_sky_bind_group_layout = create_gradient_sky_bind_group_layout(&context.device);
+    let gradient_sky_bind_group = create_gradient_sky_bind_group(
+        &context.device,
+        &gradient_sky_buffer,
+        &gradient_sky_bind_group_layout,
+    );
+    let skybox_pass = SkyboxPass::new(
+        &context.device,
+        &scene_formats,
+        &camera_bind_group_layout,
+        &skybox_bind_group_layout,
+        &gradient_sky_bind_group_layout,
+    );
+    let mut sky_mode = SkyMode::Environment;
+
+    let forward_bind_group_layouts = [
+        &texture_bind_group_layout,
+        &camera_bind_group_layout,
+        &light_bind_group_layout,
+        &environment_bind_group_layout,
+    ];
+    let packed_material_bind_group_layout =
+        create_packed_material_bind_group_layout(&context.device);
+    let packed_bind_group_layouts = [
+        &packed_material_bind_group_layout,
+        &camera_bind_group_layout,
+        &light_bind_group_layout,
+        &environment_bind_group_layout,
+    ];
+    let forward_pass = |layouts: &[&wgpu::BindGroupLayout], shading_model, materials| {
+        RenderPass::new(
+            &context.device,
+            &scene_formats,
+            layouts,
+            shading_model,
+            materials,
+        )
+    };
+    let pass = forward_pass(
+        &forward_bind_group_layouts,
+        ShadingModel::Lambert,
+        MaterialBinding::PerMaterial,
+    );
+    let pbr_pass = forward_pass(
+        &forward_bind_group_layouts,
+        ShadingModel::Pbr,
+        MaterialBinding::PerMaterial,
+    );
+    let packed_pass = forward_pass(
+        &packed_bind_group_layouts,
+        ShadingModel::Lambert,
+        MaterialBinding::Packed,
+    );
+    let packed_pbr_pass = forward_pass(
+        &packed_bind_group_layouts,
+        ShadingModel::Pbr,
+        MaterialBinding::Packed,
+    );
+    let mut shading_model = ShadingModel::Lambert;
+    let mut deferred_pass = DeferredPass::new(
+        &context.device,
+        &context.config,
+        &depth_texture,
+        &forward_bind_group_layouts[..3],
+        &packed_material_bind_group_layout,
+        &[
+            &camera_bind_group_layout,
+            &light_bind_group_layout,
+            &environment_bind_group_layout,
+        ],
+    );
+    let mut render_path = RenderPath::Forward;
+
+    let mut hdr_target = HdrTarget::new(&context.device, &context.config);
+    let mut taa = Taa::new(
+        &context.device,
+        &context.config,
+        &hdr_target,
+        &depth_texture,
+    );
+    let mut taa_enabled = true;
+    let mut bloom = Bloom::new(&context.device, &context.config, &hdr_target);
+    let mut auto_exposure = AutoExposure::new(&context.device, &context.config, &hdr_target);
+    let mut composite_pass = CompositePass::new(
+        &context.device,
+        &context.config,
+        &hdr_target,
+        &bloom,
+        &auto_exposure,
+    );
+    let mut bloom_enabled = true;
+    let mut tonemapping = Tonemapping::AcesFilmic;
+    let mut auto_exposure_enabled = true;
+    let mut exposure = 0.0f32;
+
+    // Toggled with the number keys in this order.
+    let mut post_stack = PostStack::new(
+        &context.device,
+        &context.config,
+        context.config.format,
+        &depth_texture,
+    );
+    post_stack.push(post::fog(
+        &context.device,
+        &post_stack,
+        post::FogSettings::default(),
+    ));
+    post_stack.push(
+        post::depth_of_field(
+            &context.device,
+            &post_stack,
+            post::DepthOfFieldSettings::default(),
+        )
+        .disabled(),
+    );
+    post_stack.push(post::color_grade(
+        &context.device,
+        &context.queue,
+        &mut helpers,
+        &post_stack,
+        post::warm_contrast,
+    ));
+    post_stack.push(post::fxaa(&context.device, &post_stack));
+    post_stack.push(post::chromatic_aberration(&context.device, &post_stack).disabled());
+    post_stack.push(post::vignette(&context.device, &post_stack));
+    post_stack.push(post::film_grain(&context.device, &post_stack).disabled());
+
+    let mut outline = Outline::new(
         &context.device,
         &context.config,
-        &[&texture_bind_group_layout, &camera_bind_group_layout, &light_bind_group_layout],
+        &[
+            &texture_bind_group_layout,
+            &camera_bind_group_layout,
+            &light_bind_group_layout,
+        ],
+        context.config.format,
     );
+    // Instances outlined as selected, a range into the instance buffer.
+    let mut selection = 0..0;
 
+    let mut texture_cache = TextureCache::new();
     let obj_model = pollster::block_on(resources::load_model(
         "cube.obj",
         &context.device,
         &context.queue,
+        &mut helpers,
+        &mut texture_cache,
         &texture_bind_group_layout,
     ))
     .unwrap();
+    // The model's materials in texture arrays, drawn with one bind group when packing is on.
+    let fallback_textures = MaterialTextures::fallback(
+        &context.device,
+        &context.queue,
+        &mut helpers,
+        &mut texture_cache,
+    );
+    let packed_materials = PackedMaterials::new(
+        &context.device,
+        &context.queue,
+        &mut helpers,
+        "cube.obj",
+        &obj_model,
+        &fallback_textures,
+        &packed_material_bind_group_layout,
+    )
+    .map_err(|error| log::warn!("{error}, binding materials one at a time"))
+    .ok();
+    let mut material_binding = MaterialBinding::PerMaterial;
+
+    // Procedural stand-ins for the model's base color, cycled through to check its UV layout.
+    let debug_patterns = [
+        Pattern::Checkerboard {
+            cells: 8,
+            colors: [[220, 220, 220, 255], [40, 40, 40, 255]],
+        },
+        Pattern::Grid {
+            cells: 8,
+            line_width: 4.0,
+            line: [255, 160, 0, 255],
+            background: [30, 30, 30, 255],
+        },
+        Pattern::LinearGradient {
+            direction: [1.0, 1.0],
+            colors: [[255, 40, 40, 255], [40, 40, 255, 255]],
+        },
+        Pattern::RadialGradient {
+            colors: [[255, 255, 255, 255], [20, 20, 20, 255]],
+        },
+        Pattern::Perlin {
+            cells: 4,
+            octaves: 5,
+            seed: 1,
+            colors: [[20, 20, 20, 255], [235, 235, 235, 255]],
+        },
+        Pattern::Simplex {
+            cells: 4,
+            octaves: 5,
+            seed: 1,
+            colors: [[20, 20, 20, 255], [235, 235, 235, 255]],
+        },
+    ];
+    let pattern_pipeline = PatternPipeline::new(&context.device);
+    let debug_materials = debug_patterns
+        .iter()
+        .map(|pattern| {
+            let diffuse = Texture::generate(
+                &context.device,
+                &context.queue,
+                &mut helpers,
+                &pattern_pipeline,
+                pattern,
+                (512, 512),
+                Some("debug_pattern_texture"),
+                &TextureOptions::default().repeat(),
+            );
+            Material::new(
+                &context.device,
+                &texture_bind_group_layout,
+                format!("{pattern:?}"),
+                MaterialTextures {
+                    diffuse: std::sync::Arc::new(diffuse),
+                    ..fallback_textures.clone()
+                },
+                MaterialUniform::default(),
+            )
+        })
+        .collect::<Vec<_>>();
+    let mut debug_material: Option<usize> = None;
 
     const NUM_INSTANCES_PER_ROW: u32 = 10;
     const INSTANCE_DISPLACEMENT: cgmath::Vector3<f32> = cgmath::Vector3::new(
@@ -142,24 +482,76 @@ fn main() {
 
     window.run(move |event| match event {
         WindowEvents::Resize { width, height } => {
+            context.resize(width, height);
             depth_texture =
                 Texture::create_depth_texture(&context.device, &context.config, "depth_texture");
-            context.resize(width, height)
+            deferred_pass.resize(&context.device, &context.config, &depth_texture);
+            ssao_pass.resize(&context.device, &context.config, &depth_texture);
+            environment_bind_group = create_environment_bind_group(
+                &context.device,
+                &environment,
+                &ssao_pass.occlusion,
+                &environment_bind_group_layout,
+            );
+            hdr_target = HdrTarget::new(&context.device, &context.config);
+            taa.resize(
+                &context.device,
+                &context.config,
+                &hdr_target,
+                &depth_texture,
+            );
+            bloom.resize(&context.device, &context.config, &hdr_target);
+            auto_exposure.resize(&context.device, &context.config, &hdr_target);
+            composite_pass.resize(&context.device, &hdr_target, &bloom, &auto_exposure);
+            post_stack.resize(&context.device, &context.config, &depth_texture);
+            outline.resize(&context.device, &context.config);
         }
         WindowEvents::Draw => {
             //update camera
+            if taa_enabled {
+                taa.update(&context.queue, &camera);
+                camera.set_jitter(taa.next_jitter());
+            } else {
+                camera.set_jitter(cgmath::Vector2::zero());
+            }
             camera_uniform.update_view_proj(&camera);
             context
                 .queue
                 .write_buffer(&camera_buffer, 0, bytemuck::cast_slice(&[camera_uniform]));
 
-            //update light
-            let old_position: cgmath::Vector3<_> = light_uniform.position.into();
-            light_uniform.position =
-                (cgmath::Quaternion::from_axis_angle((0.0, 1.0, 0.0).into(), cgmath::Deg(1.0))
-                    * old_position)
-                    .into();
-            context.queue.write_buffer(&light_buffer, 0, bytemuck::cast_slice(&[light_uniform]));
+            //update lights
+            for light in &mut lights {
+                let old_position: cgmath::Vector3<_> = light.position.into();
+                light.position =
+                    (cgmath::Quaternion::from_axis_angle((0.0, 1.0, 0.0).into(), cgmath::Deg(1.0))
+                        * old_position)
+                        .into();
+            }
+            context
+                .queue
+                .write_buffer(&light_buffer, 0, bytemuck::cast_slice(&lights));
+            shadow_maps.update(&context.queue, &lights);
+            light_clusters.update(
+                &context.queue,
+                &camera,
+                &context.config,
+                clustered_lighting,
+            );
+            ssao_pass.update(&context.queue, &camera);
+            let bloom_intensity = if bloom_enabled {
+                Bloom::INTENSITY
+            } else {
+                0.0
+            };
+            auto_exposure.update(&context.queue);
+            composite_pass.update(
+                &context.queue,
+                bloom_intensity,
+                exposure,
+                auto_exposure_enabled,
+                tonemapping,
+            );
+            post_stack.update(&context.queue, &camera);
 
             //render
 
@@ -174,26 +566,138 @@ fn main() {
                         label: Some("Render Encoder"),
                     });
 
-            {
-                let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                    label: Some("Render Pass"),
+            frame_timer.mark(&mut encoder, 0);
+            shadow_maps.render(
+                &mut encoder,
+                &lights,
+                &obj_model,
+                &instance_buffer,
+                0..instances.len() as u32,
+            );
+            frame_timer.mark(&mut encoder, 1);
+            if clustered_lighting {
+                light_clusters.build(&mut encoder);
+            }
+            frame_timer.mark(&mut encoder, 2);
+
+            // Packing falls back to a bind group per material for models it can't pack, and for
+            // debug materials.
+            let debug_material = debug_material.map(|i| &debug_materials[i]);
+            let packed_materials = packed_materials
+                .as_ref()
+                .filter(|_| material_binding == MaterialBinding::Packed)
+                .filter(|_| debug_material.is_none());
+            let materials = match packed_materials {
+                Some(_) => MaterialBinding::Packed,
+                None => MaterialBinding::PerMaterial,
+            };
+
+            // SSAO reads the scene depth, so the forward path lays it down in a prepass.
+            if render_path == RenderPath::Deferred || ssao_enabled {
+                let mut geometry_pass = match render_path {
+                    RenderPath::Forward => {
+                        ssao_pass.begin_depth_prepass(&mut encoder, &depth_texture.view)
+                    }
+                    RenderPath::Deferred => {
+                        deferred_pass.begin_geometry_pass(
+                            &mut encoder,
+                            materials,
+                            &taa.velocity,
+                            &depth_texture.view,
+                        )
+                    }
+                };
+                geometry_pass.set_vertex_buffer(1, instance_buffer.slice(..));
+                // The depth prepass keeps a bind group per material.
+                match (
+                    debug_material,
+                    packed_materials.filter(|_| render_path == RenderPath::Deferred),
+                ) {
+                    (Some(material), _) => geometry_pass.draw_model_with_material_instanced(
+                        &obj_model,
+                        material,
+                        0..instances.len() as u32,
+                        &camera_bind_group,
+                        &light_bind_group,
+                    ),
+                    (None, Some(packed)) => geometry_pass.draw_model_packed_instanced(
+                        &obj_model,
+                        packed,
+                        0..instances.len() as u32,
+                        &camera_bind_group,
+                        &light_bind_group,
+                    ),
+                    (None, None) => geometry_pass.draw_model_instanced(
+                        &obj_model,
+                        0..instances.len() as u32,
+                        &camera_bind_group,
+                        &light_bind_group,
+                    ),
+                }
+            }
+            frame_timer.mark(&mut encoder, 3);
+            if ssao_enabled {
+                ssao_pass.render(&mut encoder);
+            } else {
+                ssao_pass.clear(&mut encoder);
+            }
+            frame_timer.mark(&mut encoder, 4);
+
+            if render_path == RenderPath::Deferred {
+                let mut lighting_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("Lighting Pass"),
                     color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                        view: &view,
+                        view: &hdr_target.view,
                         resolve_target: None,
                         ops: wgpu::Operations {
-                            load: wgpu::LoadOp::Clear(wgpu::Color {
-                                r: 0.1,
-                                g: 0.9,
-                                b: 0.3,
-                                a: 1.0,
-                            }),
+                            load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
                             store: true,
                         },
                     })],
+                    depth_stencil_attachment: None,
+                });
+                deferred_pass.draw_lighting(
+                    &mut lighting_pass,
+                    &camera_bind_group,
+                    &light_bind_group,
+                    &environment_bind_group,
+                );
+            }
+
+            {
+                // The deferred path has already shaded the scene and filled the depth buffer.
+                let (color_load, depth_load) = match render_path {
+                    RenderPath::Forward => (
+                        wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        wgpu::LoadOp::Clear(1.0),
+                    ),
+                    RenderPath::Deferred => (wgpu::LoadOp::Load, wgpu::LoadOp::Load),
+                };
+                let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("Render Pass"),
+                    color_attachments: &[
+                        Some(wgpu::RenderPassColorAttachment {
+                            view: &hdr_target.view,
+                            resolve_target: None,
+                            ops: wgpu::Operations {
+                                // Every pixel ends up covered by the scene or the skybox.
+                                load: color_load,
+                                store: true,
+                            },
+                        }),
+                        Some(wgpu::RenderPassColorAttachment {
+                            view: &taa.velocity,
+                            resolve_target: None,
+                            ops: wgpu::Operations {
+                                load: color_load,
+                                store: true,
+                            },
+                        }),
+                    ],
                     depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
                         view: &depth_texture.view,
                         depth_ops: Some(wgpu::Operations {
-                            load: wgpu::LoadOp::Clear(1.0),
+                            load: depth_load,
                             store: true,
                         }),
                         stencil_ops: None,
@@ -202,23 +706,122 @@ fn main() {
                 render_pass.set_vertex_buffer(1, instance_buffer.slice(..));
 
                 render_pass.set_pipeline(&light_render_pipeline);
-                render_pass.draw_light_model(
+                render_pass.draw_light_model_instanced(
                     &obj_model,
+                    0..lights.len() as u32,
                     &camera_bind_group,
                     &light_bind_group,
                 );
 
-                render_pass.set_pipeline(&pass.render_pipeline);
-                render_pass.draw_model_instanced(
-                    &obj_model,
-                    0..instances.len() as u32,
-                    &camera_bind_group,
-                    &light_bind_group,
-                );
+                if render_path == RenderPath::Forward {
+                    let pass = match (shading_model, materials) {
+                        (ShadingModel::Lambert, MaterialBinding::PerMaterial) => &pass,
+                        (ShadingModel::Pbr, MaterialBinding::PerMaterial) => &pbr_pass,
+                        (ShadingModel::Lambert, MaterialBinding::Packed) => &packed_pass,
+                        (ShadingModel::Pbr, MaterialBinding::Packed) => &packed_pbr_pass,
+                    };
+                    render_pass.set_pipeline(&pass.render_pipeline);
+                    render_pass.set_bind_group(3, &environment_bind_group, &[]);
+                    match (debug_material, packed_materials) {
+                        (Some(material), _) => render_pass.draw_model_with_material_instanced(
+                            &obj_model,
+                            material,
+                            0..instances.len() as u32,
+                            &camera_bind_group,
+                            &light_bind_group,
+                        ),
+                        (None, Some(packed)) => render_pass.draw_model_packed_instanced(
+                            &obj_model,
+                            packed,
+                            0..instances.len() as u32,
+                            &camera_bind_group,
+                            &light_bind_group,
+                        ),
+                        (None, None) => render_pass.draw_model_instanced(
+                            &obj_model,
+                            0..instances.len() as u32,
+                            &camera_bind_group,
+                            &light_bind_group,
+                        ),
+                    }
+                }
+
+                match sky_mode {
+                    SkyMode::Environment => skybox_pass.draw_cubemap(
+                        &mut render_pass,
+                        &camera_bind_group,
+                        &environment_sky_bind_group,
+                    ),
+                    SkyMode::Cubemap => skybox_pass.draw_cubemap(
+                        &mut render_pass,
+                        &camera_bind_group,
+                        &cubemap_sky_bind_group,
+                    ),
+                    SkyMode::Gradient => skybox_pass.draw_gradient(
+                        &mut render_pass,
+                        &camera_bind_group,
+                        &gradient_sky_bind_group,
+                    ),
+                }
             }
 
+            frame_timer.mark(&mut encoder, 5);
+            if taa_enabled {
+                taa.render(&mut encoder, &hdr_target);
+            }
+            if bloom_enabled {
+                bloom.render(&mut encoder);
+            }
+            if auto_exposure_enabled {
+                auto_exposure.render(&mut encoder);
+            }
+            if post_stack.is_active() {
+                composite_pass.draw(&mut encoder, post_stack.input());
+                post_stack.render(&mut encoder, &view);
+            } else {
+                composite_pass.draw(&mut encoder, &view);
+            }
+            if !selection.is_empty() {
+                {
+                    let mut stencil_pass =
+                        outline.begin_stencil_pass(&mut encoder, &depth_texture.view);
+                    stencil_pass.set_vertex_buffer(1, instance_buffer.slice(..));
+                    stencil_pass.draw_model_instanced(
+                        &obj_model,
+                        selection.clone(),
+                        &camera_bind_group,
+                        &light_bind_group,
+                    );
+                }
+                outline.render(&mut encoder, &depth_texture.view, &view);
+            }
+            frame_timer.mark(&mut encoder, 6);
+            frame_timer.resolve(&mut encoder);
+
             context.queue.submit(std::iter::once(encoder.finish()));
             output.present();
+
+            let culling = if clustered_lighting {
+                "clustered"
+            } else {
+                "unculled"
+            };
+            let exposure_mode = if auto_exposure_enabled {
+                "auto"
+            } else {
+                "manual"
+            };
+            let report = frame_timer.end_frame(
+                &context.device,
+                &format!(
+                    "{render_path:?}, {tonemapping:?} at {exposure:+.1} EV {exposure_mode}, {} lights, {culling}, {} materials {materials:?}",
+                    lights.len(),
+                    obj_model.materials.len()
+                ),
+            );
+            if let Some(report) = report {
+                log::info!("{report}");
+            }
         }
         WindowEvents::Keyboard(keycode) => match keycode {
             VirtualKeyCode::W | VirtualKeyCode::Up => {
@@ -233,6 +836,49 @@ fn main() {
             VirtualKeyCode::D | VirtualKeyCode::Right => {
                 camera_controller.update(&mut camera, CameraEvent::Right)
             }
+            VirtualKeyCode::P => shading_model = shading_model.toggle(),
+            VirtualKeyCode::M => material_binding = material_binding.toggle(),
+            VirtualKeyCode::U => {
+                debug_material = match debug_material {
+                    Some(i) if i + 1 < debug_materials.len() => Some(i + 1),
+                    Some(_) => None,
+                    None => Some(0),
+                }
+            }
+            VirtualKeyCode::K => sky_mode = sky_mode.next(),
+            VirtualKeyCode::C => clustered_lighting = !clustered_lighting,
+            VirtualKeyCode::G => render_path = render_path.toggle(),
+            VirtualKeyCode::O => ssao_enabled = !ssao_enabled,
+            VirtualKeyCode::B => bloom_enabled = !bloom_enabled,
+            VirtualKeyCode::T => tonemapping = tonemapping.next(),
+            VirtualKeyCode::E => auto_exposure_enabled = !auto_exposure_enabled,
+            VirtualKeyCode::J => {
+                taa_enabled = !taa_enabled;
+                taa.reset();
+            }
+            VirtualKeyCode::Key1 => post_stack.toggle(0),
+            VirtualKeyCode::Key2 => post_stack.toggle(1),
+            VirtualKeyCode::Key3 => post_stack.toggle(2),
+            VirtualKeyCode::Key4 => post_stack.toggle(3),
+            VirtualKeyCode::Key5 => post_stack.toggle(4),
+            VirtualKeyCode::Key6 => post_stack.toggle(5),
+            VirtualKeyCode::Key7 => post_stack.toggle(6),
+            VirtualKeyCode::N => {
+                // Steps the selection through the rows of the grid, then back to nothing.
+                let row = if selection.is_empty() {
+                    0
+                } else {
+                    selection.end / NUM_INSTANCES_PER_ROW
+                };
+                selection = if row < NUM_INSTANCES_PER_ROW {
+                    row * NUM_INSTANCES_PER_ROW..(row + 1) * NUM_INSTANCES_PER_ROW
+                } else {
+                    0..0
+                };
+            }
+            VirtualKeyCode::F => frame_timer.toggle(),
+            VirtualKeyCode::Minus => exposure -= 0.5,
+            VirtualKeyCode::Equals => exposure += 0.5,
             _ => {}
         },
     });
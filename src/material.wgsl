@@ -0,0 +1,64 @@
+// material.wgsl
+// Material bindings, shared by the forward shading and G-buffer pipelines. Lambert shading only
+// uses the base color, normal and emissive maps.
+
+struct Material {
+    base_color: vec4<f32>,
+    emissive: vec3<f32>,
+    metallic: f32,
+    roughness: f32,
+}
+
+@group(0) @binding(0)
+var t_diffuse: texture_2d<f32>;
+@group(0) @binding(1)
+var s_diffuse: sampler;
+@group(0) @binding(2)
+var t_normal: texture_2d<f32>;
+@group(0) @binding(3)
+var s_normal: sampler;
+@group(0) @binding(4)
+var t_metallic_roughness: texture_2d<f32>;
+@group(0) @binding(5)
+var s_metallic_roughness: sampler;
+@group(0) @binding(6)
+var t_occlusion: texture_2d<f32>;
+@group(0) @binding(7)
+var s_occlusion: sampler;
+@group(0) @binding(8)
+var t_emissive: texture_2d<f32>;
+@group(0) @binding(9)
+var s_emissive: sampler;
+@group(0) @binding(10)
+var<uniform> material: Material;
+
+struct MaterialSample {
+    base_color: vec4<f32>,
+    // World space normal with the normal map applied.
+    normal: vec3<f32>,
+    metallic: f32,
+    roughness: f32,
+    occlusion: f32,
+    emissive: vec3<f32>,
+}
+
+fn sample_material(in: VertexOutput) -> MaterialSample {
+    let object_normal = textureSample(t_normal, s_normal, in.tex_coords);
+    // glTF channel layout: roughness in green, metallic in blue.
+    let metallic_roughness = textureSample(t_metallic_roughness, s_metallic_roughness, in.tex_coords);
+
+    let tangent_matrix = mat3x3<f32>(
+        normalize(in.world_tangent),
+        normalize(in.world_bitangent),
+        normalize(in.world_normal),
+    );
+
+    var out: MaterialSample;
+    out.base_color = textureSample(t_diffuse, s_diffuse, in.tex_coords) * material.base_color;
+    out.normal = normalize(tangent_matrix * (object_normal.xyz * 2.0 - 1.0));
+    out.metallic = clamp(metallic_roughness.b * material.metallic, 0.0, 1.0);
+    out.roughness = clamp(metallic_roughness.g * material.roughness, 0.04, 1.0);
+    out.occlusion = textureSample(t_occlusion, s_occlusion, in.tex_coords).r;
+    out.emissive = textureSample(t_emissive, s_emissive, in.tex_coords).rgb * material.emissive;
+    return out;
+}
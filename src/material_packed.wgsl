@@ -0,0 +1,110 @@
+// material_packed.wgsl
+// Material bindings with the textures of every material of a model packed into one 2D array per
+// map, a drop-in replacement for material.wgsl. Fragments find their material's uniform by the
+// index their vertices carry; the uniform holds the layer of each map, negative for maps the
+// material lacks.
+
+struct Material {
+    base_color: vec4<f32>,
+    emissive: vec3<f32>,
+    metallic: f32,
+    roughness: f32,
+}
+
+struct PackedMaterial {
+    factors: Material,
+    diffuse_layer: i32,
+    normal_layer: i32,
+    metallic_roughness_layer: i32,
+    occlusion_layer: i32,
+    emissive_layer: i32,
+}
+
+@group(0) @binding(0)
+var t_diffuse: texture_2d_array<f32>;
+@group(0) @binding(1)
+var s_diffuse: sampler;
+@group(0) @binding(2)
+var t_normal: texture_2d_array<f32>;
+@group(0) @binding(3)
+var s_normal: sampler;
+@group(0) @binding(4)
+var t_metallic_roughness: texture_2d_array<f32>;
+@group(0) @binding(5)
+var s_metallic_roughness: sampler;
+@group(0) @binding(6)
+var t_occlusion: texture_2d_array<f32>;
+@group(0) @binding(7)
+var s_occlusion: sampler;
+@group(0) @binding(8)
+var t_emissive: texture_2d_array<f32>;
+@group(0) @binding(9)
+var s_emissive: sampler;
+// `PackedMaterials::MAX_MATERIALS`, which keeps the array within the 16 KiB uniform buffers of
+// the most limited devices.
+const MAX_MATERIALS: u32 = 128u;
+
+@group(0) @binding(10)
+var<uniform> materials: array<PackedMaterial, MAX_MATERIALS>;
+
+struct MaterialSample {
+    base_color: vec4<f32>,
+    // World space normal with the normal map applied.
+    normal: vec3<f32>,
+    metallic: f32,
+    roughness: f32,
+    occlusion: f32,
+    emissive: vec3<f32>,
+}
+
+// The texel of `layer`, or `fallback` for a missing map. Sampling unconditionally keeps the
+// implicit derivatives in uniform control flow.
+fn sample_layer(
+    t: texture_2d_array<f32>,
+    s: sampler,
+    tex_coords: vec2<f32>,
+    layer: i32,
+    fallback: vec4<f32>,
+) -> vec4<f32> {
+    let texel = textureSample(t, s, tex_coords, max(layer, 0));
+    return select(fallback, texel, layer >= 0);
+}
+
+fn sample_material(in: VertexOutput) -> MaterialSample {
+    let material = materials[in.material];
+    let white = vec4<f32>(1.0);
+    let object_normal = sample_layer(
+        t_normal,
+        s_normal,
+        in.tex_coords,
+        material.normal_layer,
+        vec4<f32>(0.5, 0.5, 1.0, 1.0),
+    );
+    // glTF channel layout: roughness in green, metallic in blue.
+    let metallic_roughness = sample_layer(
+        t_metallic_roughness,
+        s_metallic_roughness,
+        in.tex_coords,
+        material.metallic_roughness_layer,
+        white,
+    );
+
+    let tangent_matrix = mat3x3<f32>(
+        normalize(in.world_tangent),
+        normalize(in.world_bitangent),
+        normalize(in.world_normal),
+    );
+
+    var out: MaterialSample;
+    out.base_color = sample_layer(t_diffuse, s_diffuse, in.tex_coords, material.diffuse_layer, white)
+        * material.factors.base_color;
+    out.normal = normalize(tangent_matrix * (object_normal.xyz * 2.0 - 1.0));
+    out.metallic = clamp(metallic_roughness.b * material.factors.metallic, 0.0, 1.0);
+    out.roughness = clamp(metallic_roughness.g * material.factors.roughness, 0.04, 1.0);
+    out.occlusion =
+        sample_layer(t_occlusion, s_occlusion, in.tex_coords, material.occlusion_layer, white).r;
+    out.emissive =
+        sample_layer(t_emissive, s_emissive, in.tex_coords, material.emissive_layer, white).rgb
+        * material.factors.emissive;
+    return out;
+}
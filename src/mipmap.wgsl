@@ -0,0 +1,13 @@
+// mipmap.wgsl
+// Renders one mip level from the level above it. The bilinear sample at the center of each
+// target texel lands between four source texels and averages them.
+
+@group(0) @binding(0)
+var t_source: texture_2d<f32>;
+@group(0) @binding(1)
+var s_source: sampler;
+
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    return textureSampleLevel(t_source, s_source, in.uv, 0.0);
+}
@@ -0,0 +1,100 @@
+// model.wgsl
+// Vertex stage shared by the forward shading pipelines.
+
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+    view_position: vec4<f32>,
+    inv_view_proj: mat4x4<f32>,
+    unjittered_view_proj: mat4x4<f32>,
+    prev_view_proj: mat4x4<f32>,
+};
+@group(1) @binding(0) // 1.
+var<uniform> camera: CameraUniform;
+
+// Vertex shader
+struct InstanceInput {
+    @location(6) model_matrix_0: vec4<f32>,
+    @location(7) model_matrix_1: vec4<f32>,
+    @location(8) model_matrix_2: vec4<f32>,
+    @location(9) model_matrix_3: vec4<f32>,
+    @location(10) prev_model_matrix_0: vec4<f32>,
+    @location(11) prev_model_matrix_1: vec4<f32>,
+    @location(12) prev_model_matrix_2: vec4<f32>,
+    @location(13) prev_model_matrix_3: vec4<f32>,
+};
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) tex_coords: vec2<f32>,
+    @location(2) normal: vec3<f32>,
+    @location(3) tangent: vec3<f32>,
+    @location(4) bitangent: vec3<f32>,
+    @location(5) material: u32,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) world_normal: vec3<f32>,
+    @location(2) world_position: vec3<f32>,
+    @location(3) world_tangent: vec3<f32>,
+    @location(4) world_bitangent: vec3<f32>,
+    // Unjittered clip positions this frame and last frame, for motion vectors.
+    @location(5) current_position: vec4<f32>,
+    @location(6) previous_position: vec4<f32>,
+    // Index of the material in its model, for packed materials.
+    @location(7) @interpolate(flat) material: u32,
+}
+@vertex
+fn vs_main(
+    model: VertexInput,
+    instance: InstanceInput,
+) -> VertexOutput {
+
+    let model_matrix = mat4x4<f32>(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+
+    // The cofactor matrix of the upper 3x3 is its inverse transpose scaled by the determinant,
+    // which the normalization in the fragment stage removes.
+    let basis = mat3x3<f32>(model_matrix[0].xyz, model_matrix[1].xyz, model_matrix[2].xyz);
+    let normal_matrix = mat3x3<f32>(
+        cross(basis[1], basis[2]),
+        cross(basis[2], basis[0]),
+        cross(basis[0], basis[1]),
+    );
+
+    var out: VertexOutput;
+    out.tex_coords = model.tex_coords;
+    out.material = model.material;
+    out.world_normal = normal_matrix * model.normal;
+    out.world_tangent = normal_matrix * model.tangent;
+    out.world_bitangent = normal_matrix * model.bitangent;
+    var world_position: vec4<f32> = model_matrix * vec4<f32>(model.position, 1.0);
+    out.world_position = world_position.xyz;
+    out.clip_position = camera.view_proj * world_position;
+
+    let prev_model_matrix = mat4x4<f32>(
+        instance.prev_model_matrix_0,
+        instance.prev_model_matrix_1,
+        instance.prev_model_matrix_2,
+        instance.prev_model_matrix_3,
+    );
+    out.current_position = camera.unjittered_view_proj * world_position;
+    out.previous_position = camera.prev_view_proj * prev_model_matrix * vec4<f32>(model.position, 1.0);
+    return out;
+}
+
+// Screen space motion from last frame to this one, in UV units with y pointing down.
+fn motion_vector(current: vec4<f32>, previous: vec4<f32>) -> vec2<f32> {
+    return (current.xy / current.w - previous.xy / previous.w) * vec2<f32>(0.5, -0.5);
+}
+
+// Targets of the forward shading pipelines: the lit color and the motion vector.
+struct ForwardOutput {
+    @location(0) color: vec4<f32>,
+    @location(1) velocity: vec2<f32>,
+}
@@ -1,5 +1,10 @@
+use crate::bind_groups::{create_bind_group, create_packed_material_bind_group};
+use crate::error::ResourceError;
 use crate::texture;
+use bytemuck::Zeroable;
 use std::ops::Range;
+use std::sync::Arc;
+use wgpu::util::DeviceExt;
 
 pub struct Model {
     pub meshes: Vec<Mesh>,
@@ -8,10 +13,248 @@ pub struct Model {
 
 pub struct Material {
     pub name: String,
-    pub diffuse_texture: texture::Texture,
+    pub textures: MaterialTextures,
+    pub uniform: MaterialUniform,
+    // Kept alive for the bind group.
+    _uniform_buffer: wgpu::Buffer,
     pub bind_group: wgpu::BindGroup,
 }
 
+/// Textures sampled by the shading pipelines; maps missing from the source material are 1x1 fallbacks.
+/// Each texture can be shared with other materials through the `TextureCache`.
+#[derive(Clone)]
+pub struct MaterialTextures {
+    pub diffuse: Arc<texture::Texture>,
+    pub normal: Arc<texture::Texture>,
+    pub metallic_roughness: Arc<texture::Texture>,
+    pub occlusion: Arc<texture::Texture>,
+    pub emissive: Arc<texture::Texture>,
+}
+
+impl MaterialTextures {
+    /// The 1x1 stand-ins for a material without any maps: white for the maps multiplied with a
+    /// material factor and a flat normal.
+    pub fn fallback(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        helpers: &mut texture::TextureHelpers,
+        texture_cache: &mut texture::TextureCache,
+    ) -> MaterialTextures {
+        let mut white = |linear| {
+            let options = texture::TextureOptions::solid_color(linear);
+            texture_cache.get_or_insert_with("white_texture", &options, || {
+                texture::Texture::white(device, queue, helpers, linear)
+            })
+        };
+        let (white_srgb, white_linear) = (white(false), white(true));
+        let normal = texture_cache.get_or_insert_with(
+            "flat_normal_texture",
+            &texture::TextureOptions::solid_color(true),
+            || texture::Texture::flat_normal(device, queue, helpers),
+        );
+
+        MaterialTextures {
+            diffuse: white_srgb.clone(),
+            normal,
+            metallic_roughness: white_linear.clone(),
+            occlusion: white_linear,
+            emissive: white_srgb,
+        }
+    }
+}
+
+/// Scalar factors multiplied with the material textures, following the glTF metallic-roughness model.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct MaterialUniform {
+    pub base_color_factor: [f32; 4],
+    pub emissive_factor: [f32; 3],
+    pub metallic_factor: f32,
+    pub roughness_factor: f32,
+    _padding: [f32; 3],
+}
+
+impl Default for MaterialUniform {
+    fn default() -> Self {
+        Self {
+            base_color_factor: [1.0; 4],
+            emissive_factor: [0.0; 3],
+            metallic_factor: 0.0,
+            roughness_factor: 1.0,
+            _padding: [0.0; 3],
+        }
+    }
+}
+
+impl Material {
+    pub fn new(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        name: String,
+        textures: MaterialTextures,
+        uniform: MaterialUniform,
+    ) -> Material {
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{} Material Buffer", name)),
+            contents: bytemuck::cast_slice(&[uniform]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let bind_group = create_bind_group(device, layout, &textures, &uniform_buffer);
+
+        Material {
+            name,
+            textures,
+            uniform,
+            _uniform_buffer: uniform_buffer,
+            bind_group,
+        }
+    }
+}
+
+/// `MaterialUniform` followed by the layer of each map in the arrays of `PackedMaterials`, -1 for
+/// maps the material lacks.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct PackedMaterialUniform {
+    pub material: MaterialUniform,
+    pub layers: [i32; 5],
+    _padding: [i32; 3],
+}
+
+/// The materials of a model packed into one 2D texture array per map and one uniform array, so the
+/// whole model draws with a single bind group. Each vertex carries the index of its material.
+/// Packing needs every texture of a map to share its size, format and sampler.
+pub struct PackedMaterials {
+    pub bind_group: wgpu::BindGroup,
+    // Kept alive for the bind group.
+    _textures: MaterialTextures,
+    _uniform_buffer: wgpu::Buffer,
+}
+
+impl PackedMaterials {
+    /// The most materials one model can pack, the size of the uniform array in
+    /// material_packed.wgsl.
+    pub const MAX_MATERIALS: usize = 128;
+
+    /// Packs the materials of `model`, loaded from `path`, failing when the model has more than
+    /// `MAX_MATERIALS` or their textures can't share arrays. Maps that are the `fallback` textures
+    /// aren't packed and sample as the fallback value.
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        helpers: &mut texture::TextureHelpers,
+        path: &str,
+        model: &Model,
+        fallback: &MaterialTextures,
+        layout: &wgpu::BindGroupLayout,
+    ) -> Result<PackedMaterials, ResourceError> {
+        if model.materials.len() > Self::MAX_MATERIALS {
+            return Err(ResourceError::UnsupportedFormat {
+                path: path.to_string(),
+                format: format!(
+                    "{} materials to pack, at most {} fit",
+                    model.materials.len(),
+                    Self::MAX_MATERIALS
+                ),
+            });
+        }
+        let maps = |textures: &MaterialTextures| {
+            [
+                textures.diffuse.clone(),
+                textures.normal.clone(),
+                textures.metallic_roughness.clone(),
+                textures.occlusion.clone(),
+                textures.emissive.clone(),
+            ]
+        };
+        let fallback = maps(fallback);
+        let mut layers: Vec<Vec<Arc<texture::Texture>>> = vec![Vec::new(); fallback.len()];
+        let mut uniforms = vec![PackedMaterialUniform::zeroed(); Self::MAX_MATERIALS];
+        for (material, uniform) in model.materials.iter().zip(&mut uniforms) {
+            *uniform = PackedMaterialUniform {
+                material: material.uniform,
+                layers: [-1; 5],
+                _padding: [0; 3],
+            };
+            for (map, texture) in maps(&material.textures).into_iter().enumerate() {
+                if Arc::ptr_eq(&texture, &fallback[map]) {
+                    continue;
+                }
+                let layer = match layers[map].iter().position(|t| Arc::ptr_eq(t, &texture)) {
+                    Some(layer) => layer,
+                    None => {
+                        layers[map].push(texture);
+                        layers[map].len() - 1
+                    }
+                };
+                uniform.layers[map] = layer as i32;
+            }
+        }
+
+        let mut arrays = Vec::with_capacity(layers.len());
+        for (map, textures) in layers.iter().enumerate() {
+            let array = if textures.is_empty() {
+                // Every material samples the fallback value for this map, but the layout still
+                // needs an array to bind.
+                let options = texture::TextureOptions {
+                    mipmaps: texture::Mipmaps::None,
+                    ..Default::default()
+                };
+                // Two layers, as `Texture::pack_layers` explains.
+                let placeholder = image::DynamicImage::new_rgba8(1, 1);
+                texture::Texture::from_layers(
+                    device,
+                    queue,
+                    helpers,
+                    &[placeholder.clone(), placeholder],
+                    Some("packed_material_placeholder"),
+                    &options,
+                )?
+            } else {
+                texture::Texture::pack_layers(
+                    device,
+                    queue,
+                    textures,
+                    Some("packed_material_texture"),
+                )
+                .ok_or_else(|| ResourceError::UnsupportedFormat {
+                    path: path.to_string(),
+                    format: format!(
+                        "packing map {map}, its textures differ in size, format or sampler"
+                    ),
+                })?
+            };
+            arrays.push(Arc::new(array));
+        }
+        let Ok([diffuse, normal, metallic_roughness, occlusion, emissive]) =
+            <[Arc<texture::Texture>; 5]>::try_from(arrays)
+        else {
+            unreachable!("there is one array per map");
+        };
+        let textures = MaterialTextures {
+            diffuse,
+            normal,
+            metallic_roughness,
+            occlusion,
+            emissive,
+        };
+
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Packed Material Buffer"),
+            contents: bytemuck::cast_slice(&uniforms),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let bind_group =
+            create_packed_material_bind_group(device, layout, &textures, &uniform_buffer);
+
+        Ok(PackedMaterials {
+            bind_group,
+            _textures: textures,
+            _uniform_buffer: uniform_buffer,
+        })
+    }
+}
+
 pub struct Mesh {
     pub name: String,
     pub vertex_buffer: wgpu::Buffer,
@@ -44,6 +287,25 @@ pub trait DrawModel<'a> {
         camera_bind_group: &'a wgpu::BindGroup,
         light_bind_group: &'a wgpu::BindGroup,
     );
+    /// Draws every mesh of `model` with `material` in place of its own.
+    fn draw_model_with_material_instanced(
+        &mut self,
+        model: &'a Model,
+        material: &'a Material,
+        instances: Range<u32>,
+        camera_bind_group: &'a wgpu::BindGroup,
+        light_bind_group: &'a wgpu::BindGroup,
+    );
+    /// Draws `model` with its materials bound through `packed`, for pipelines built with the
+    /// packed material layout.
+    fn draw_model_packed_instanced(
+        &mut self,
+        model: &'a Model,
+        packed: &'a PackedMaterials,
+        instances: Range<u32>,
+        camera_bind_group: &'a wgpu::BindGroup,
+        light_bind_group: &'a wgpu::BindGroup,
+    );
 }
 impl<'a, 'b> DrawModel<'b> for wgpu::RenderPass<'a>
 where
@@ -89,6 +351,37 @@ where
             self.draw_mesh_instanced(mesh, material, instances.clone(), camera_bind_group, light_bind_group);
         }
     }
+
+    fn draw_model_with_material_instanced(
+        &mut self,
+        model: &'b Model,
+        material: &'b Material,
+        instances: Range<u32>,
+        camera_bind_group: &'b wgpu::BindGroup,
+        light_bind_group: &'b wgpu::BindGroup,
+    ) {
+        for mesh in &model.meshes {
+            self.draw_mesh_instanced(mesh, material, instances.clone(), camera_bind_group, light_bind_group);
+        }
+    }
+
+    fn draw_model_packed_instanced(
+        &mut self,
+        model: &'b Model,
+        packed: &'b PackedMaterials,
+        instances: Range<u32>,
+        camera_bind_group: &'b wgpu::BindGroup,
+        light_bind_group: &'b wgpu::BindGroup,
+    ) {
+        self.set_bind_group(0, &packed.bind_group, &[]);
+        self.set_bind_group(1, camera_bind_group, &[]);
+        self.set_bind_group(2, light_bind_group, &[]);
+        for mesh in &model.meshes {
+            self.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+            self.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+            self.draw_indexed(0..mesh.num_elements, 0, instances.clone());
+        }
+    }
 }
 
 // model.rs
@@ -107,12 +400,6 @@ pub trait DrawLight<'a> {
         light_bind_group: &'a wgpu::BindGroup,
     );
 
-    fn draw_light_model(
-        &mut self,
-        model: &'a Model,
-        camera_bind_group: &'a wgpu::BindGroup,
-        light_bind_group: &'a wgpu::BindGroup,
-    );
     fn draw_light_model_instanced(
         &mut self,
         model: &'a Model,
@@ -149,24 +436,63 @@ impl<'a, 'b> DrawLight<'b> for wgpu::RenderPass<'a>
         self.draw_indexed(0..mesh.num_elements, 0, instances);
     }
 
-    fn draw_light_model(
+    fn draw_light_model_instanced(
         &mut self,
         model: &'b Model,
+        instances: Range<u32>,
         camera_bind_group: &'b wgpu::BindGroup,
         light_bind_group: &'b wgpu::BindGroup,
     ) {
-        self.draw_light_model_instanced(model, 0..1, camera_bind_group, light_bind_group);
+        for mesh in &model.meshes {
+            self.draw_light_mesh_instanced(mesh, instances.clone(), camera_bind_group, light_bind_group);
+        }
     }
-    fn draw_light_model_instanced(
+}
+
+
+pub trait DrawShadow<'a> {
+    fn draw_shadow_mesh_instanced(
+        &mut self,
+        mesh: &'a Mesh,
+        instances: Range<u32>,
+        shadow_bind_group: &'a wgpu::BindGroup,
+        face_offset: u32,
+    );
+    fn draw_shadow_model_instanced(
+        &mut self,
+        model: &'a Model,
+        instances: Range<u32>,
+        shadow_bind_group: &'a wgpu::BindGroup,
+        face_offset: u32,
+    );
+}
+
+impl<'a, 'b> DrawShadow<'b> for wgpu::RenderPass<'a>
+where
+    'b: 'a,
+{
+    fn draw_shadow_mesh_instanced(
+        &mut self,
+        mesh: &'b Mesh,
+        instances: Range<u32>,
+        shadow_bind_group: &'b wgpu::BindGroup,
+        face_offset: u32,
+    ) {
+        self.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+        self.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        self.set_bind_group(0, shadow_bind_group, &[face_offset]);
+        self.draw_indexed(0..mesh.num_elements, 0, instances);
+    }
+
+    fn draw_shadow_model_instanced(
         &mut self,
         model: &'b Model,
         instances: Range<u32>,
-        camera_bind_group: &'b wgpu::BindGroup,
-        light_bind_group: &'b wgpu::BindGroup,
+        shadow_bind_group: &'b wgpu::BindGroup,
+        face_offset: u32,
     ) {
         for mesh in &model.meshes {
-            self.draw_light_mesh_instanced(mesh, instances.clone(), camera_bind_group, light_bind_group);
+            self.draw_shadow_mesh_instanced(mesh, instances.clone(), shadow_bind_group, face_offset);
         }
     }
 }
-
@@ -0,0 +1,404 @@
+use wgpu::util::DeviceExt;
+use wgpu::{BindGroup, BindGroupLayout, Device};
+
+use crate::bind_groups::{create_outline_bind_group, create_outline_bind_group_layout};
+use crate::instance::InstanceRaw;
+use crate::object;
+use crate::object::Vertex;
+use crate::texture::Texture;
+
+/// Stencil value marking the pixels covered by the selection.
+const SELECTED: u32 = 1;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct OutlineUniform {
+    color: [f32; 4],
+    width: f32,
+    step: f32,
+    _padding: [f32; 2],
+}
+
+/// Outline around the selected instances.
+///
+/// The selection is drawn through `DrawModel` into the stencil of the depth texture by
+/// `begin_stencil_pass`, ignoring depth so hidden parts count too. `render` then grows the marked
+/// area by jump flooding and draws the ring around it where the stencil isn't set, so the outline
+/// has the same width in pixels at any distance and stays visible behind other objects.
+pub struct Outline {
+    stencil_pipeline: wgpu::RenderPipeline,
+    seed_pipeline: wgpu::RenderPipeline,
+    jump_pipeline: wgpu::RenderPipeline,
+    outline_pipeline: wgpu::RenderPipeline,
+    bind_group_layout: BindGroupLayout,
+    // One per jump pass with its step, and a last one for the outline pass.
+    uniform_buffers: Vec<wgpu::Buffer>,
+    seeds: [wgpu::TextureView; 2],
+    bind_groups: Vec<BindGroup>,
+}
+
+impl Outline {
+    const SEED_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rg32Float;
+    /// Linear color and opacity of the outline.
+    const COLOR: [f32; 4] = [1.0, 0.55, 0.1, 1.0];
+    /// Width in pixels.
+    const WIDTH: f32 = 3.0;
+
+    /// `stencil_bind_group_layouts` are the forward material, camera and light layouts, the
+    /// stencil pass draws through `DrawModel` like the other scene passes. The outline is drawn
+    /// into a target of `format`.
+    pub fn new(
+        device: &Device,
+        config: &wgpu::SurfaceConfiguration,
+        stencil_bind_group_layouts: &[&BindGroupLayout],
+        format: wgpu::TextureFormat,
+    ) -> Outline {
+        // Steps from the first power of two covering the width down to 1 reach every pixel
+        // within it.
+        let mut steps = Vec::new();
+        let mut step = (Self::WIDTH.ceil() as u32).next_power_of_two();
+        while step > 0 {
+            steps.push(step as f32);
+            step /= 2;
+        }
+        let uniform_buffers = steps
+            .iter()
+            .chain(&[0.0])
+            .map(|&step| {
+                device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Outline Buffer"),
+                    contents: bytemuck::cast_slice(&[OutlineUniform {
+                        color: Self::COLOR,
+                        width: Self::WIDTH,
+                        step,
+                        _padding: [0.0; 2],
+                    }]),
+                    usage: wgpu::BufferUsages::UNIFORM,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let stencil_pipeline = create_stencil_pipeline(device, stencil_bind_group_layouts);
+
+        let bind_group_layout = create_outline_bind_group_layout(device);
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Outline Shader"),
+            source: wgpu::ShaderSource::Wgsl(
+                concat!(
+                    include_str!("fullscreen.wgsl"),
+                    include_str!("outline.wgsl")
+                )
+                .into(),
+            ),
+        });
+        let seed_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Outline Seed Pipeline Layout"),
+            bind_group_layouts: &[],
+            push_constant_ranges: &[],
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Outline Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let seed_pipeline = create_fullscreen_pipeline(
+            device,
+            &seed_layout,
+            &shader,
+            "fs_seed",
+            Self::SEED_FORMAT,
+            None,
+            Some(wgpu::CompareFunction::Equal),
+        );
+        let jump_pipeline = create_fullscreen_pipeline(
+            device,
+            &layout,
+            &shader,
+            "fs_jump",
+            Self::SEED_FORMAT,
+            None,
+            None,
+        );
+        let outline_pipeline = create_fullscreen_pipeline(
+            device,
+            &layout,
+            &shader,
+            "fs_outline",
+            format,
+            Some(wgpu::BlendState::ALPHA_BLENDING),
+            Some(wgpu::CompareFunction::NotEqual),
+        );
+
+        let (seeds, bind_groups) =
+            create_targets(device, config, &uniform_buffers, &bind_group_layout);
+
+        Outline {
+            stencil_pipeline,
+            seed_pipeline,
+            jump_pipeline,
+            outline_pipeline,
+            bind_group_layout,
+            uniform_buffers,
+            seeds,
+            bind_groups,
+        }
+    }
+
+    /// Recreates the seed targets to match the surface.
+    pub fn resize(&mut self, device: &Device, config: &wgpu::SurfaceConfiguration) {
+        (self.seeds, self.bind_groups) = create_targets(
+            device,
+            config,
+            &self.uniform_buffers,
+            &self.bind_group_layout,
+        );
+    }
+
+    /// Starts a pass clearing the stencil of `depth_view` and marking whatever is drawn into it as
+    /// selected. Draw the selected instances with `DrawModel`.
+    pub fn begin_stencil_pass<'a>(
+        &'a self,
+        encoder: &'a mut wgpu::CommandEncoder,
+        depth_view: &'a wgpu::TextureView,
+    ) -> wgpu::RenderPass<'a> {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Outline Stencil Pass"),
+            color_attachments: &[],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: depth_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: true,
+                }),
+                stencil_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(0),
+                    store: true,
+                }),
+            }),
+        });
+        render_pass.set_pipeline(&self.stencil_pipeline);
+        render_pass.set_stencil_reference(SELECTED);
+        render_pass
+    }
+
+    /// Draws the outline into `output` around the pixels marked by the stencil pass.
+    pub fn render(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        depth_view: &wgpu::TextureView,
+        output: &wgpu::TextureView,
+    ) {
+        {
+            let mut render_pass = begin_outline_pass(
+                encoder,
+                "Outline Seed Pass",
+                &self.seeds[0],
+                Some(depth_view),
+                true,
+            );
+            render_pass.set_pipeline(&self.seed_pipeline);
+            render_pass.set_stencil_reference(SELECTED);
+            render_pass.draw(0..3, 0..1);
+        }
+
+        let jumps = self.bind_groups.len() - 1;
+        for (index, bind_group) in self.bind_groups[..jumps].iter().enumerate() {
+            let mut render_pass = begin_outline_pass(
+                encoder,
+                "Outline Jump Pass",
+                &self.seeds[(index + 1) % 2],
+                None,
+                false,
+            );
+            render_pass.set_pipeline(&self.jump_pipeline);
+            render_pass.set_bind_group(0, bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+
+        let mut render_pass =
+            begin_outline_pass(encoder, "Outline Pass", output, Some(depth_view), false);
+        render_pass.set_pipeline(&self.outline_pipeline);
+        render_pass.set_stencil_reference(SELECTED);
+        render_pass.set_bind_group(0, &self.bind_groups[jumps], &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+// Returns the two ping-pong seed targets and a bind group for each uniform buffer, each reading
+// the target written by the pass before the one using it.
+fn create_targets(
+    device: &Device,
+    config: &wgpu::SurfaceConfiguration,
+    uniform_buffers: &[wgpu::Buffer],
+    bind_group_layout: &BindGroupLayout,
+) -> ([wgpu::TextureView; 2], Vec<BindGroup>) {
+    let seeds = [0, 1].map(|_| {
+        device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some("outline_seeds"),
+                size: wgpu::Extent3d {
+                    width: config.width,
+                    height: config.height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: Outline::SEED_FORMAT,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                    | wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            })
+            .create_view(&wgpu::TextureViewDescriptor::default())
+    });
+    let bind_groups = uniform_buffers
+        .iter()
+        .enumerate()
+        .map(|(index, buffer)| {
+            create_outline_bind_group(device, &seeds[index % 2], buffer, bind_group_layout)
+        })
+        .collect();
+
+    (seeds, bind_groups)
+}
+
+// The stencil is only tested here, the depth-stencil attachment is bound read only.
+fn begin_outline_pass<'a>(
+    encoder: &'a mut wgpu::CommandEncoder,
+    label: &str,
+    target: &'a wgpu::TextureView,
+    depth_view: Option<&'a wgpu::TextureView>,
+    clear: bool,
+) -> wgpu::RenderPass<'a> {
+    let load = if clear {
+        // Negative coordinates mark pixels without a seed.
+        wgpu::LoadOp::Clear(wgpu::Color {
+            r: -1.0,
+            g: -1.0,
+            b: 0.0,
+            a: 0.0,
+        })
+    } else {
+        wgpu::LoadOp::Load
+    };
+    encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some(label),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view: target,
+            resolve_target: None,
+            ops: wgpu::Operations { load, store: true },
+        })],
+        depth_stencil_attachment: depth_view.map(|view| wgpu::RenderPassDepthStencilAttachment {
+            view,
+            depth_ops: None,
+            stencil_ops: None,
+        }),
+    })
+}
+
+fn create_fullscreen_pipeline(
+    device: &Device,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    entry_point: &str,
+    format: wgpu::TextureFormat,
+    blend: Option<wgpu::BlendState>,
+    stencil_compare: Option<wgpu::CompareFunction>,
+) -> wgpu::RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(entry_point),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: "vs_main",
+            buffers: &[],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point,
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend,
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: stencil_compare.map(|compare| {
+            let face = wgpu::StencilFaceState {
+                compare,
+                fail_op: wgpu::StencilOperation::Keep,
+                depth_fail_op: wgpu::StencilOperation::Keep,
+                pass_op: wgpu::StencilOperation::Keep,
+            };
+            wgpu::DepthStencilState {
+                format: Texture::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Always,
+                stencil: wgpu::StencilState {
+                    front: face,
+                    back: face,
+                    read_mask: 0xff,
+                    write_mask: 0,
+                },
+                bias: wgpu::DepthBiasState::default(),
+            }
+        }),
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+    })
+}
+
+fn create_stencil_pipeline(
+    device: &Device,
+    bind_group_layouts: &[&BindGroupLayout],
+) -> wgpu::RenderPipeline {
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Outline Stencil Pipeline Layout"),
+        bind_group_layouts,
+        push_constant_ranges: &[],
+    });
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Outline Stencil Shader"),
+        source: wgpu::ShaderSource::Wgsl(include_str!("model.wgsl").into()),
+    });
+    let face = wgpu::StencilFaceState {
+        compare: wgpu::CompareFunction::Always,
+        fail_op: wgpu::StencilOperation::Keep,
+        depth_fail_op: wgpu::StencilOperation::Replace,
+        pass_op: wgpu::StencilOperation::Replace,
+    };
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Outline Stencil Pipeline"),
+        layout: Some(&layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: "vs_main",
+            buffers: &[object::ModelVertex::desc(), InstanceRaw::desc()],
+        },
+        fragment: None,
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: Some(wgpu::Face::Back),
+            polygon_mode: wgpu::PolygonMode::Fill,
+            unclipped_depth: false,
+            conservative: false,
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: Texture::DEPTH_FORMAT,
+            depth_write_enabled: false,
+            depth_compare: wgpu::CompareFunction::Always,
+            stencil: wgpu::StencilState {
+                front: face,
+                back: face,
+                read_mask: 0xff,
+                write_mask: 0xff,
+            },
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+    })
+}
@@ -0,0 +1,66 @@
+// outline.wgsl
+// Selection outline by jump flooding. The pixels the stencil marks as selected seed a texture
+// with their own coordinates, then each jump pass lets every pixel adopt the nearest seed known
+// to its neighbours `step` pixels away, halving the step each time. Afterwards every pixel holds
+// the nearest selected pixel, and the outline covers those within its width.
+
+struct Outline {
+    color: vec4<f32>,
+    // Outline width in pixels.
+    width: f32,
+    // Distance to the neighbours read by a jump pass, in pixels.
+    step: f32,
+}
+
+@group(0) @binding(0)
+var t_seeds: texture_2d<f32>;
+@group(0) @binding(1)
+var<uniform> outline: Outline;
+
+// Only runs where the stencil test passed, the rest of the target keeps the clear value of -1
+// meaning no seed.
+@fragment
+fn fs_seed(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(in.position.xy, 0.0, 0.0);
+}
+
+@fragment
+fn fs_jump(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let size = vec2<i32>(textureDimensions(t_seeds));
+    let pixel = vec2<i32>(in.position.xy);
+    let step = i32(outline.step);
+
+    var nearest = vec2<f32>(-1.0);
+    var nearest_distance = 3.4e38;
+    for (var y = -1; y <= 1; y++) {
+        for (var x = -1; x <= 1; x++) {
+            let neighbour = pixel + vec2<i32>(x, y) * step;
+            if (any(neighbour < vec2<i32>(0)) || any(neighbour >= size)) {
+                continue;
+            }
+            let seed = textureLoad(t_seeds, neighbour, 0).xy;
+            let offset = seed - in.position.xy;
+            let distance = dot(offset, offset);
+            if (seed.x >= 0.0 && distance < nearest_distance) {
+                nearest = seed;
+                nearest_distance = distance;
+            }
+        }
+    }
+    return vec4<f32>(nearest, 0.0, 0.0);
+}
+
+// Drawn with the stencil test rejecting the selection itself, so only the ring around it shows.
+@fragment
+fn fs_outline(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let seed = textureLoad(t_seeds, vec2<i32>(in.position.xy), 0).xy;
+    if (seed.x < 0.0) {
+        discard;
+    }
+    // Fades over the last pixel so the outer edge is smooth.
+    let coverage = clamp(outline.width + 0.5 - distance(seed, in.position.xy), 0.0, 1.0);
+    if (coverage <= 0.0) {
+        discard;
+    }
+    return vec4<f32>(outline.color.rgb, outline.color.a * coverage);
+}
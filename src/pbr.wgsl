@@ -0,0 +1,23 @@
+// pbr.wgsl
+// Forward metallic-roughness shading.
+
+@fragment
+fn fs_main(in: VertexOutput) -> ForwardOutput {
+    let sample = sample_material(in);
+
+    var surface: PbrSurface;
+    surface.position = in.world_position;
+    surface.geometric_normal = normalize(in.world_normal);
+    surface.normal = sample.normal;
+    surface.base_color = sample.base_color.rgb;
+    surface.metallic = sample.metallic;
+    surface.roughness = sample.roughness;
+    surface.occlusion = sample.occlusion;
+
+    let color = pbr_lighting(surface, in.clip_position, camera.view_position.xyz) + sample.emissive;
+
+    var out: ForwardOutput;
+    out.color = vec4<f32>(color, sample.base_color.a);
+    out.velocity = motion_vector(in.current_position, in.previous_position);
+    return out;
+}
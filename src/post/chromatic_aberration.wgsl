@@ -0,0 +1,21 @@
+// chromatic_aberration.wgsl
+// Splits the red and blue channels radially, like a lens refracting colors by different amounts.
+
+struct ChromaticAberration {
+    // Channel offset at the edge of the frame, in UV units.
+    strength: f32,
+}
+
+@group(1) @binding(0)
+var<uniform> aberration: ChromaticAberration;
+
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let offset = (in.uv - 0.5) * 2.0 * aberration.strength;
+    let color = vec3<f32>(
+        color_at(in.uv + offset).r,
+        color_at(in.uv).g,
+        color_at(in.uv - offset).b,
+    );
+    return vec4<f32>(color, 1.0);
+}
@@ -0,0 +1,23 @@
+// color_grade.wgsl
+// Color grading through a 3D lookup table indexed by the display encoded color.
+
+struct ColorGrade {
+    // Blend between the original and graded color.
+    strength: f32,
+    lut_size: f32,
+}
+
+@group(1) @binding(0)
+var<uniform> grade: ColorGrade;
+@group(1) @binding(1)
+var t_lut: texture_3d<f32>;
+
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let display = saturate(linear_to_srgb(color_at(in.uv)));
+    // Sample texel centers so 0 and 1 land on the first and last entries.
+    let scale = (grade.lut_size - 1.0) / grade.lut_size;
+    let offset = 0.5 / grade.lut_size;
+    let graded = textureSampleLevel(t_lut, s_post, display * scale + offset, 0.0).rgb;
+    return vec4<f32>(srgb_to_linear(mix(display, graded, grade.strength)), 1.0);
+}
@@ -0,0 +1,57 @@
+// depth_of_field.wgsl
+// Single pass gather depth of field after Dennis Gustafsson's "Bokeh depth of field in a single
+// pass": samples along a golden angle spiral are accepted where their own circle of confusion
+// reaches back to the pixel. Out of focus foreground (the near field) keeps its full blur so it
+// spreads over the sharper surfaces behind it, while the background (the far field) is limited
+// to the pixel's own blur so it can't smear over sharp edges in front of it.
+
+struct DepthOfField {
+    focus_distance: f32,
+    aperture: f32,
+    // Largest blur radius in pixels.
+    max_radius: f32,
+    // Spacing of the spiral, smaller steps take more samples.
+    radius_step: f32,
+}
+
+@group(1) @binding(0)
+var<uniform> dof: DepthOfField;
+
+const GOLDEN_ANGLE: f32 = 2.39996323;
+
+// Blur radius in pixels of a surface at `distance`, from a thin lens circle of confusion.
+fn blur_radius(distance: f32) -> f32 {
+    let coc = clamp((1.0 / dof.focus_distance - 1.0 / distance) * dof.aperture, -1.0, 1.0);
+    return abs(coc) * dof.max_radius;
+}
+
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let center_distance = linear_depth(depth_at(in.uv));
+    let center_radius = blur_radius(center_distance);
+
+    var color = color_at(in.uv);
+    var total = 1.0;
+    var radius = dof.radius_step;
+    var angle = 0.0;
+    loop {
+        if (radius >= dof.max_radius) {
+            break;
+        }
+        let uv = in.uv + vec2<f32>(cos(angle), sin(angle)) * post.texel_size * radius;
+        let sample_color = color_at(uv);
+        let sample_distance = linear_depth(depth_at(uv));
+        var sample_radius = blur_radius(sample_distance);
+        if (sample_distance > center_distance) {
+            sample_radius = min(sample_radius, center_radius * 2.0);
+        }
+
+        // Rejected samples count as the running average, so the pixel keeps its own color.
+        let coverage = smoothstep(radius - 0.5, radius + 0.5, sample_radius);
+        color += mix(color / total, sample_color, coverage);
+        total += 1.0;
+        radius += dof.radius_step / radius;
+        angle += GOLDEN_ANGLE;
+    }
+    return vec4<f32>(color / total, 1.0);
+}
@@ -0,0 +1,300 @@
+use image::{DynamicImage, RgbaImage};
+use wgpu::{Device, Queue};
+
+use super::{PostEffect, PostStack};
+use crate::texture::{Mipmaps, Texture, TextureHelpers, TextureOptions};
+
+/// Entries per side of the color grading lookup table.
+const LUT_SIZE: u32 = 32;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct FxaaParams {
+    edge_threshold: f32,
+    edge_threshold_min: f32,
+    span_max: f32,
+    reduce_mul: f32,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct VignetteParams {
+    intensity: f32,
+    radius: f32,
+    softness: f32,
+    _padding: f32,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct ChromaticAberrationParams {
+    strength: f32,
+    _padding: [f32; 3],
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct FilmGrainParams {
+    intensity: f32,
+    _padding: [f32; 3],
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct ColorGradeParams {
+    strength: f32,
+    lut_size: f32,
+    _padding: [f32; 2],
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct DepthOfFieldParams {
+    focus_distance: f32,
+    aperture: f32,
+    max_radius: f32,
+    radius_step: f32,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct FogParams {
+    color: [f32; 3],
+    density: f32,
+    height_density: f32,
+    height_falloff: f32,
+    base_height: f32,
+    start: f32,
+}
+
+/// Lens settings for `depth_of_field`.
+#[derive(Debug, Copy, Clone)]
+pub struct DepthOfFieldSettings {
+    /// Distance from the camera that is perfectly sharp, in world units.
+    pub focus_distance: f32,
+    /// How quickly surfaces blur away from the focus distance, larger apertures give a
+    /// shallower depth of field.
+    pub aperture: f32,
+    /// Blur radius for the most out of focus surfaces, in pixels.
+    pub max_radius: f32,
+}
+
+impl Default for DepthOfFieldSettings {
+    fn default() -> Self {
+        Self {
+            focus_distance: 10.0,
+            aperture: 4.0,
+            max_radius: 12.0,
+        }
+    }
+}
+
+/// Fog settings for `fog`. Distance fog thickens uniformly with the distance from the camera,
+/// height fog is densest at `base_height` and thins out exponentially above it.
+#[derive(Debug, Copy, Clone)]
+pub struct FogSettings {
+    /// Linear color the fog fades towards.
+    pub color: [f32; 3],
+    /// Distance fog per world unit.
+    pub density: f32,
+    /// Distance from the camera where the distance fog begins.
+    pub start: f32,
+    /// Height fog per world unit at `base_height`.
+    pub height_density: f32,
+    /// How quickly the height fog thins out going up.
+    pub height_falloff: f32,
+    pub base_height: f32,
+}
+
+impl Default for FogSettings {
+    fn default() -> Self {
+        Self {
+            color: [0.45, 0.5, 0.55],
+            density: 0.01,
+            start: 5.0,
+            height_density: 0.02,
+            height_falloff: 0.5,
+            base_height: -2.0,
+        }
+    }
+}
+
+pub fn fxaa(device: &Device, stack: &PostStack) -> PostEffect {
+    PostEffect::new(
+        device,
+        stack,
+        "FXAA",
+        include_str!("fxaa.wgsl"),
+        &FxaaParams {
+            edge_threshold: 0.125,
+            edge_threshold_min: 0.0312,
+            span_max: 8.0,
+            reduce_mul: 0.125,
+        },
+        &[],
+    )
+}
+
+pub fn vignette(device: &Device, stack: &PostStack) -> PostEffect {
+    PostEffect::new(
+        device,
+        stack,
+        "Vignette",
+        include_str!("vignette.wgsl"),
+        &VignetteParams {
+            intensity: 0.4,
+            radius: 0.9,
+            softness: 0.6,
+            _padding: 0.0,
+        },
+        &[],
+    )
+}
+
+pub fn chromatic_aberration(device: &Device, stack: &PostStack) -> PostEffect {
+    PostEffect::new(
+        device,
+        stack,
+        "Chromatic Aberration",
+        include_str!("chromatic_aberration.wgsl"),
+        &ChromaticAberrationParams {
+            strength: 0.003,
+            _padding: [0.0; 3],
+        },
+        &[],
+    )
+}
+
+pub fn film_grain(device: &Device, stack: &PostStack) -> PostEffect {
+    PostEffect::new(
+        device,
+        stack,
+        "Film Grain",
+        include_str!("film_grain.wgsl"),
+        &FilmGrainParams {
+            intensity: 0.06,
+            _padding: [0.0; 3],
+        },
+        &[],
+    )
+}
+
+/// Blurs surfaces by how far they are from the focus distance, gathering a spiral of samples
+/// whose size grows with the circle of confusion. Reads the camera near and far planes from the
+/// stack to linearize depth.
+pub fn depth_of_field(
+    device: &Device,
+    stack: &PostStack,
+    settings: DepthOfFieldSettings,
+) -> PostEffect {
+    PostEffect::new(
+        device,
+        stack,
+        "Depth of Field",
+        include_str!("depth_of_field.wgsl"),
+        &DepthOfFieldParams {
+            focus_distance: settings.focus_distance,
+            aperture: settings.aperture,
+            max_radius: settings.max_radius,
+            radius_step: 0.75,
+        },
+        &[],
+    )
+}
+
+/// Fades surfaces towards the fog color by distance and height, reconstructing their positions
+/// from the depth buffer. The sky only receives height fog, so it keeps its gradient above the
+/// horizon.
+pub fn fog(device: &Device, stack: &PostStack, settings: FogSettings) -> PostEffect {
+    PostEffect::new(
+        device,
+        stack,
+        "Fog",
+        include_str!("fog.wgsl"),
+        &FogParams {
+            color: settings.color,
+            density: settings.density,
+            height_density: settings.height_density,
+            height_falloff: settings.height_falloff,
+            base_height: settings.base_height,
+            start: settings.start,
+        },
+        &[],
+    )
+}
+
+/// Grades through a lookup table baked from `grade`, which maps display encoded colors in 0..1.
+pub fn color_grade(
+    device: &Device,
+    queue: &Queue,
+    helpers: &mut TextureHelpers,
+    stack: &PostStack,
+    grade: impl Fn([f32; 3]) -> [f32; 3],
+) -> PostEffect {
+    let lut = create_lut(device, queue, helpers, grade);
+    PostEffect::new(
+        device,
+        stack,
+        "Color Grade",
+        include_str!("color_grade.wgsl"),
+        &ColorGradeParams {
+            strength: 1.0,
+            lut_size: lut.size.width as f32,
+            _padding: [0.0; 2],
+        },
+        &[(&lut.view, wgpu::TextureViewDimension::D3)],
+    )
+}
+
+/// A gentle filmic look: an S-curve for contrast, a little extra saturation, and shadows pushed
+/// towards teal against warmer highlights.
+pub fn warm_contrast(color: [f32; 3]) -> [f32; 3] {
+    let luma = 0.299 * color[0] + 0.587 * color[1] + 0.114 * color[2];
+    let tint = [
+        0.03 * (luma - 0.4),
+        0.01 * (luma - 0.4),
+        -0.03 * (luma - 0.4),
+    ];
+    [0, 1, 2].map(|channel| {
+        let saturated = luma + (color[channel] - luma) * 1.1;
+        let x = (saturated + tint[channel]).clamp(0.0, 1.0);
+        // Smoothstep blended halfway with the identity.
+        let curve = x * x * (3.0 - 2.0 * x);
+        x + (curve - x) * 0.5
+    })
+}
+
+// Bakes `grade` into a LUT_SIZE cube, red along x, green along y and blue along z.
+fn create_lut(
+    device: &Device,
+    queue: &Queue,
+    helpers: &mut TextureHelpers,
+    grade: impl Fn([f32; 3]) -> [f32; 3],
+) -> Texture {
+    let step = 1.0 / (LUT_SIZE - 1) as f32;
+    let slices = (0..LUT_SIZE)
+        .map(|b| {
+            DynamicImage::ImageRgba8(RgbaImage::from_fn(LUT_SIZE, LUT_SIZE, |r, g| {
+                let graded = grade([r as f32 * step, g as f32 * step, b as f32 * step]);
+                let [r, g, b] =
+                    graded.map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8);
+                image::Rgba([r, g, b, 255])
+            }))
+        })
+        .collect::<Vec<_>>();
+
+    // Every slice is an RGBA8 image of the same size.
+    Texture::from_volume(
+        device,
+        queue,
+        helpers,
+        &slices,
+        Some("color_grade_lut"),
+        &TextureOptions {
+            linear: true,
+            mipmaps: Mipmaps::None,
+            ..Default::default()
+        },
+    )
+    .unwrap()
+}
@@ -0,0 +1,28 @@
+// film_grain.wgsl
+// Animated per-pixel noise, strongest in the shadows and midtones like film grain.
+
+struct FilmGrain {
+    // Largest change of a display value.
+    intensity: f32,
+}
+
+@group(1) @binding(0)
+var<uniform> grain: FilmGrain;
+
+// PCG hash, a new pattern every frame without a noise texture.
+fn hash(value: u32) -> u32 {
+    let state = value * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let pixel = vec2<u32>(in.position.xy);
+    let noise = f32(hash(pixel.x ^ hash(pixel.y ^ hash(post.frame)))) / 4294967295.0;
+
+    let display = linear_to_srgb(color_at(in.uv));
+    let response = 1.0 - luma(display) * 0.5;
+    let grained = display + (noise - 0.5) * grain.intensity * response;
+    return vec4<f32>(srgb_to_linear(max(grained, vec3<f32>(0.0))), 1.0);
+}
@@ -0,0 +1,43 @@
+// fog.wgsl
+// Exponential distance and height fog, integrated along the view ray to each surface.
+
+struct Fog {
+    color: vec3<f32>,
+    density: f32,
+    height_density: f32,
+    height_falloff: f32,
+    base_height: f32,
+    start: f32,
+}
+
+@group(1) @binding(0)
+var<uniform> fog: Fog;
+
+// Height fog density falls off as exp(-falloff * (y - base)), so its integral along the ray has
+// a closed form: the density at the eye times the distance, scaled by how the ray climbs.
+fn height_fog(position: vec3<f32>, distance: f32) -> f32 {
+    let eye_density = fog.height_density * exp(-fog.height_falloff * (post.eye.y - fog.base_height));
+    let rise = fog.height_falloff * (position.y - post.eye.y);
+    // (1 - exp(-rise)) / rise tends to 1 for level rays.
+    var factor = 1.0;
+    if (abs(rise) > 0.001) {
+        factor = (1.0 - exp(-rise)) / rise;
+    }
+    return eye_density * distance * factor;
+}
+
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let color = color_at(in.uv);
+    let depth = depth_at(in.uv);
+    let position = world_position_at(in.uv, depth);
+    let distance = length(position - post.eye);
+
+    var optical_depth = height_fog(position, distance);
+    // The sky sits on the far plane, distance fog there would wash it out completely.
+    if (depth < 1.0) {
+        optical_depth += fog.density * max(distance - fog.start, 0.0);
+    }
+    let amount = 1.0 - exp(-optical_depth);
+    return vec4<f32>(mix(color, fog.color, amount), 1.0);
+}
@@ -0,0 +1,60 @@
+// fxaa.wgsl
+// FXAA after Timothy Lottes' console version: finds the edge direction from the luma of the
+// diagonal neighbours and blends along it, falling back to a shorter blend when the longer one
+// would overshoot the local luma range.
+
+struct Fxaa {
+    // Minimum local contrast to treat as an edge, relative to the brightest neighbour.
+    edge_threshold: f32,
+    // Contrast below which dark areas are left alone.
+    edge_threshold_min: f32,
+    // Longest blend along an edge, in pixels.
+    span_max: f32,
+    // Keeps the direction estimate from blowing up on faint edges.
+    reduce_mul: f32,
+}
+
+@group(1) @binding(0)
+var<uniform> fxaa: Fxaa;
+
+const REDUCE_MIN: f32 = 0.0078125;
+
+// Edges are found on display values, where the eye judges contrast.
+fn display_luma(uv: vec2<f32>) -> f32 {
+    return luma(linear_to_srgb(color_at(uv)));
+}
+
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let texel = post.texel_size;
+    let color = color_at(in.uv);
+
+    let luma_m = luma(linear_to_srgb(color));
+    let luma_nw = display_luma(in.uv + vec2<f32>(-1.0, -1.0) * texel);
+    let luma_ne = display_luma(in.uv + vec2<f32>(1.0, -1.0) * texel);
+    let luma_sw = display_luma(in.uv + vec2<f32>(-1.0, 1.0) * texel);
+    let luma_se = display_luma(in.uv + vec2<f32>(1.0, 1.0) * texel);
+
+    let luma_min = min(luma_m, min(min(luma_nw, luma_ne), min(luma_sw, luma_se)));
+    let luma_max = max(luma_m, max(max(luma_nw, luma_ne), max(luma_sw, luma_se)));
+    if (luma_max - luma_min < max(fxaa.edge_threshold_min, luma_max * fxaa.edge_threshold)) {
+        return vec4<f32>(color, 1.0);
+    }
+
+    var dir = vec2<f32>(
+        (luma_sw + luma_se) - (luma_nw + luma_ne),
+        (luma_nw + luma_sw) - (luma_ne + luma_se),
+    );
+    let dir_reduce = max((luma_nw + luma_ne + luma_sw + luma_se) * 0.25 * fxaa.reduce_mul, REDUCE_MIN);
+    let dir_scale = 1.0 / (min(abs(dir.x), abs(dir.y)) + dir_reduce);
+    dir = clamp(dir * dir_scale, vec2<f32>(-fxaa.span_max), vec2<f32>(fxaa.span_max)) * texel;
+
+    let near = 0.5 * (color_at(in.uv + dir * (1.0 / 3.0 - 0.5)) + color_at(in.uv + dir * (2.0 / 3.0 - 0.5)));
+    let far = near * 0.5 + 0.25 * (color_at(in.uv - dir * 0.5) + color_at(in.uv + dir * 0.5));
+
+    let luma_far = luma(linear_to_srgb(far));
+    if (luma_far < luma_min || luma_far > luma_max) {
+        return vec4<f32>(near, 1.0);
+    }
+    return vec4<f32>(far, 1.0);
+}
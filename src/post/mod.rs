@@ -0,0 +1,354 @@
+use std::time::Instant;
+
+use wgpu::util::DeviceExt;
+use wgpu::{BindGroup, BindGroupLayout, Device, Queue};
+
+use crate::bind_groups::{create_post_bind_group, create_post_bind_group_layout};
+use crate::camera::Camera;
+use crate::texture::DepthTexture;
+
+pub use effects::{
+    chromatic_aberration, color_grade, depth_of_field, film_grain, fog, fxaa, vignette,
+    warm_contrast, DepthOfFieldSettings, FogSettings,
+};
+
+mod effects;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct PostFrameUniform {
+    inv_view_proj: [[f32; 4]; 4],
+    eye: [f32; 3],
+    time: f32,
+    texel_size: [f32; 2],
+    znear: f32,
+    zfar: f32,
+    frame: u32,
+    _padding: [u32; 3],
+}
+
+/// Ordered chain of fullscreen effects applied to a finished frame.
+///
+/// Whatever is drawn into `input` is run through each enabled effect in turn, ping-ponging
+/// between two targets of the stack's format, and the last enabled effect writes to the output
+/// view given to `render`. Every effect can read the color written by the previous one, the
+/// scene depth and the per-frame values of `post.wgsl`, which include the camera so effects can
+/// turn depth back into distances and world positions.
+pub struct PostStack {
+    pub effects: Vec<PostEffect>,
+    format: wgpu::TextureFormat,
+    bind_group_layout: BindGroupLayout,
+    sampler: wgpu::Sampler,
+    frame_buffer: wgpu::Buffer,
+    targets: [wgpu::TextureView; 2],
+    bind_groups: [BindGroup; 2],
+    size: [u32; 2],
+    start: Instant,
+    frame: u32,
+}
+
+impl PostStack {
+    pub fn new(
+        device: &Device,
+        config: &wgpu::SurfaceConfiguration,
+        format: wgpu::TextureFormat,
+        depth_texture: &DepthTexture,
+    ) -> PostStack {
+        let frame_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Post Frame Buffer"),
+            size: std::mem::size_of::<PostFrameUniform>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("post_sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+        let bind_group_layout = create_post_bind_group_layout(device);
+        let (targets, bind_groups) = create_targets(
+            device,
+            config,
+            format,
+            depth_texture,
+            &sampler,
+            &frame_buffer,
+            &bind_group_layout,
+        );
+
+        PostStack {
+            effects: Vec::new(),
+            format,
+            bind_group_layout,
+            sampler,
+            frame_buffer,
+            targets,
+            bind_groups,
+            size: [config.width, config.height],
+            start: Instant::now(),
+            frame: 0,
+        }
+    }
+
+    /// Recreates the targets for the new surface size, call after recreating `depth_texture`.
+    pub fn resize(
+        &mut self,
+        device: &Device,
+        config: &wgpu::SurfaceConfiguration,
+        depth_texture: &DepthTexture,
+    ) {
+        self.size = [config.width, config.height];
+        (self.targets, self.bind_groups) = create_targets(
+            device,
+            config,
+            self.format,
+            depth_texture,
+            &self.sampler,
+            &self.frame_buffer,
+            &self.bind_group_layout,
+        );
+    }
+
+    pub fn push(&mut self, effect: PostEffect) {
+        self.effects.push(effect);
+    }
+
+    /// Flips the effect at `index`, counting in the order they were pushed.
+    pub fn toggle(&mut self, index: usize) {
+        if let Some(effect) = self.effects.get_mut(index) {
+            effect.enabled = !effect.enabled;
+        }
+    }
+
+    /// Whether any effect is enabled. When none are the frame can be drawn straight to the
+    /// output instead of `input`.
+    pub fn is_active(&self) -> bool {
+        self.effects.iter().any(|effect| effect.enabled)
+    }
+
+    /// Target the frame is drawn into before the effects run.
+    pub fn input(&self) -> &wgpu::TextureView {
+        &self.targets[0]
+    }
+
+    /// Uploads this frame's values, call after the camera's jitter for the frame is set so the
+    /// depth reconstructs to the right positions.
+    pub fn update(&mut self, queue: &Queue, camera: &Camera) {
+        use cgmath::SquareMatrix;
+        let inv_view_proj = camera.build_view_projection_matrix().invert().unwrap();
+        let uniform = PostFrameUniform {
+            inv_view_proj: inv_view_proj.into(),
+            eye: camera.eye().into(),
+            time: self.start.elapsed().as_secs_f32(),
+            texel_size: [1.0 / self.size[0] as f32, 1.0 / self.size[1] as f32],
+            znear: camera.znear(),
+            zfar: camera.zfar(),
+            frame: self.frame,
+            _padding: [0; 3],
+        };
+        queue.write_buffer(&self.frame_buffer, 0, bytemuck::cast_slice(&[uniform]));
+        self.frame = self.frame.wrapping_add(1);
+    }
+
+    pub fn render(&self, encoder: &mut wgpu::CommandEncoder, output: &wgpu::TextureView) {
+        let enabled = self
+            .effects
+            .iter()
+            .filter(|effect| effect.enabled)
+            .collect::<Vec<_>>();
+
+        for (index, effect) in enabled.iter().enumerate() {
+            let target = if index + 1 == enabled.len() {
+                output
+            } else {
+                &self.targets[(index + 1) % 2]
+            };
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some(effect.label),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: target,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+            render_pass.set_pipeline(&effect.pipeline);
+            render_pass.set_bind_group(0, &self.bind_groups[index % 2], &[]);
+            render_pass.set_bind_group(1, &effect.bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+    }
+}
+
+/// One fullscreen pass of a `PostStack`.
+///
+/// The shader is appended to `fullscreen.wgsl` and `post.wgsl` and provides `fs_main`. Its own
+/// parameters are a uniform at `@group(1) @binding(0)`, followed by any extra textures at
+/// consecutive bindings, all sampled with `s_post`.
+pub struct PostEffect {
+    pub label: &'static str,
+    pub enabled: bool,
+    pipeline: wgpu::RenderPipeline,
+    bind_group: BindGroup,
+}
+
+impl PostEffect {
+    pub fn new<P: bytemuck::Pod>(
+        device: &Device,
+        stack: &PostStack,
+        label: &'static str,
+        source: &str,
+        params: &P,
+        textures: &[(&wgpu::TextureView, wgpu::TextureViewDimension)],
+    ) -> PostEffect {
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(label),
+            contents: bytemuck::bytes_of(params),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+        let mut layout_entries = vec![wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        }];
+        let mut entries = vec![wgpu::BindGroupEntry {
+            binding: 0,
+            resource: uniform_buffer.as_entire_binding(),
+        }];
+        for (binding, (view, view_dimension)) in (1..).zip(textures) {
+            layout_entries.push(wgpu::BindGroupLayoutEntry {
+                binding,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: *view_dimension,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            });
+            entries.push(wgpu::BindGroupEntry {
+                binding,
+                resource: wgpu::BindingResource::TextureView(view),
+            });
+        }
+        let effect_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &layout_entries,
+            label: Some(label),
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &effect_layout,
+            entries: &entries,
+            label: Some(label),
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(label),
+            bind_group_layouts: &[&stack.bind_group_layout, &effect_layout],
+            push_constant_ranges: &[],
+        });
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(label),
+            source: wgpu::ShaderSource::Wgsl(
+                format!(
+                    "{}{}{}",
+                    include_str!("../fullscreen.wgsl"),
+                    include_str!("post.wgsl"),
+                    source
+                )
+                .into(),
+            ),
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(label),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: stack.format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        PostEffect {
+            label,
+            enabled: true,
+            pipeline,
+            bind_group,
+        }
+    }
+
+    /// Starts the effect disabled, to be switched on at runtime.
+    pub fn disabled(mut self) -> PostEffect {
+        self.enabled = false;
+        self
+    }
+}
+
+// Returns the two ping-pong targets and the bind groups reading each of them.
+fn create_targets(
+    device: &Device,
+    config: &wgpu::SurfaceConfiguration,
+    format: wgpu::TextureFormat,
+    depth_texture: &DepthTexture,
+    sampler: &wgpu::Sampler,
+    frame_buffer: &wgpu::Buffer,
+    bind_group_layout: &BindGroupLayout,
+) -> ([wgpu::TextureView; 2], [BindGroup; 2]) {
+    let targets = [0, 1].map(|_| {
+        device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some("post_target"),
+                size: wgpu::Extent3d {
+                    width: config.width,
+                    height: config.height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                    | wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            })
+            .create_view(&wgpu::TextureViewDescriptor::default())
+    });
+    let bind_groups = [0, 1].map(|index| {
+        create_post_bind_group(
+            device,
+            &targets[index],
+            &depth_texture.depth_view,
+            sampler,
+            frame_buffer,
+            bind_group_layout,
+        )
+    });
+
+    (targets, bind_groups)
+}
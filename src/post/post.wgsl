@@ -0,0 +1,65 @@
+// post.wgsl
+// Inputs shared by every post-processing effect: the color written by the previous effect, the
+// scene depth and per-frame values, including the camera the depth was rendered with. Effects
+// bind their own parameters in group 1.
+
+struct PostFrame {
+    inv_view_proj: mat4x4<f32>,
+    eye: vec3<f32>,
+    // Seconds since the stack was created.
+    time: f32,
+    texel_size: vec2<f32>,
+    znear: f32,
+    zfar: f32,
+    frame: u32,
+}
+
+@group(0) @binding(0)
+var t_color: texture_2d<f32>;
+@group(0) @binding(1)
+var t_depth: texture_depth_2d;
+@group(0) @binding(2)
+var s_post: sampler;
+@group(0) @binding(3)
+var<uniform> post: PostFrame;
+
+fn color_at(uv: vec2<f32>) -> vec3<f32> {
+    return textureSampleLevel(t_color, s_post, uv, 0.0).rgb;
+}
+
+// Non-linear depth buffer value under `uv`.
+fn depth_at(uv: vec2<f32>) -> f32 {
+    let size = vec2<i32>(textureDimensions(t_depth));
+    let coord = clamp(vec2<i32>(uv * vec2<f32>(size)), vec2<i32>(0), size - 1);
+    return textureLoad(t_depth, coord, 0);
+}
+
+// Distance along the view direction for a depth buffer value.
+fn linear_depth(depth: f32) -> f32 {
+    return post.znear * post.zfar / (post.zfar - depth * (post.zfar - post.znear));
+}
+
+// World space position of the surface under `uv` with the given depth buffer value.
+fn world_position_at(uv: vec2<f32>, depth: f32) -> vec3<f32> {
+    let ndc = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, depth, 1.0);
+    let world = post.inv_view_proj * ndc;
+    return world.xyz / world.w;
+}
+
+fn luma(color: vec3<f32>) -> f32 {
+    return dot(color, vec3<f32>(0.299, 0.587, 0.114));
+}
+
+// The targets are sRGB, so colors arrive linear. Effects working on display values, like FXAA
+// edges and LUT lookups, convert with these.
+fn linear_to_srgb(color: vec3<f32>) -> vec3<f32> {
+    let low = color * 12.92;
+    let high = 1.055 * pow(color, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return select(high, low, color <= vec3<f32>(0.0031308));
+}
+
+fn srgb_to_linear(color: vec3<f32>) -> vec3<f32> {
+    let low = color / 12.92;
+    let high = pow((color + 0.055) / 1.055, vec3<f32>(2.4));
+    return select(high, low, color <= vec3<f32>(0.04045));
+}
@@ -0,0 +1,23 @@
+// vignette.wgsl
+// Darkens the frame towards its corners, like light falling off through a lens.
+
+struct Vignette {
+    // Darkening in the corners, 0 leaves the frame untouched.
+    intensity: f32,
+    // Distance from the center where the falloff ends, half the frame height being 0.5.
+    radius: f32,
+    // Width of the falloff.
+    softness: f32,
+}
+
+@group(1) @binding(0)
+var<uniform> vignette: Vignette;
+
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let aspect = post.texel_size.y / post.texel_size.x;
+    let distance = length((in.uv - 0.5) * vec2<f32>(aspect, 1.0));
+    let falloff = smoothstep(vignette.radius, vignette.radius - vignette.softness, distance);
+    let color = color_at(in.uv) * mix(1.0 - vignette.intensity, 1.0, falloff);
+    return vec4<f32>(color, 1.0);
+}
@@ -0,0 +1,420 @@
+use std::f32::consts::FRAC_1_SQRT_2;
+
+use image::{DynamicImage, Rgba, RgbaImage};
+use wgpu::util::DeviceExt;
+use wgpu::Device;
+
+const WORKGROUP_SIZE: u32 = 8;
+
+// Noise octaves stop doubling the lattice past this many cells, where `f32` positions can no
+// longer tell the cells apart and the period would soon overflow.
+const MAX_PERIOD: u32 = 1 << 24;
+
+// Pattern ids, must match `procedural.wgsl`.
+const SOLID: u32 = 0;
+const CHECKERBOARD: u32 = 1;
+const GRID: u32 = 2;
+const LINEAR_GRADIENT: u32 = 3;
+const RADIAL_GRADIENT: u32 = 4;
+const PERLIN: u32 = 5;
+const SIMPLEX: u32 = 6;
+
+/// An image described by code, drawn at any size either on the CPU with `image` or on the GPU
+/// with `write`, see `Texture::from_pattern` and `Texture::generate`. Both produce the same texels
+/// up to rounding.
+///
+/// Positions are texture coordinates, (0, 0) at the top left and (1, 1) at the bottom right, so a
+/// pattern keeps its look at every size. Colors are blended as they are stored, sRGB encoded
+/// unless the texture is linear.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Pattern {
+    Solid([u8; 4]),
+    /// `cells` by `cells` squares alternating between the two colors, the first in the top left.
+    Checkerboard {
+        cells: u32,
+        colors: [[u8; 4]; 2],
+    },
+    /// Lines `line_width` texels wide between `cells` by `cells` squares. The lines along the
+    /// texture edges are half as wide, so the grid tiles.
+    Grid {
+        cells: u32,
+        line_width: f32,
+        line: [u8; 4],
+        background: [u8; 4],
+    },
+    /// Blends from the first color to the second along `direction`, reaching them at the corners
+    /// furthest behind and ahead.
+    LinearGradient {
+        direction: [f32; 2],
+        colors: [[u8; 4]; 2],
+    },
+    /// Blends from the first color at the center to the second at the middle of the edges.
+    RadialGradient {
+        colors: [[u8; 4]; 2],
+    },
+    /// Fractal Perlin noise blended between the two colors. The first of `octaves` has `cells`
+    /// lattice cells across the texture, each further one doubles the frequency and halves the
+    /// amplitude, up to 2^24 cells. The lattice wraps around, so the noise tiles.
+    Perlin {
+        cells: u32,
+        octaves: u32,
+        seed: u32,
+        colors: [[u8; 4]; 2],
+    },
+    /// Fractal simplex noise, like `Perlin` but without its axis aligned artifacts. Doesn't tile.
+    Simplex {
+        cells: u32,
+        octaves: u32,
+        seed: u32,
+        colors: [[u8; 4]; 2],
+    },
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct PatternUniform {
+    colors: [[f32; 4]; 2],
+    direction: [f32; 2],
+    pattern: u32,
+    cells: u32,
+    octaves: u32,
+    seed: u32,
+    line_width: f32,
+    _padding: u32,
+}
+
+/// The compute pipeline that draws patterns on the GPU. Create it once and pass it to every
+/// `Pattern::write` or `Texture::generate`.
+pub struct PatternPipeline {
+    pipeline: wgpu::ComputePipeline,
+}
+
+impl PatternPipeline {
+    pub fn new(device: &Device) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Pattern Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("procedural.wgsl").into()),
+        });
+        // The layout is derived from the shader.
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Pattern Pipeline"),
+            layout: None,
+            module: &shader,
+            entry_point: "main",
+        });
+        Self { pipeline }
+    }
+}
+
+impl Pattern {
+    /// Draws the pattern on the CPU.
+    pub fn image(&self, width: u32, height: u32) -> DynamicImage {
+        let uniform = self.uniform();
+        let size = [width as f32, height as f32];
+        DynamicImage::ImageRgba8(RgbaImage::from_fn(width, height, |x, y| {
+            let uv = [(x as f32 + 0.5) / size[0], (y as f32 + 0.5) / size[1]];
+            Rgba(shade(&uniform, uv, size).map(|value| (value * 255.0).round() as u8))
+        }))
+    }
+
+    /// Records a compute pass drawing the pattern into `view`, a 2D `Rgba8Unorm` view with
+    /// `STORAGE_BINDING` usage.
+    pub fn write(
+        &self,
+        device: &Device,
+        pipeline: &PatternPipeline,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        width: u32,
+        height: u32,
+    ) {
+        let uniform = self.uniform();
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Pattern Buffer"),
+            contents: bytemuck::cast_slice(&[uniform]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let pipeline = &pipeline.pipeline;
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("pattern_bind_group"),
+            layout: &pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Pattern Pass"),
+        });
+        compute_pass.set_pipeline(pipeline);
+        compute_pass.set_bind_group(0, &bind_group, &[]);
+        compute_pass.dispatch_workgroups(
+            width.div_ceil(WORKGROUP_SIZE),
+            height.div_ceil(WORKGROUP_SIZE),
+            1,
+        );
+    }
+
+    fn uniform(&self) -> PatternUniform {
+        let mut uniform = PatternUniform {
+            colors: [[0.0; 4]; 2],
+            direction: [0.0; 2],
+            pattern: SOLID,
+            cells: 1,
+            octaves: 1,
+            seed: 0,
+            line_width: 0.0,
+            _padding: 0,
+        };
+        let colors = match *self {
+            Pattern::Solid(color) => [color; 2],
+            Pattern::Checkerboard { cells, colors } => {
+                uniform.pattern = CHECKERBOARD;
+                uniform.cells = cells;
+                colors
+            }
+            Pattern::Grid {
+                cells,
+                line_width,
+                line,
+                background,
+            } => {
+                uniform.pattern = GRID;
+                uniform.cells = cells;
+                uniform.line_width = line_width;
+                [background, line]
+            }
+            Pattern::LinearGradient { direction, colors } => {
+                uniform.pattern = LINEAR_GRADIENT;
+                uniform.direction = direction;
+                colors
+            }
+            Pattern::RadialGradient { colors } => {
+                uniform.pattern = RADIAL_GRADIENT;
+                colors
+            }
+            Pattern::Perlin {
+                cells,
+                octaves,
+                seed,
+                colors,
+            }
+            | Pattern::Simplex {
+                cells,
+                octaves,
+                seed,
+                colors,
+            } => {
+                uniform.pattern = if matches!(self, Pattern::Perlin { .. }) {
+                    PERLIN
+                } else {
+                    SIMPLEX
+                };
+                uniform.cells = cells;
+                uniform.octaves = octaves;
+                uniform.seed = seed;
+                colors
+            }
+        };
+        uniform.colors = colors.map(|color| color.map(|value| value as f32 / 255.0));
+        uniform
+    }
+}
+
+// Mirrors `shade` in `procedural.wgsl`: the color at `uv` in a texture of `size` texels.
+fn shade(uniform: &PatternUniform, uv: [f32; 2], size: [f32; 2]) -> [f32; 4] {
+    let cells = uniform.cells.max(1);
+    let t = match uniform.pattern {
+        CHECKERBOARD => {
+            let cell = uv.map(|value| (value * cells as f32).floor() as u32);
+            ((cell[0] + cell[1]) % 2) as f32
+        }
+        GRID => {
+            // Distance in texels to the nearest line along each axis.
+            let distance = [0, 1].map(|axis| {
+                let cell_size = size[axis] / cells as f32;
+                let position = uv[axis] * cells as f32 + 0.5;
+                (position - position.floor() - 0.5).abs() * cell_size
+            });
+            (distance[0].min(distance[1]) < uniform.line_width * 0.5) as u32 as f32
+        }
+        LINEAR_GRADIENT => {
+            let [x, y] = uniform.direction;
+            let start = x.min(0.0) + y.min(0.0);
+            let end = x.max(0.0) + y.max(0.0);
+            (uv[0] * x + uv[1] * y - start) / (end - start).max(1e-6)
+        }
+        RADIAL_GRADIENT => ((uv[0] - 0.5).hypot(uv[1] - 0.5) * 2.0).min(1.0),
+        PERLIN | SIMPLEX => {
+            let mut value = 0.0;
+            let mut amplitude = 1.0;
+            let mut total = 0.0;
+            let mut period = cells.min(MAX_PERIOD);
+            for octave in 0..uniform.octaves.max(1) {
+                let p = uv.map(|value| value * period as f32);
+                let seed = uniform.seed.wrapping_add(octave);
+                value += amplitude
+                    * if uniform.pattern == PERLIN {
+                        perlin(p, period, seed)
+                    } else {
+                        simplex(p, seed)
+                    };
+                total += amplitude;
+                amplitude *= 0.5;
+                if period > MAX_PERIOD / 2 {
+                    break;
+                }
+                period *= 2;
+            }
+            0.5 + 0.5 * value / total
+        }
+        _ => 0.0,
+    };
+    let t = t.clamp(0.0, 1.0);
+    let [from, to] = uniform.colors;
+    [0, 1, 2, 3].map(|i| from[i] + (to[i] - from[i]) * t)
+}
+
+// Unit gradients for the noise lattice, indexed by the low bits of `hash`.
+const GRADIENTS: [[f32; 2]; 8] = [
+    [1.0, 0.0],
+    [-1.0, 0.0],
+    [0.0, 1.0],
+    [0.0, -1.0],
+    [FRAC_1_SQRT_2, FRAC_1_SQRT_2],
+    [-FRAC_1_SQRT_2, FRAC_1_SQRT_2],
+    [FRAC_1_SQRT_2, -FRAC_1_SQRT_2],
+    [-FRAC_1_SQRT_2, -FRAC_1_SQRT_2],
+];
+
+// Integer hash of a lattice point, identical in `procedural.wgsl`.
+fn hash(x: u32, y: u32, seed: u32) -> u32 {
+    let mut h =
+        x.wrapping_mul(0x8da6b343) ^ y.wrapping_mul(0xd8163841) ^ seed.wrapping_mul(0xcb1ab31f);
+    h = (h ^ (h >> 16)).wrapping_mul(0x7feb352d);
+    h = (h ^ (h >> 15)).wrapping_mul(0x846ca68b);
+    h ^ (h >> 16)
+}
+
+fn gradient(x: u32, y: u32, seed: u32, offset: [f32; 2]) -> f32 {
+    let g = GRADIENTS[(hash(x, y, seed) & 7) as usize];
+    g[0] * offset[0] + g[1] * offset[1]
+}
+
+// Perlin noise in -1..1 with the lattice wrapping every `period` cells.
+fn perlin(p: [f32; 2], period: u32, seed: u32) -> f32 {
+    let cell = p.map(f32::floor);
+    let f = [p[0] - cell[0], p[1] - cell[1]];
+    let [x0, y0] = cell.map(|value| value as u32 % period);
+    let (x1, y1) = ((x0 + 1) % period, (y0 + 1) % period);
+    // Quintic fade, continuous in the second derivative across cells.
+    let fade = f.map(|t| t * t * t * (t * (t * 6.0 - 15.0) + 10.0));
+    let mix = |a: f32, b: f32, t: f32| a + (b - a) * t;
+    let top = mix(
+        gradient(x0, y0, seed, f),
+        gradient(x1, y0, seed, [f[0] - 1.0, f[1]]),
+        fade[0],
+    );
+    let bottom = mix(
+        gradient(x0, y1, seed, [f[0], f[1] - 1.0]),
+        gradient(x1, y1, seed, [f[0] - 1.0, f[1] - 1.0]),
+        fade[0],
+    );
+    // Unit gradients reach at most half a diagonal.
+    mix(top, bottom, fade[1]) * std::f32::consts::SQRT_2
+}
+
+// 2D simplex noise in about -1..1, summing the contributions of the three corners of the triangle
+// around `p` on the skewed lattice.
+fn simplex(p: [f32; 2], seed: u32) -> f32 {
+    const F2: f32 = 0.366_025_4; // (sqrt(3) - 1) / 2
+    const G2: f32 = 0.211_324_87; // (3 - sqrt(3)) / 6
+    let s = (p[0] + p[1]) * F2;
+    let cell = [(p[0] + s).floor(), (p[1] + s).floor()];
+    let t = (cell[0] + cell[1]) * G2;
+    let x0 = [p[0] - (cell[0] - t), p[1] - (cell[1] - t)];
+    let step = if x0[0] > x0[1] {
+        [1.0, 0.0]
+    } else {
+        [0.0, 1.0]
+    };
+    let corners = [
+        ([0.0, 0.0], x0),
+        (step, [x0[0] - step[0] + G2, x0[1] - step[1] + G2]),
+        ([1.0, 1.0], [x0[0] - 1.0 + 2.0 * G2, x0[1] - 1.0 + 2.0 * G2]),
+    ];
+    let mut value = 0.0;
+    for (corner, offset) in corners {
+        let falloff = 0.5 - offset[0] * offset[0] - offset[1] * offset[1];
+        if falloff > 0.0 {
+            let (x, y) = (cell[0] + corner[0], cell[1] + corner[1]);
+            value += falloff.powi(4) * gradient(x as i32 as u32, y as i32 as u32, seed, offset);
+        }
+    }
+    70.0 * value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn noise(cells: u32, octaves: u32) -> PatternUniform {
+        Pattern::Perlin {
+            cells,
+            octaves,
+            seed: 7,
+            colors: [[0; 4], [255; 4]],
+        }
+        .uniform()
+    }
+
+    #[test]
+    fn perlin_noise_wraps_around() {
+        let uniform = noise(4, 3);
+        for i in 0..16 {
+            let uv = [i as f32 / 16.0 + 0.01, 0.3];
+            let wrapped = [uv[0] + 1.0, uv[1] + 1.0];
+            let (a, b) = (
+                shade(&uniform, uv, [64.0; 2]),
+                shade(&uniform, wrapped, [64.0; 2]),
+            );
+            assert!((a[0] - b[0]).abs() < 1e-4, "{a:?} != {b:?} at {uv:?}");
+        }
+        // The lattice corners at the edges meet.
+        for y in 0..8 {
+            let p = [0.0, y as f32 * 0.37];
+            assert!((perlin(p, 5, 1) - perlin([5.0, p[1]], 5, 1)).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn noise_stays_in_range() {
+        let (mut min, mut max) = (f32::MAX, f32::MIN);
+        for y in 0..200 {
+            for x in 0..200 {
+                let p = [x as f32 * 0.037, y as f32 * 0.041];
+                for value in [perlin(p, 8, 3), simplex(p, 3)] {
+                    min = min.min(value);
+                    max = max.max(value);
+                }
+            }
+        }
+        assert!(min >= -1.0 && max <= 1.0, "{min}..{max}");
+        assert!(min < -0.5 && max > 0.5, "{min}..{max}");
+    }
+
+    #[test]
+    fn stops_doubling_the_period_before_it_overflows() {
+        for (cells, octaves) in [(3, 40), (1 << 20, 8), (u32::MAX, u32::MAX)] {
+            let value = shade(&noise(cells, octaves), [0.7, 0.2], [16.0; 2]);
+            assert!((0.0..=1.0).contains(&value[0]), "{value:?}");
+        }
+    }
+}
@@ -0,0 +1,146 @@
+// procedural.wgsl
+// Draws a `Pattern` into a storage texture, texel for texel what `shade` in procedural.rs draws on
+// the CPU.
+
+struct PatternParams {
+    colors: array<vec4<f32>, 2>,
+    direction: vec2<f32>,
+    pattern: u32,
+    cells: u32,
+    octaves: u32,
+    seed: u32,
+    line_width: f32,
+}
+
+@group(0) @binding(0)
+var dst: texture_storage_2d<rgba8unorm, write>;
+@group(0) @binding(1)
+var<uniform> params: PatternParams;
+
+const CHECKERBOARD: u32 = 1u;
+const GRID: u32 = 2u;
+const LINEAR_GRADIENT: u32 = 3u;
+const RADIAL_GRADIENT: u32 = 4u;
+const PERLIN: u32 = 5u;
+const SIMPLEX: u32 = 6u;
+
+// Mirrors `MAX_PERIOD` in procedural.rs.
+const MAX_PERIOD: u32 = 16777216u;
+
+fn hash(x: u32, y: u32, seed: u32) -> u32 {
+    var h = (x * 0x8da6b343u) ^ (y * 0xd8163841u) ^ (seed * 0xcb1ab31fu);
+    h = (h ^ (h >> 16u)) * 0x7feb352du;
+    h = (h ^ (h >> 15u)) * 0x846ca68bu;
+    return h ^ (h >> 16u);
+}
+
+fn gradient(x: u32, y: u32, seed: u32, offset: vec2<f32>) -> f32 {
+    let d = 0.70710678;
+    var gradients = array<vec2<f32>, 8>(
+        vec2<f32>(1.0, 0.0),
+        vec2<f32>(-1.0, 0.0),
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(0.0, -1.0),
+        vec2<f32>(d, d),
+        vec2<f32>(-d, d),
+        vec2<f32>(d, -d),
+        vec2<f32>(-d, -d),
+    );
+    return dot(gradients[hash(x, y, seed) & 7u], offset);
+}
+
+fn perlin(p: vec2<f32>, period: u32, seed: u32) -> f32 {
+    let cell = floor(p);
+    let f = p - cell;
+    let c0 = vec2<u32>(cell) % period;
+    let c1 = (c0 + 1u) % period;
+    let fade = f * f * f * (f * (f * 6.0 - 15.0) + 10.0);
+    let top = mix(
+        gradient(c0.x, c0.y, seed, f),
+        gradient(c1.x, c0.y, seed, f - vec2<f32>(1.0, 0.0)),
+        fade.x,
+    );
+    let bottom = mix(
+        gradient(c0.x, c1.y, seed, f - vec2<f32>(0.0, 1.0)),
+        gradient(c1.x, c1.y, seed, f - vec2<f32>(1.0, 1.0)),
+        fade.x,
+    );
+    return mix(top, bottom, fade.y) * 1.41421356;
+}
+
+fn simplex_corner(cell: vec2<f32>, seed: u32, offset: vec2<f32>) -> f32 {
+    let falloff = 0.5 - dot(offset, offset);
+    if (falloff <= 0.0) {
+        return 0.0;
+    }
+    let lattice = bitcast<vec2<u32>>(vec2<i32>(cell));
+    let falloff2 = falloff * falloff;
+    return falloff2 * falloff2 * gradient(lattice.x, lattice.y, seed, offset);
+}
+
+fn simplex(p: vec2<f32>, seed: u32) -> f32 {
+    let F2 = 0.36602540;
+    let G2 = 0.21132487;
+    let cell = floor(p + (p.x + p.y) * F2);
+    let x0 = p - (cell - (cell.x + cell.y) * G2);
+    let step = select(vec2<f32>(0.0, 1.0), vec2<f32>(1.0, 0.0), x0.x > x0.y);
+    var value = simplex_corner(cell, seed, x0);
+    value += simplex_corner(cell + step, seed, x0 - step + G2);
+    value += simplex_corner(cell + 1.0, seed, x0 - 1.0 + 2.0 * G2);
+    return 70.0 * value;
+}
+
+fn shade(uv: vec2<f32>, size: vec2<f32>) -> vec4<f32> {
+    let cells = max(params.cells, 1u);
+    var t = 0.0;
+    let pattern = params.pattern;
+    if (pattern == CHECKERBOARD) {
+        let cell = vec2<u32>(floor(uv * f32(cells)));
+        t = f32((cell.x + cell.y) % 2u);
+    } else if (pattern == GRID) {
+        // Distance in texels to the nearest line along each axis.
+        let position = uv * f32(cells) + 0.5;
+        let distance = abs(fract(position) - 0.5) * size / f32(cells);
+        t = select(0.0, 1.0, min(distance.x, distance.y) < params.line_width * 0.5);
+    } else if (pattern == LINEAR_GRADIENT) {
+        let direction = params.direction;
+        let start = min(direction.x, 0.0) + min(direction.y, 0.0);
+        let end = max(direction.x, 0.0) + max(direction.y, 0.0);
+        t = (dot(uv, direction) - start) / max(end - start, 1e-6);
+    } else if (pattern == RADIAL_GRADIENT) {
+        t = min(length(uv - 0.5) * 2.0, 1.0);
+    } else if (pattern == PERLIN || pattern == SIMPLEX) {
+        var value = 0.0;
+        var amplitude = 1.0;
+        var total = 0.0;
+        var period = min(cells, MAX_PERIOD);
+        for (var octave = 0u; octave < max(params.octaves, 1u); octave++) {
+            let p = uv * f32(period);
+            let seed = params.seed + octave;
+            if (pattern == PERLIN) {
+                value += amplitude * perlin(p, period, seed);
+            } else {
+                value += amplitude * simplex(p, seed);
+            }
+            total += amplitude;
+            amplitude *= 0.5;
+            if (period > MAX_PERIOD / 2u) {
+                break;
+            }
+            period *= 2u;
+        }
+        t = 0.5 + 0.5 * value / total;
+    }
+    return mix(params.colors[0], params.colors[1], clamp(t, 0.0, 1.0));
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = textureDimensions(dst);
+    if (id.x >= size.x || id.y >= size.y) {
+        return;
+    }
+
+    let uv = (vec2<f32>(id.xy) + 0.5) / vec2<f32>(size);
+    textureStore(dst, vec2<i32>(id.xy), shade(uv, vec2<f32>(size)));
+}
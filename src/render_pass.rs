@@ -5,6 +5,108 @@ use crate::object;
 use crate::object::Vertex;
 use crate::texture::Texture;
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ShadingModel {
+    Lambert,
+    Pbr,
+}
+
+impl ShadingModel {
+    pub fn toggle(self) -> ShadingModel {
+        match self {
+            ShadingModel::Lambert => ShadingModel::Pbr,
+            ShadingModel::Pbr => ShadingModel::Lambert,
+        }
+    }
+
+    fn shader(self, materials: MaterialBinding) -> wgpu::ShaderModuleDescriptor<'static> {
+        // The fragment stages share the model vertex stage, the point light code and the
+        // environment lighting.
+        let (label, source) = match (self, materials) {
+            (ShadingModel::Lambert, MaterialBinding::PerMaterial) => (
+                "Shader",
+                concat!(
+                    include_str!("model.wgsl"),
+                    include_str!("lights.wgsl"),
+                    include_str!("environment/ambient.wgsl"),
+                    include_str!("material.wgsl"),
+                    include_str!("shader.wgsl")
+                ),
+            ),
+            (ShadingModel::Lambert, MaterialBinding::Packed) => (
+                "Packed Material Shader",
+                concat!(
+                    include_str!("model.wgsl"),
+                    include_str!("lights.wgsl"),
+                    include_str!("environment/ambient.wgsl"),
+                    include_str!("material_packed.wgsl"),
+                    include_str!("shader.wgsl")
+                ),
+            ),
+            (ShadingModel::Pbr, MaterialBinding::PerMaterial) => (
+                "PBR Shader",
+                concat!(
+                    include_str!("model.wgsl"),
+                    include_str!("lights.wgsl"),
+                    include_str!("environment/ambient.wgsl"),
+                    include_str!("brdf.wgsl"),
+                    include_str!("material.wgsl"),
+                    include_str!("pbr.wgsl")
+                ),
+            ),
+            (ShadingModel::Pbr, MaterialBinding::Packed) => (
+                "Packed Material PBR Shader",
+                concat!(
+                    include_str!("model.wgsl"),
+                    include_str!("lights.wgsl"),
+                    include_str!("environment/ambient.wgsl"),
+                    include_str!("brdf.wgsl"),
+                    include_str!("material_packed.wgsl"),
+                    include_str!("pbr.wgsl")
+                ),
+            ),
+        };
+
+        wgpu::ShaderModuleDescriptor {
+            label: Some(label),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        }
+    }
+}
+
+/// How models bind their materials: a bind group per material, or every material of the model
+/// packed into texture arrays behind a single bind group, see `PackedMaterials`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MaterialBinding {
+    PerMaterial,
+    Packed,
+}
+
+impl MaterialBinding {
+    pub fn toggle(self) -> MaterialBinding {
+        match self {
+            MaterialBinding::PerMaterial => MaterialBinding::Packed,
+            MaterialBinding::Packed => MaterialBinding::PerMaterial,
+        }
+    }
+}
+
+/// Whether the scene is shaded while drawing it, or drawn into a G-buffer and shaded afterwards.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RenderPath {
+    Forward,
+    Deferred,
+}
+
+impl RenderPath {
+    pub fn toggle(self) -> RenderPath {
+        match self {
+            RenderPath::Forward => RenderPath::Deferred,
+            RenderPath::Deferred => RenderPath::Forward,
+        }
+    }
+}
+
 pub struct RenderPass {
     pub render_pipeline: wgpu::RenderPipeline,
 }
@@ -12,8 +114,10 @@ pub struct RenderPass {
 impl RenderPass {
     pub fn new(
         device: &wgpu::Device,
-        config: &wgpu::SurfaceConfiguration,
+        color_formats: &[wgpu::TextureFormat],
         bind_group_layouts: &[&BindGroupLayout],
+        shading_model: ShadingModel,
+        materials: MaterialBinding,
     ) -> RenderPass {
         let render_pipeline_layout =
             device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
@@ -22,21 +126,14 @@ impl RenderPass {
                 push_constant_ranges: &[],
             });
 
-        let render_pipeline = {
-            let shader = wgpu::ShaderModuleDescriptor {
-                label: Some("Shader"),
-                source: wgpu::ShaderSource::Wgsl(include_str!("shader.wgsl").into()),
-            };
-
-            create_render_pipeline(
-                device,
-                &render_pipeline_layout,
-                config.format,
-                Some(Texture::DEPTH_FORMAT),
-                &[object::ModelVertex::desc(), InstanceRaw::desc()],
-                shader,
-            )
-        };
+        let render_pipeline = create_render_pipeline(
+            device,
+            &render_pipeline_layout,
+            color_formats,
+            Some(Texture::DEPTH_FORMAT),
+            &[object::ModelVertex::desc(), InstanceRaw::desc()],
+            shading_model.shader(materials),
+        );
 
         RenderPass { render_pipeline }
     }
@@ -1,21 +1,36 @@
+use cgmath::{InnerSpace, Zero};
+use std::cell::RefCell;
 use std::io::{BufReader, Cursor};
+use std::sync::Arc;
 use wgpu::util::DeviceExt;
 use wgpu::{Device, Queue};
-use crate::bind_groups::create_bind_group;
 
+use crate::compressed::CompressedImage;
+use crate::environment::Environment;
+use crate::error::ResourceError;
 use crate::object;
-use crate::texture::Texture;
+use crate::texture::{
+    decode_image, Mipmaps, Texture, TextureCache, TextureHelpers, TextureOptions,
+};
 
 pub async fn load_model(
     file_name: &str,
     device: &Device,
     queue: &Queue,
+    helpers: &mut TextureHelpers,
+    texture_cache: &mut TextureCache,
     layout: &wgpu::BindGroupLayout,
-) -> anyhow::Result<object::Model> {
+) -> Result<object::Model, ResourceError> {
     let obj_text = load_string(file_name).await?;
     let obj_cursor = Cursor::new(obj_text);
     let mut obj_reader = BufReader::new(obj_cursor);
 
+    // tobj only lets the material loader return its own error type, so the loader keeps the
+    // actual error here for when tobj hands back a failed material library.
+    let mtl_error = RefCell::new(None);
+    // Deprecated since tobj 4.0.3 for parsing synchronously, which is all it needs to do with the
+    // OBJ already in memory. Only the material libraries are loaded asynchronously.
+    #[allow(deprecated)]
     let (models, obj_materials) = tobj::load_obj_buf_async(
         &mut obj_reader,
         &tobj::LoadOptions {
@@ -23,43 +38,161 @@ pub async fn load_model(
             single_index: true,
             ..Default::default()
         },
-        |p| async move {
-            let mat_text = load_string(&p).await.unwrap();
-            tobj::load_mtl_buf(&mut BufReader::new(Cursor::new(mat_text)))
+        |p| {
+            let mtl_error = &mtl_error;
+            async move {
+                let mat_text = match load_string(&p).await {
+                    Ok(mat_text) => mat_text,
+                    Err(error) => {
+                        *mtl_error.borrow_mut() = Some(error);
+                        return Err(tobj::LoadError::OpenFileFailed);
+                    }
+                };
+                tobj::load_mtl_buf(&mut BufReader::new(Cursor::new(mat_text))).inspect_err(
+                    |&error| {
+                        *mtl_error.borrow_mut() = Some(ResourceError::decode(&p, error));
+                    },
+                )
+            }
         },
     )
-    .await?;
+    .await
+    .map_err(|error| ResourceError::decode(file_name, error))?;
+    let obj_materials = obj_materials.map_err(|error| {
+        mtl_error
+            .take()
+            .unwrap_or_else(|| ResourceError::decode(file_name, error))
+    })?;
 
+    let fallback = object::MaterialTextures::fallback(device, queue, helpers, texture_cache);
     let mut materials = Vec::new();
-    for m in obj_materials? {
-        let diffuse_texture = load_texture(&m.diffuse_texture.unwrap(), device, queue).await;
-        let bind_group = create_bind_group(device, layout, &diffuse_texture);
-
-        materials.push(object::Material {
-            name: m.name,
-            diffuse_texture,
-            bind_group,
-        })
+    for m in obj_materials {
+        let diffuse = match &m.diffuse_texture {
+            Some(statement) => or_fallback(
+                load_material_texture(statement, false, device, queue, helpers, texture_cache)
+                    .await,
+                || {
+                    texture_cache.get_or_insert_with(
+                        "checker_texture",
+                        &TextureOptions::checker(),
+                        || Texture::checker(device, queue, helpers),
+                    )
+                },
+            ),
+            None => fallback.diffuse.clone(),
+        };
+        let normal = match &m.normal_texture {
+            Some(statement) => or_fallback(
+                load_material_texture(statement, true, device, queue, helpers, texture_cache).await,
+                || fallback.normal.clone(),
+            ),
+            None => fallback.normal.clone(),
+        };
+        let metallic_roughness = or_fallback(
+            load_metallic_roughness_texture(
+                texture_param(&m, "map_Pr"),
+                texture_param(&m, "map_Pm"),
+                &fallback.metallic_roughness,
+                device,
+                queue,
+                helpers,
+                texture_cache,
+            )
+            .await,
+            || fallback.metallic_roughness.clone(),
+        );
+        // OBJ has no occlusion map, exporters commonly bake it into the ambient map instead.
+        let occlusion = match &m.ambient_texture {
+            Some(statement) => or_fallback(
+                load_material_texture(statement, true, device, queue, helpers, texture_cache).await,
+                || fallback.occlusion.clone(),
+            ),
+            None => fallback.occlusion.clone(),
+        };
+        let emissive = match texture_param(&m, "map_Ke") {
+            Some(statement) => or_fallback(
+                load_material_texture(statement, false, device, queue, helpers, texture_cache)
+                    .await,
+                || fallback.emissive.clone(),
+            ),
+            None => fallback.emissive.clone(),
+        };
+
+        let mut uniform = object::MaterialUniform::default();
+        // Exporters write a `Kd` next to `map_Kd` as well, but only untextured materials use it.
+        if let (None, Some([r, g, b])) = (&m.diffuse_texture, m.diffuse) {
+            uniform.base_color_factor = [r, g, b, 1.0];
+        }
+        if let Some(roughness) = float_param(&m, "Pr") {
+            uniform.roughness_factor = roughness;
+        }
+        if let Some(metallic) = float_param(&m, "Pm") {
+            uniform.metallic_factor = metallic;
+        }
+        // tobj parses `Ke` itself from 4.0.5 on, earlier versions leave it in `unknown_param`.
+        if let Some(emissive) = m.emissive.or_else(|| color_param(&m, "Ke")) {
+            uniform.emissive_factor = emissive;
+        } else if texture_param(&m, "map_Ke").is_some() {
+            uniform.emissive_factor = [1.0; 3];
+        }
+
+        let textures = object::MaterialTextures {
+            diffuse,
+            normal,
+            metallic_roughness,
+            occlusion,
+            emissive,
+        };
+        materials.push(object::Material::new(
+            device, layout, m.name, textures, uniform,
+        ));
     }
 
+    // Meshes without a material, or with one the material libraries don't define, share a
+    // default material added after the others.
+    let default_material = materials.len();
+    let mut uses_default_material = false;
     let meshes = models
         .into_iter()
         .map(|m| {
-            let vertices = (0..m.mesh.positions.len() / 3)
+            if m.mesh.normals.is_empty() {
+                return Err(ResourceError::missing_attribute(
+                    file_name,
+                    format!("normals on mesh {:?}", m.name),
+                ));
+            }
+
+            let material = match m.mesh.material_id {
+                Some(material_id) if material_id < default_material => material_id,
+                _ => {
+                    uses_default_material = true;
+                    default_material
+                }
+            };
+
+            let mut vertices = (0..m.mesh.positions.len() / 3)
                 .map(|i| object::ModelVertex {
                     position: [
                         m.mesh.positions[i * 3],
                         m.mesh.positions[i * 3 + 1],
                         m.mesh.positions[i * 3 + 2],
                     ],
-                    tex_coords: [m.mesh.texcoords[i * 2], m.mesh.texcoords[i * 2 + 1]],
+                    // Untextured OBJs often leave out texture coordinates altogether.
+                    tex_coords: match m.mesh.texcoords.get(i * 2..i * 2 + 2) {
+                        Some(&[u, v]) => [u, v],
+                        _ => [0.0; 2],
+                    },
                     normal: [
                         m.mesh.normals[i * 3],
                         m.mesh.normals[i * 3 + 1],
                         m.mesh.normals[i * 3 + 2],
                     ],
+                    tangent: [0.0; 3],
+                    bitangent: [0.0; 3],
+                    material: material as u32,
                 })
                 .collect::<Vec<_>>();
+            compute_tangents(&mut vertices, &m.mesh.indices);
 
             let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
                 label: Some(&format!("{:?} Vertex Buffer", file_name)),
@@ -72,38 +205,348 @@ pub async fn load_model(
                 usage: wgpu::BufferUsages::INDEX,
             });
 
-            object::Mesh {
+            Ok(object::Mesh {
                 name: file_name.to_string(),
                 vertex_buffer,
                 index_buffer,
                 num_elements: m.mesh.indices.len() as u32,
-                material: m.mesh.material_id.unwrap_or(0),
-            }
+                material,
+            })
         })
-        .collect::<Vec<_>>();
+        .collect::<Result<Vec<_>, _>>()?;
+    if uses_default_material {
+        materials.push(object::Material::new(
+            device,
+            layout,
+            "default".to_string(),
+            fallback,
+            object::MaterialUniform::default(),
+        ));
+    }
 
     Ok(object::Model { meshes, materials })
 }
 
-pub async fn load_texture(file_name: &str, device: &Device, queue: &Queue) -> Texture {
-    let data = load_binary(file_name).await.unwrap();
-    Texture::from_bytes(device, queue, &data, Some(file_name))
+/// Material textures that fail to load are logged as warnings and replaced, rather than failing
+/// the whole model.
+fn or_fallback(
+    texture: Result<Arc<Texture>, ResourceError>,
+    fallback: impl FnOnce() -> Arc<Texture>,
+) -> Arc<Texture> {
+    texture.unwrap_or_else(|error| {
+        log::warn!("{error}, using a fallback texture");
+        fallback()
+    })
+}
+
+fn float_param(material: &tobj::Material, key: &str) -> Option<f32> {
+    material.unknown_param.get(key)?.trim().parse().ok()
+}
+
+fn color_param(material: &tobj::Material, key: &str) -> Option<[f32; 3]> {
+    let values = material
+        .unknown_param
+        .get(key)?
+        .split_whitespace()
+        .map(str::parse)
+        .collect::<Result<Vec<f32>, _>>()
+        .ok()?;
+    match values[..] {
+        [r, g, b] => Some([r, g, b]),
+        [v] => Some([v; 3]),
+        _ => None,
+    }
+}
+
+/// Texture statements tobj doesn't know about, including any `-option` arguments.
+fn texture_param<'a>(material: &'a tobj::Material, key: &str) -> Option<&'a str> {
+    material
+        .unknown_param
+        .get(key)
+        .map(|statement| statement.trim())
+}
+
+/// Splits an MTL texture statement into the file name, its last word, and the options to load
+/// it with. MTL textures repeat unless the statement has `-clamp on`, and material textures are
+/// filtered anisotropically as surfaces are often seen at grazing angles. They can be copied
+/// from, for packing a model's materials into texture arrays.
+fn texture_statement(statement: &str, linear: bool) -> (&str, TextureOptions) {
+    let words = statement.split_whitespace().collect::<Vec<_>>();
+    let clamp = words
+        .windows(2)
+        .any(|pair| pair[0] == "-clamp" && pair[1] == "on");
+    let mut options = TextureOptions {
+        linear,
+        anisotropy_clamp: 16,
+        usage: wgpu::TextureUsages::COPY_SRC,
+        ..Default::default()
+    };
+    if !clamp {
+        options = options.repeat();
+    }
+    (words.last().copied().unwrap_or_default(), options)
+}
+
+async fn load_material_texture(
+    statement: &str,
+    linear: bool,
+    device: &Device,
+    queue: &Queue,
+    helpers: &mut TextureHelpers,
+    texture_cache: &mut TextureCache,
+) -> Result<Arc<Texture>, ResourceError> {
+    let (file_name, options) = texture_statement(statement, linear);
+    if let Some(texture) = texture_cache.get(file_name, &options) {
+        return Ok(texture);
+    }
+    let texture = load_texture(file_name, &options, device, queue, helpers).await?;
+    Ok(texture_cache.insert(file_name, &options, texture))
+}
+
+/// Packs the separate OBJ roughness (`map_Pr`) and metallic (`map_Pm`) maps into a single texture
+/// with the glTF layout: roughness in green, metallic in blue. Materials with neither get
+/// `fallback`.
+async fn load_metallic_roughness_texture(
+    roughness_statement: Option<&str>,
+    metallic_statement: Option<&str>,
+    fallback: &Arc<Texture>,
+    device: &Device,
+    queue: &Queue,
+    helpers: &mut TextureHelpers,
+    texture_cache: &mut TextureCache,
+) -> Result<Arc<Texture>, ResourceError> {
+    let Some(statement) = roughness_statement.or(metallic_statement) else {
+        return Ok(fallback.clone());
+    };
+    // Both maps end up in one texture, sampled as the first one says, and cached under both names.
+    let (file_name, options) = texture_statement(statement, true);
+    let cache_key = [roughness_statement, metallic_statement]
+        .map(|statement| statement.map_or("", |statement| texture_statement(statement, true).0))
+        .join("+");
+    if let Some(texture) = texture_cache.get(&cache_key, &options) {
+        return Ok(texture);
+    }
+
+    let mut maps = Vec::new();
+    for statement in [roughness_statement, metallic_statement] {
+        let map = match statement {
+            Some(statement) => {
+                let (file_name, _) = texture_statement(statement, true);
+                let map = decode_image(&load_binary(file_name).await?, file_name)?;
+                Some(map.to_luma8())
+            }
+            None => None,
+        };
+        maps.push(map);
+    }
+    let (width, height) = maps.iter().flatten().next().unwrap().dimensions();
+    let channel = |map: &Option<image::GrayImage>| match map {
+        Some(map) if map.dimensions() == (width, height) => map.clone(),
+        Some(map) => {
+            image::imageops::resize(map, width, height, image::imageops::FilterType::Triangle)
+        }
+        None => image::GrayImage::from_pixel(width, height, image::Luma([255])),
+    };
+    let (roughness, metallic) = (channel(&maps[0]), channel(&maps[1]));
+
+    let packed = image::RgbaImage::from_fn(width, height, |x, y| {
+        image::Rgba([
+            255,
+            roughness.get_pixel(x, y)[0],
+            metallic.get_pixel(x, y)[0],
+            255,
+        ])
+    });
+    let texture = Texture::from_image(
+        device,
+        queue,
+        helpers,
+        &image::DynamicImage::ImageRgba8(packed),
+        Some(file_name),
+        &options,
+    );
+    Ok(texture_cache.insert(&cache_key, &options, texture))
+}
+
+/// Fills in per-vertex tangents and bitangents from the triangle positions and texture coordinates,
+/// averaging over shared triangles and orthogonalizing against the vertex normal.
+fn compute_tangents(vertices: &mut [object::ModelVertex], indices: &[u32]) {
+    let mut tangents = vec![cgmath::Vector3::zero(); vertices.len()];
+    let mut bitangents = vec![cgmath::Vector3::zero(); vertices.len()];
+
+    for c in indices.chunks_exact(3) {
+        let [v0, v1, v2] = [c[0] as usize, c[1] as usize, c[2] as usize];
+        let pos0: cgmath::Vector3<f32> = vertices[v0].position.into();
+        let pos1: cgmath::Vector3<f32> = vertices[v1].position.into();
+        let pos2: cgmath::Vector3<f32> = vertices[v2].position.into();
+        let uv0: cgmath::Vector2<f32> = vertices[v0].tex_coords.into();
+        let uv1: cgmath::Vector2<f32> = vertices[v1].tex_coords.into();
+        let uv2: cgmath::Vector2<f32> = vertices[v2].tex_coords.into();
+
+        let delta_pos1 = pos1 - pos0;
+        let delta_pos2 = pos2 - pos0;
+        let delta_uv1 = uv1 - uv0;
+        let delta_uv2 = uv2 - uv0;
+
+        let det = delta_uv1.x * delta_uv2.y - delta_uv1.y * delta_uv2.x;
+        if det.abs() < f32::EPSILON {
+            continue;
+        }
+        let r = 1.0 / det;
+        let tangent = (delta_pos1 * delta_uv2.y - delta_pos2 * delta_uv1.y) * r;
+        // Texture v grows downwards in wgpu, so flip the bitangent to keep normal maps right-handed.
+        let bitangent = (delta_pos2 * delta_uv1.x - delta_pos1 * delta_uv2.x) * -r;
+
+        for v in [v0, v1, v2] {
+            tangents[v] += tangent;
+            bitangents[v] += bitangent;
+        }
+    }
+
+    for (i, vertex) in vertices.iter_mut().enumerate() {
+        let normal: cgmath::Vector3<f32> = vertex.normal.into();
+        let mut tangent = tangents[i] - normal * normal.dot(tangents[i]);
+        if tangent.magnitude2() < f32::EPSILON {
+            // No usable texture coordinates, pick any direction perpendicular to the normal.
+            let axis = if normal.x.abs() < 0.9 {
+                cgmath::Vector3::unit_x()
+            } else {
+                cgmath::Vector3::unit_y()
+            };
+            tangent = normal.cross(axis);
+        }
+        let tangent = tangent.normalize();
+        let mut bitangent = normal.cross(tangent);
+        if bitangent.dot(bitangents[i]) < 0.0 {
+            bitangent = -bitangent;
+        }
+
+        vertex.tangent = tangent.into();
+        vertex.bitangent = bitangent.into();
+    }
+}
+
+/// Loads a texture with its mip chain generated as `options` say. With `Mipmaps::Files` the chain
+/// is read from `name_mip1.ext`, `name_mip2.ext`, ... next to it when the first of those exists.
+/// KTX2 and DDS files carry their own mip chain and keep their compressed format.
+pub async fn load_texture(
+    file_name: &str,
+    options: &TextureOptions,
+    device: &Device,
+    queue: &Queue,
+    helpers: &mut TextureHelpers,
+) -> Result<Texture, ResourceError> {
+    let data = load_binary(file_name).await?;
+    if CompressedImage::is_container(&data) {
+        let image = CompressedImage::parse(file_name, &data, options.linear)?;
+        return Texture::from_compressed(device, queue, helpers, &image, Some(file_name), options);
+    }
+    let mut levels = Vec::new();
+    if options.mipmaps == Mipmaps::Files {
+        loop {
+            let level_name = mip_file_name(file_name, levels.len() + 1);
+            let Ok(level) = load_binary(&level_name).await else {
+                break;
+            };
+            levels.push((level_name, level));
+        }
+    }
+    if levels.is_empty() {
+        return Texture::from_bytes(device, queue, helpers, &data, Some(file_name), options);
+    }
+
+    let levels = std::iter::once((file_name.to_string(), data))
+        .chain(levels)
+        .map(|(level_name, level)| decode_image(&level, &level_name))
+        .collect::<Result<Vec<_>, _>>()?;
+    Texture::from_mip_images(device, queue, helpers, &levels, Some(file_name), options)
+}
+
+// `textures/brick.png` level 2 is `textures/brick_mip2.png`.
+fn mip_file_name(file_name: &str, mip_level: usize) -> String {
+    let path = std::path::Path::new(file_name);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(extension) => format!("{stem}_mip{mip_level}.{}", extension.to_string_lossy()),
+        None => format!("{stem}_mip{mip_level}"),
+    };
+    path.with_file_name(name).to_string_lossy().into_owned()
+}
+
+/// Loads a cube map from six face images given in +X, -X, +Y, -Y, +Z, -Z order, or from a single
+/// image of the faces laid out in a cross.
+pub async fn load_cube_texture(
+    file_names: &[&str],
+    device: &Device,
+    queue: &Queue,
+    helpers: &mut TextureHelpers,
+) -> Result<Texture, ResourceError> {
+    let mut faces = Vec::with_capacity(file_names.len());
+    for file_name in file_names {
+        faces.push(decode_image(&load_binary(file_name).await?, file_name)?);
+    }
+    let options = TextureOptions::default();
+    let label = Some(file_names[0]);
+
+    match <[_; 6]>::try_from(faces) {
+        Ok(faces) => Texture::from_cube_faces(device, queue, helpers, &faces, label, &options),
+        Err(faces) if faces.len() == 1 => Texture::from_cube_cross(
+            device, queue, helpers, &faces[0], label, &options,
+        )
+        .ok_or_else(|| ResourceError::UnsupportedFormat {
+            path: file_names[0].to_string(),
+            format: "cube map images that aren't a cross of faces".to_string(),
+        }),
+        Err(faces) => Err(ResourceError::UnsupportedFormat {
+            path: file_names[0].to_string(),
+            format: format!("cube maps made of {} images", faces.len()),
+        }),
+    }
+}
+
+/// Loads an equirectangular Radiance HDR or OpenEXR image and precomputes its image based
+/// lighting.
+pub async fn load_environment(
+    file_name: &str,
+    device: &Device,
+    queue: &Queue,
+) -> Result<Environment, ResourceError> {
+    let image = decode_image(&load_binary(file_name).await?, file_name)?.to_rgba32f();
+    let pixels = image
+        .pixels()
+        .map(|pixel| [pixel[0], pixel[1], pixel[2], 1.0])
+        .collect::<Vec<_>>();
+
+    Ok(Environment::from_equirectangular(
+        device,
+        queue,
+        image.width(),
+        image.height(),
+        &pixels,
+    ))
 }
 
-pub async fn load_string(file_name: &str) -> anyhow::Result<String> {
+pub async fn load_string(file_name: &str) -> Result<String, ResourceError> {
     let path = std::path::Path::new(env!("OUT_DIR"))
         .join("res")
         .join(file_name);
 
-    let txt = std::fs::read_to_string(path)?;
+    let txt = std::fs::read_to_string(path).map_err(|source| missing_file(file_name, source))?;
 
     Ok(txt)
 }
 
-pub async fn load_binary(file_name: &str) -> anyhow::Result<Vec<u8>> {
+pub async fn load_binary(file_name: &str) -> Result<Vec<u8>, ResourceError> {
     let path = std::path::Path::new(env!("OUT_DIR"))
         .join("res")
         .join(file_name);
-    let data = std::fs::read(path)?;
+    let data = std::fs::read(path).map_err(|source| missing_file(file_name, source))?;
     Ok(data)
 }
+
+fn missing_file(file_name: &str, source: std::io::Error) -> ResourceError {
+    ResourceError::MissingFile {
+        path: file_name.to_string(),
+        source,
+    }
+}
@@ -1,90 +1,32 @@
-
-struct Light {
-    position: vec3<f32>,
-    color: vec3<f32>,
-}
-@group(2) @binding(0)
-var<uniform> light: Light;
-
-
-struct CameraUniform {
-    view_proj: mat4x4<f32>,
-};
-@group(1) @binding(0) // 1.
-var<uniform> camera: CameraUniform;
-
-// Vertex shader
-struct InstanceInput {
-    @location(5) model_matrix_0: vec4<f32>,
-    @location(6) model_matrix_1: vec4<f32>,
-    @location(7) model_matrix_2: vec4<f32>,
-    @location(8) model_matrix_3: vec4<f32>,
-    @location(9) normal_matrix_0: vec3<f32>,
-    @location(10) normal_matrix_1: vec3<f32>,
-    @location(11) normal_matrix_2: vec3<f32>,
-};
-
-struct VertexInput {
-    @location(0) position: vec3<f32>,
-    @location(1) tex_coords: vec2<f32>,
-    @location(2) normal: vec3<f32>
-}
-
-struct VertexOutput {
-    @builtin(position) clip_position: vec4<f32>,
-    @location(0) tex_coords: vec2<f32>,
-    @location(1) world_normal: vec3<f32>,
-    @location(2) world_position: vec3<f32>,
-}
-@vertex
-fn vs_main(
-    model: VertexInput,
-    instance: InstanceInput,
-) -> VertexOutput {
-
-    let model_matrix = mat4x4<f32>(
-        instance.model_matrix_0,
-        instance.model_matrix_1,
-        instance.model_matrix_2,
-        instance.model_matrix_3,
-    );
-
-    let normal_matrix = mat3x3<f32>(
-        instance.normal_matrix_0,
-        instance.normal_matrix_1,
-        instance.normal_matrix_2,
-    );
-
-    var out: VertexOutput;
-    out.tex_coords = model.tex_coords;
-    out.world_normal = normal_matrix * model.normal;
-    var world_position: vec4<f32> = model_matrix * vec4<f32>(model.position, 1.0);
-    out.world_position = world_position.xyz;
-    out.clip_position = camera.view_proj * world_position;
-    return out;
-}
-
 // Fragment shader
 
-@group(0) @binding(0)
-var t_diffuse: texture_2d<f32>;
+@fragment
+fn fs_main(in: VertexOutput) -> ForwardOutput {
+    let sample = sample_material(in);
+    let object_color = sample.base_color;
+    let world_normal = sample.normal;
+    let geometric_normal = normalize(in.world_normal);
 
-@group(0)@binding(1)
-var s_diffuse: sampler;
+    var result = ambient_diffuse(world_normal) * object_color.xyz * ambient_occlusion(in.clip_position);
 
-@fragment
-fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    let object_color: vec4<f32> = textureSample(t_diffuse, s_diffuse, in.tex_coords);
+    let cluster = cluster_index(in.clip_position, in.world_position);
+    for (var i = 0u; i < cluster_light_count(cluster); i++) {
+        let light = cluster_light(cluster, i);
 
-    let ambient_strength = 0.1;
-    let ambient_color = light.color * ambient_strength;
+        let to_light = light.position - in.world_position;
+        let light_dir = normalize(to_light);
+        let attenuation = range_attenuation(length(to_light), light.range);
 
-    let light_dir = normalize(light.position - in.world_position);
+        let diffuse_strength = max(dot(world_normal, light_dir), 0.0);
+        let diffuse_color = light.color * diffuse_strength * attenuation;
 
-    let diffuse_strength = max(dot(in.world_normal, light_dir), 0.0);
-    let diffuse_color = light.color * diffuse_strength;
+        let shadow = point_shadow(light, in.world_position, geometric_normal);
 
-    let result = (ambient_color + diffuse_color) * object_color.xyz;
+        result += diffuse_color * shadow * object_color.xyz;
+    }
 
-    return vec4<f32>(result, object_color.a);
+    var out: ForwardOutput;
+    out.color = vec4<f32>(result + sample.emissive, object_color.a);
+    out.velocity = motion_vector(in.current_position, in.previous_position);
+    return out;
 }
@@ -0,0 +1,260 @@
+use std::ops::Range;
+
+use wgpu::util::DeviceExt;
+use wgpu::{BindGroup, Device, Queue};
+
+use crate::camera::OPENGL_TO_WGPU_MATRIX;
+use crate::instance::InstanceRaw;
+use crate::light::{LightUniform, MAX_SHADOW_CASTERS};
+use crate::object::{DrawShadow, Model, ModelVertex, Vertex};
+
+const FACE_UNIFORM_STRIDE: wgpu::BufferAddress = 256;
+const NEAR_PLANE: f32 = 0.05;
+
+// Look direction and up vector for each cube face, in the +X, -X, +Y, -Y, +Z, -Z layer order.
+const CUBE_FACES: [([f32; 3], [f32; 3]); 6] = [
+    ([1.0, 0.0, 0.0], [0.0, -1.0, 0.0]),
+    ([-1.0, 0.0, 0.0], [0.0, -1.0, 0.0]),
+    ([0.0, 1.0, 0.0], [0.0, 0.0, 1.0]),
+    ([0.0, -1.0, 0.0], [0.0, 0.0, -1.0]),
+    ([0.0, 0.0, 1.0], [0.0, -1.0, 0.0]),
+    ([0.0, 0.0, -1.0], [0.0, -1.0, 0.0]),
+];
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct ShadowFaceUniform {
+    view_proj: [[f32; 4]; 4],
+    light_position: [f32; 3],
+    far: f32,
+}
+
+/// Omnidirectional shadow maps for point lights, one cube (six layers) per shadow casting light.
+///
+/// Each face stores the linear distance to the light divided by the light range, so the lighting
+/// shader can compare against `length(fragment - light) / range` with any cube direction.
+pub struct PointShadowMaps {
+    pub view: wgpu::TextureView,
+    pub sampler: wgpu::Sampler,
+    face_views: Vec<wgpu::TextureView>,
+    face_buffer: wgpu::Buffer,
+    face_bind_group: BindGroup,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl PointShadowMaps {
+    pub const SIZE: u32 = 512;
+    pub const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+    pub fn new(device: &Device) -> PointShadowMaps {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("point_shadow_texture"),
+            size: wgpu::Extent3d {
+                width: Self::SIZE,
+                height: Self::SIZE,
+                depth_or_array_layers: (MAX_SHADOW_CASTERS * 6) as u32,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: Self::FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("point_shadow_view"),
+            dimension: Some(wgpu::TextureViewDimension::CubeArray),
+            ..Default::default()
+        });
+        let face_views = (0..MAX_SHADOW_CASTERS as u32 * 6)
+            .map(|layer| {
+                texture.create_view(&wgpu::TextureViewDescriptor {
+                    label: Some("point_shadow_face_view"),
+                    dimension: Some(wgpu::TextureViewDimension::D2),
+                    base_array_layer: layer,
+                    array_layer_count: Some(1),
+                    ..Default::default()
+                })
+            })
+            .collect();
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("point_shadow_sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            compare: Some(wgpu::CompareFunction::LessEqual),
+            ..Default::default()
+        });
+
+        let face_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Shadow Face Buffer"),
+            contents: &vec![0; FACE_UNIFORM_STRIDE as usize * MAX_SHADOW_CASTERS * 6],
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let face_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: true,
+                        min_binding_size: wgpu::BufferSize::new(
+                            std::mem::size_of::<ShadowFaceUniform>() as u64,
+                        ),
+                    },
+                    count: None,
+                }],
+                label: Some("shadow_face_bind_group_layout"),
+            });
+        let face_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &face_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer: &face_buffer,
+                    offset: 0,
+                    size: wgpu::BufferSize::new(std::mem::size_of::<ShadowFaceUniform>() as u64),
+                }),
+            }],
+            label: Some("shadow_face_bind_group"),
+        });
+
+        let pipeline = create_shadow_pipeline(device, &face_bind_group_layout);
+
+        PointShadowMaps {
+            view,
+            sampler,
+            face_views,
+            face_buffer,
+            face_bind_group,
+            pipeline,
+        }
+    }
+
+    /// Uploads the six face matrices of every shadow casting light.
+    pub fn update(&self, queue: &Queue, lights: &[LightUniform]) {
+        for light in lights.iter().filter(|light| light.casts_shadow()) {
+            for (face, uniform) in face_uniforms(light).iter().enumerate() {
+                let layer = light.shadow_index as usize * 6 + face;
+                queue.write_buffer(
+                    &self.face_buffer,
+                    layer as wgpu::BufferAddress * FACE_UNIFORM_STRIDE,
+                    bytemuck::cast_slice(&[*uniform]),
+                );
+            }
+        }
+    }
+
+    pub fn render(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        lights: &[LightUniform],
+        model: &Model,
+        instance_buffer: &wgpu::Buffer,
+        instances: Range<u32>,
+    ) {
+        for light in lights.iter().filter(|light| light.casts_shadow()) {
+            for face in 0..6 {
+                let layer = light.shadow_index as usize * 6 + face;
+                let mut shadow_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("Point Shadow Pass"),
+                    color_attachments: &[],
+                    depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                        view: &self.face_views[layer],
+                        depth_ops: Some(wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(1.0),
+                            store: true,
+                        }),
+                        stencil_ops: None,
+                    }),
+                });
+                shadow_pass.set_pipeline(&self.pipeline);
+                shadow_pass.set_vertex_buffer(1, instance_buffer.slice(..));
+                shadow_pass.draw_shadow_model_instanced(
+                    model,
+                    instances.clone(),
+                    &self.face_bind_group,
+                    (layer as wgpu::BufferAddress * FACE_UNIFORM_STRIDE) as u32,
+                );
+            }
+        }
+    }
+}
+
+fn face_uniforms(light: &LightUniform) -> [ShadowFaceUniform; 6] {
+    let position = cgmath::Point3::from(light.position);
+    // Cube faces are addressed top-down, so the GL style face basis is flipped vertically.
+    let flip_y = cgmath::Matrix4::from_nonuniform_scale(1.0, -1.0, 1.0);
+    let proj = flip_y
+        * OPENGL_TO_WGPU_MATRIX
+        * cgmath::perspective(cgmath::Deg(90.0), 1.0, NEAR_PLANE, light.range);
+
+    CUBE_FACES.map(|(direction, up)| {
+        let view = cgmath::Matrix4::look_at_rh(
+            position,
+            position + cgmath::Vector3::from(direction),
+            up.into(),
+        );
+        ShadowFaceUniform {
+            view_proj: (proj * view).into(),
+            light_position: light.position,
+            far: light.range,
+        }
+    })
+}
+
+fn create_shadow_pipeline(
+    device: &Device,
+    face_bind_group_layout: &wgpu::BindGroupLayout,
+) -> wgpu::RenderPipeline {
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Shadow Pipeline Layout"),
+        bind_group_layouts: &[face_bind_group_layout],
+        push_constant_ranges: &[],
+    });
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Shadow Shader"),
+        source: wgpu::ShaderSource::Wgsl(include_str!("shadow.wgsl").into()),
+    });
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Shadow Pipeline"),
+        layout: Some(&layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: "vs_main",
+            buffers: &[ModelVertex::desc(), InstanceRaw::desc()],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: "fs_main",
+            targets: &[],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            // The flipped face projection mirrors the winding order, so draw both sides.
+            cull_mode: None,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            unclipped_depth: false,
+            conservative: false,
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: PointShadowMaps::FORMAT,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::LessEqual,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+    })
+}
@@ -0,0 +1,195 @@
+use wgpu::{BindGroup, BindGroupLayout, Device};
+
+use crate::texture::Texture;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SkyMode {
+    /// The cube map projected from the equirectangular environment used for lighting.
+    Environment,
+    /// A cube map loaded from six face images.
+    Cubemap,
+    Gradient,
+}
+
+impl SkyMode {
+    pub fn next(self) -> SkyMode {
+        match self {
+            SkyMode::Environment => SkyMode::Cubemap,
+            SkyMode::Cubemap => SkyMode::Gradient,
+            SkyMode::Gradient => SkyMode::Environment,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct GradientSky {
+    zenith_color: [f32; 3],
+    exponent: f32,
+    horizon_color: [f32; 3],
+    _padding: f32,
+    ground_color: [f32; 3],
+    _padding2: f32,
+}
+
+impl GradientSky {
+    /// `exponent` shapes the blend away from the horizon, lower values give a thinner horizon band.
+    pub fn new(
+        zenith_color: [f32; 3],
+        horizon_color: [f32; 3],
+        ground_color: [f32; 3],
+        exponent: f32,
+    ) -> Self {
+        Self {
+            zenith_color,
+            exponent,
+            horizon_color,
+            _padding: 0.0,
+            ground_color,
+            _padding2: 0.0,
+        }
+    }
+}
+
+impl Default for GradientSky {
+    fn default() -> Self {
+        Self::new([0.15, 0.3, 0.75], [0.75, 0.8, 0.9], [0.2, 0.17, 0.14], 0.5)
+    }
+}
+
+/// Draws the sky behind the scene. Run it after the opaque geometry so only the pixels the depth
+/// buffer left at the far plane are shaded.
+pub struct SkyboxPass {
+    cubemap_pipeline: wgpu::RenderPipeline,
+    gradient_pipeline: wgpu::RenderPipeline,
+}
+
+impl SkyboxPass {
+    pub fn new(
+        device: &Device,
+        color_formats: &[wgpu::TextureFormat],
+        camera_bind_group_layout: &BindGroupLayout,
+        skybox_bind_group_layout: &BindGroupLayout,
+        gradient_sky_bind_group_layout: &BindGroupLayout,
+    ) -> SkyboxPass {
+        let cubemap_pipeline = create_skybox_pipeline(
+            device,
+            color_formats,
+            &[camera_bind_group_layout, skybox_bind_group_layout],
+            wgpu::ShaderModuleDescriptor {
+                label: Some("Skybox Cubemap Shader"),
+                source: wgpu::ShaderSource::Wgsl(
+                    concat!(
+                        include_str!("skybox.wgsl"),
+                        include_str!("skybox_cubemap.wgsl")
+                    )
+                    .into(),
+                ),
+            },
+        );
+        let gradient_pipeline = create_skybox_pipeline(
+            device,
+            color_formats,
+            &[camera_bind_group_layout, gradient_sky_bind_group_layout],
+            wgpu::ShaderModuleDescriptor {
+                label: Some("Skybox Gradient Shader"),
+                source: wgpu::ShaderSource::Wgsl(
+                    concat!(
+                        include_str!("skybox.wgsl"),
+                        include_str!("skybox_gradient.wgsl")
+                    )
+                    .into(),
+                ),
+            },
+        );
+
+        SkyboxPass {
+            cubemap_pipeline,
+            gradient_pipeline,
+        }
+    }
+
+    /// Draws a cube map bound with `create_texture_bind_group`.
+    pub fn draw_cubemap<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        camera_bind_group: &'a BindGroup,
+        skybox_bind_group: &'a BindGroup,
+    ) {
+        render_pass.set_pipeline(&self.cubemap_pipeline);
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_bind_group(1, skybox_bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+
+    /// Draws the procedural sky bound with `create_gradient_sky_bind_group`.
+    pub fn draw_gradient<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        camera_bind_group: &'a BindGroup,
+        gradient_sky_bind_group: &'a BindGroup,
+    ) {
+        render_pass.set_pipeline(&self.gradient_pipeline);
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_bind_group(1, gradient_sky_bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+fn create_skybox_pipeline(
+    device: &Device,
+    color_formats: &[wgpu::TextureFormat],
+    bind_group_layouts: &[&BindGroupLayout],
+    shader: wgpu::ShaderModuleDescriptor,
+) -> wgpu::RenderPipeline {
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Skybox Pipeline Layout"),
+        bind_group_layouts,
+        push_constant_ranges: &[],
+    });
+    let shader = device.create_shader_module(shader);
+    let targets = color_formats
+        .iter()
+        .map(|&format| {
+            Some(wgpu::ColorTargetState {
+                format,
+                blend: Some(wgpu::BlendState::REPLACE),
+                write_mask: wgpu::ColorWrites::ALL,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Skybox Pipeline"),
+        layout: Some(&layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: "vs_main",
+            buffers: &[],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: "fs_main",
+            targets: &targets,
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: None,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            unclipped_depth: false,
+            conservative: false,
+        },
+        // Test against the scene but never write, the sky sits exactly on the cleared far plane.
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: Texture::DEPTH_FORMAT,
+            depth_write_enabled: false,
+            depth_compare: wgpu::CompareFunction::LessEqual,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+    })
+}
@@ -0,0 +1,49 @@
+// skybox.wgsl
+// Fullscreen triangle at the far plane, shared by the skybox fragment stages.
+
+struct Camera {
+    view_proj: mat4x4<f32>,
+    view_position: vec4<f32>,
+    inv_view_proj: mat4x4<f32>,
+    unjittered_view_proj: mat4x4<f32>,
+    prev_view_proj: mat4x4<f32>,
+}
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) ndc: vec2<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    let ndc = uv * 2.0 - 1.0;
+
+    var out: VertexOutput;
+    // Depth 1.0 keeps the sky behind everything already in the depth buffer.
+    out.clip_position = vec4<f32>(ndc, 1.0, 1.0);
+    out.ndc = ndc;
+    return out;
+}
+
+// World space direction of the view ray through a point on the screen.
+fn view_direction(ndc: vec2<f32>) -> vec3<f32> {
+    let world = camera.inv_view_proj * vec4<f32>(ndc, 1.0, 1.0);
+    return normalize(world.xyz / world.w - camera.view_position.xyz);
+}
+
+// Targets of the skybox pipelines, matching the forward ones.
+struct SkyOutput {
+    @location(0) color: vec4<f32>,
+    @location(1) velocity: vec2<f32>,
+}
+
+// The sky is infinitely far away, so only camera rotation moves it: directions are projected with
+// w = 0, which drops the translation.
+fn sky_velocity(direction: vec3<f32>) -> vec2<f32> {
+    let current = camera.unjittered_view_proj * vec4<f32>(direction, 0.0);
+    let previous = camera.prev_view_proj * vec4<f32>(direction, 0.0);
+    return (current.xy / current.w - previous.xy / previous.w) * vec2<f32>(0.5, -0.5);
+}
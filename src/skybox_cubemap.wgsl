@@ -0,0 +1,15 @@
+// skybox_cubemap.wgsl
+
+@group(1) @binding(0)
+var t_sky: texture_cube<f32>;
+@group(1) @binding(1)
+var s_sky: sampler;
+
+@fragment
+fn fs_main(in: VertexOutput) -> SkyOutput {
+    let direction = view_direction(in.ndc);
+    var out: SkyOutput;
+    out.color = vec4<f32>(textureSample(t_sky, s_sky, direction).rgb, 1.0);
+    out.velocity = sky_velocity(direction);
+    return out;
+}
@@ -0,0 +1,26 @@
+// skybox_gradient.wgsl
+// Procedural sky blending from the horizon color up to the zenith and down to the ground.
+
+struct GradientSky {
+    zenith_color: vec3<f32>,
+    exponent: f32,
+    horizon_color: vec3<f32>,
+    ground_color: vec3<f32>,
+}
+@group(1) @binding(0)
+var<uniform> sky: GradientSky;
+
+@fragment
+fn fs_main(in: VertexOutput) -> SkyOutput {
+    let direction = view_direction(in.ndc);
+    let t = pow(abs(direction.y), sky.exponent);
+    let color = select(
+        mix(sky.horizon_color, sky.ground_color, t),
+        mix(sky.horizon_color, sky.zenith_color, t),
+        direction.y >= 0.0,
+    );
+    var out: SkyOutput;
+    out.color = vec4<f32>(color, 1.0);
+    out.velocity = sky_velocity(direction);
+    return out;
+}
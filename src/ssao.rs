@@ -0,0 +1,355 @@
+use cgmath::{InnerSpace, SquareMatrix};
+use wgpu::util::DeviceExt;
+use wgpu::{BindGroup, BindGroupLayout, Device, Queue};
+
+use crate::bind_groups::{
+    create_ssao_bind_group, create_ssao_bind_group_layout, create_ssao_blur_bind_group,
+    create_ssao_blur_bind_group_layout,
+};
+use crate::camera::Camera;
+use crate::graphics_context::create_render_pipeline;
+use crate::instance::InstanceRaw;
+use crate::object;
+use crate::object::Vertex;
+use crate::texture::{DepthTexture, Texture};
+
+/// Must match `KERNEL_SIZE` in `ssao.wgsl`.
+const KERNEL_SIZE: usize = 32;
+/// Side of the tiled rotation pattern, must match `NOISE_SIZE` in `ssao.wgsl`.
+const NOISE_SIZE: usize = 4;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct SsaoUniform {
+    proj: [[f32; 4]; 4],
+    inv_proj: [[f32; 4]; 4],
+    kernel: [[f32; 4]; KERNEL_SIZE],
+    noise: [[f32; 4]; NOISE_SIZE * NOISE_SIZE],
+    radius: f32,
+    bias: f32,
+    power: f32,
+    _padding: f32,
+}
+
+/// Screen-space ambient occlusion computed from the depth buffer alone, so it serves both render
+/// paths. The forward path fills depth with `begin_depth_prepass` first, the deferred path reuses
+/// the depth written by its geometry pass.
+///
+/// `occlusion` is bound with the environment maps and scales the ambient light of both shading
+/// models.
+pub struct SsaoPass {
+    uniform: SsaoUniform,
+    uniform_buffer: wgpu::Buffer,
+    prepass_pipeline: wgpu::RenderPipeline,
+    occlusion_pipeline: wgpu::RenderPipeline,
+    blur_pipeline: wgpu::RenderPipeline,
+    ssao_bind_group_layout: BindGroupLayout,
+    blur_bind_group_layout: BindGroupLayout,
+    raw_occlusion: wgpu::TextureView,
+    pub occlusion: wgpu::TextureView,
+    ssao_bind_group: BindGroup,
+    blur_bind_group: BindGroup,
+}
+
+impl SsaoPass {
+    pub const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R8Unorm;
+
+    /// `prepass_bind_group_layouts` are the forward material, camera and light layouts, the depth
+    /// prepass draws through `DrawModel` like the other scene passes.
+    pub fn new(
+        device: &Device,
+        config: &wgpu::SurfaceConfiguration,
+        depth_texture: &DepthTexture,
+        prepass_bind_group_layouts: &[&BindGroupLayout],
+    ) -> SsaoPass {
+        let mut seed = 0x9e37_79b9;
+        let uniform = SsaoUniform {
+            proj: cgmath::Matrix4::identity().into(),
+            inv_proj: cgmath::Matrix4::identity().into(),
+            kernel: hemisphere_kernel(&mut seed),
+            noise: rotation_noise(&mut seed),
+            radius: 0.5,
+            bias: 0.025,
+            power: 2.0,
+            _padding: 0.0,
+        };
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("SSAO Buffer"),
+            contents: bytemuck::cast_slice(&[uniform]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let prepass_pipeline = create_prepass_pipeline(device, prepass_bind_group_layouts);
+
+        let ssao_bind_group_layout = create_ssao_bind_group_layout(device);
+        let occlusion_pipeline = create_fullscreen_pipeline(
+            device,
+            &ssao_bind_group_layout,
+            wgpu::ShaderModuleDescriptor {
+                label: Some("SSAO Shader"),
+                source: wgpu::ShaderSource::Wgsl(
+                    concat!(include_str!("fullscreen.wgsl"), include_str!("ssao.wgsl")).into(),
+                ),
+            },
+        );
+        let blur_bind_group_layout = create_ssao_blur_bind_group_layout(device);
+        let blur_pipeline = create_fullscreen_pipeline(
+            device,
+            &blur_bind_group_layout,
+            wgpu::ShaderModuleDescriptor {
+                label: Some("SSAO Blur Shader"),
+                source: wgpu::ShaderSource::Wgsl(
+                    concat!(
+                        include_str!("fullscreen.wgsl"),
+                        include_str!("ssao_blur.wgsl")
+                    )
+                    .into(),
+                ),
+            },
+        );
+
+        let raw_occlusion = create_occlusion_target(device, config, "ssao_raw_occlusion");
+        let occlusion = create_occlusion_target(device, config, "ssao_occlusion");
+        let ssao_bind_group = create_ssao_bind_group(
+            device,
+            &depth_texture.depth_view,
+            &uniform_buffer,
+            &ssao_bind_group_layout,
+        );
+        let blur_bind_group =
+            create_ssao_blur_bind_group(device, &raw_occlusion, &blur_bind_group_layout);
+
+        SsaoPass {
+            uniform,
+            uniform_buffer,
+            prepass_pipeline,
+            occlusion_pipeline,
+            blur_pipeline,
+            ssao_bind_group_layout,
+            blur_bind_group_layout,
+            raw_occlusion,
+            occlusion,
+            ssao_bind_group,
+            blur_bind_group,
+        }
+    }
+
+    /// Recreates the occlusion targets to match the surface, call after recreating the depth
+    /// texture. `occlusion` is replaced, so bind groups holding it must be recreated too.
+    pub fn resize(
+        &mut self,
+        device: &Device,
+        config: &wgpu::SurfaceConfiguration,
+        depth_texture: &DepthTexture,
+    ) {
+        self.raw_occlusion = create_occlusion_target(device, config, "ssao_raw_occlusion");
+        self.occlusion = create_occlusion_target(device, config, "ssao_occlusion");
+        self.ssao_bind_group = create_ssao_bind_group(
+            device,
+            &depth_texture.depth_view,
+            &self.uniform_buffer,
+            &self.ssao_bind_group_layout,
+        );
+        self.blur_bind_group =
+            create_ssao_blur_bind_group(device, &self.raw_occlusion, &self.blur_bind_group_layout);
+    }
+
+    /// Uploads the projection positions are reconstructed with.
+    pub fn update(&mut self, queue: &Queue, camera: &Camera) {
+        let proj = camera.projection_matrix();
+        self.uniform.proj = proj.into();
+        self.uniform.inv_proj = proj.invert().unwrap().into();
+        queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[self.uniform]),
+        );
+    }
+
+    /// Starts a depth only pass over `depth_view`, clearing it. Draw models into it with
+    /// `DrawModel`.
+    pub fn begin_depth_prepass<'a>(
+        &'a self,
+        encoder: &'a mut wgpu::CommandEncoder,
+        depth_view: &'a wgpu::TextureView,
+    ) -> wgpu::RenderPass<'a> {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Depth Prepass"),
+            color_attachments: &[],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: depth_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: true,
+                }),
+                stencil_ops: None,
+            }),
+        });
+        render_pass.set_pipeline(&self.prepass_pipeline);
+        render_pass
+    }
+
+    /// Computes `occlusion` from the depth texture, which must hold this frame's scene.
+    pub fn render(&self, encoder: &mut wgpu::CommandEncoder) {
+        {
+            let mut render_pass =
+                begin_occlusion_pass(encoder, &self.raw_occlusion, "SSAO Pass", None);
+            render_pass.set_pipeline(&self.occlusion_pipeline);
+            render_pass.set_bind_group(0, &self.ssao_bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+
+        let mut render_pass =
+            begin_occlusion_pass(encoder, &self.occlusion, "SSAO Blur Pass", None);
+        render_pass.set_pipeline(&self.blur_pipeline);
+        render_pass.set_bind_group(0, &self.blur_bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+
+    /// Leaves the ambient light unoccluded, use instead of `render` while SSAO is disabled.
+    pub fn clear(&self, encoder: &mut wgpu::CommandEncoder) {
+        begin_occlusion_pass(
+            encoder,
+            &self.occlusion,
+            "SSAO Clear Pass",
+            Some(wgpu::Color::WHITE),
+        );
+    }
+}
+
+// xorshift32, the kernel only needs to be well spread and the same every run.
+fn next_random(state: &mut u32) -> f32 {
+    *state ^= *state << 13;
+    *state ^= *state >> 17;
+    *state ^= *state << 5;
+    (*state >> 8) as f32 / (1 << 24) as f32
+}
+
+// Points inside the +Z hemisphere, scaled so most of them land close to the surface where
+// occlusion matters most.
+fn hemisphere_kernel(seed: &mut u32) -> [[f32; 4]; KERNEL_SIZE] {
+    std::array::from_fn(|i| {
+        let direction = cgmath::Vector3::new(
+            next_random(seed) * 2.0 - 1.0,
+            next_random(seed) * 2.0 - 1.0,
+            next_random(seed),
+        )
+        .normalize();
+        let scale = i as f32 / KERNEL_SIZE as f32;
+        let scale = 0.1 + 0.9 * scale * scale;
+        (direction * next_random(seed) * scale).extend(0.0).into()
+    })
+}
+
+// Random tangents in the view plane, the shader projects them onto each pixel's tangent plane.
+fn rotation_noise(seed: &mut u32) -> [[f32; 4]; NOISE_SIZE * NOISE_SIZE] {
+    std::array::from_fn(|_| {
+        [
+            next_random(seed) * 2.0 - 1.0,
+            next_random(seed) * 2.0 - 1.0,
+            0.0,
+            0.0,
+        ]
+    })
+}
+
+fn create_occlusion_target(
+    device: &Device,
+    config: &wgpu::SurfaceConfiguration,
+    label: &str,
+) -> wgpu::TextureView {
+    device
+        .create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: config.width,
+                height: config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: SsaoPass::FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        })
+        .create_view(&wgpu::TextureViewDescriptor::default())
+}
+
+// Every pixel is written by the fullscreen triangle, so only the clear pass needs to clear.
+fn begin_occlusion_pass<'a>(
+    encoder: &'a mut wgpu::CommandEncoder,
+    target: &'a wgpu::TextureView,
+    label: &str,
+    clear: Option<wgpu::Color>,
+) -> wgpu::RenderPass<'a> {
+    encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some(label),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view: target,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: clear.map_or(wgpu::LoadOp::Load, wgpu::LoadOp::Clear),
+                store: true,
+            },
+        })],
+        depth_stencil_attachment: None,
+    })
+}
+
+fn create_fullscreen_pipeline(
+    device: &Device,
+    bind_group_layout: &BindGroupLayout,
+    shader: wgpu::ShaderModuleDescriptor,
+) -> wgpu::RenderPipeline {
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("SSAO Pipeline Layout"),
+        bind_group_layouts: &[bind_group_layout],
+        push_constant_ranges: &[],
+    });
+    create_render_pipeline(device, &layout, &[SsaoPass::FORMAT], None, &[], shader)
+}
+
+fn create_prepass_pipeline(
+    device: &Device,
+    bind_group_layouts: &[&BindGroupLayout],
+) -> wgpu::RenderPipeline {
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Depth Prepass Pipeline Layout"),
+        bind_group_layouts,
+        push_constant_ranges: &[],
+    });
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Depth Prepass Shader"),
+        source: wgpu::ShaderSource::Wgsl(include_str!("model.wgsl").into()),
+    });
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Depth Prepass Pipeline"),
+        layout: Some(&layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: "vs_main",
+            buffers: &[object::ModelVertex::desc(), InstanceRaw::desc()],
+        },
+        fragment: None,
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: Some(wgpu::Face::Back),
+            polygon_mode: wgpu::PolygonMode::Fill,
+            unclipped_depth: false,
+            conservative: false,
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: Texture::DEPTH_FORMAT,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+    })
+}
@@ -0,0 +1,78 @@
+// ssao.wgsl
+// Screen-space ambient occlusion: rebuilds view-space positions and normals from the depth buffer
+// and counts how much of a hemisphere of samples around each pixel lies behind the scene.
+
+// Must match `KERNEL_SIZE` and `NOISE_SIZE` in `ssao.rs`.
+const KERNEL_SIZE: u32 = 32u;
+const NOISE_SIZE: u32 = 4u;
+
+struct Ssao {
+    proj: mat4x4<f32>,
+    inv_proj: mat4x4<f32>,
+    // Hemisphere offsets around +Z, denser near the origin.
+    kernel: array<vec4<f32>, KERNEL_SIZE>,
+    // Rotations of the kernel around the normal, tiled over the screen.
+    noise: array<vec4<f32>, 16>,
+    radius: f32,
+    bias: f32,
+    power: f32,
+}
+
+@group(0) @binding(0)
+var t_depth: texture_depth_2d;
+@group(0) @binding(1)
+var<uniform> ssao: Ssao;
+
+fn view_position(texel: vec2<i32>) -> vec3<f32> {
+    let size = vec2<i32>(textureDimensions(t_depth));
+    let clamped = clamp(texel, vec2<i32>(0), size - 1);
+    let depth = textureLoad(t_depth, clamped, 0);
+    let uv = (vec2<f32>(clamped) + 0.5) / vec2<f32>(size);
+    let position = ssao.inv_proj * vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, depth, 1.0);
+    return position.xyz / position.w;
+}
+
+// Differences towards the neighbour closest in depth, so silhouettes don't bend the normal.
+fn view_normal(texel: vec2<i32>, position: vec3<f32>) -> vec3<f32> {
+    let left = position - view_position(texel - vec2<i32>(1, 0));
+    let right = view_position(texel + vec2<i32>(1, 0)) - position;
+    let up = position - view_position(texel - vec2<i32>(0, 1));
+    let down = view_position(texel + vec2<i32>(0, 1)) - position;
+    let dx = select(right, left, abs(left.z) < abs(right.z));
+    let dy = select(down, up, abs(up.z) < abs(down.z));
+    return normalize(cross(dy, dx));
+}
+
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let frag_coord = in.position;
+    let texel = vec2<i32>(frag_coord.xy);
+    if (textureLoad(t_depth, texel, 0) >= 1.0) {
+        return vec4<f32>(1.0);
+    }
+
+    let position = view_position(texel);
+    let normal = view_normal(texel, position);
+    let noise = ssao.noise[(u32(texel.y) % NOISE_SIZE) * NOISE_SIZE + u32(texel.x) % NOISE_SIZE].xyz;
+    let tangent = normalize(noise - normal * dot(noise, normal));
+    let tbn = mat3x3<f32>(tangent, cross(normal, tangent), normal);
+    let size = vec2<f32>(textureDimensions(t_depth));
+
+    var occlusion = 0.0;
+    for (var i = 0u; i < KERNEL_SIZE; i++) {
+        let sample_position = position + tbn * ssao.kernel[i].xyz * ssao.radius;
+        let clip = ssao.proj * vec4<f32>(sample_position, 1.0);
+        let ndc = clip.xy / clip.w;
+        let uv = vec2<f32>(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5);
+        if (any(uv < vec2<f32>(0.0)) || any(uv >= vec2<f32>(1.0))) {
+            continue;
+        }
+
+        let scene = view_position(vec2<i32>(uv * size));
+        // Fade out occluders far in front of the sample, they belong to another object.
+        let range_check = smoothstep(0.0, 1.0, ssao.radius / abs(position.z - scene.z));
+        occlusion += select(0.0, range_check, scene.z >= sample_position.z + ssao.bias);
+    }
+
+    return vec4<f32>(pow(1.0 - occlusion / f32(KERNEL_SIZE), ssao.power));
+}
@@ -0,0 +1,22 @@
+// ssao_blur.wgsl
+// Box blur over the noise tile, removing the pattern left by the rotated kernels.
+
+@group(0) @binding(0)
+var t_occlusion: texture_2d<f32>;
+
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let frag_coord = in.position;
+    let texel = vec2<i32>(frag_coord.xy);
+    let size = vec2<i32>(textureDimensions(t_occlusion));
+
+    var occlusion = 0.0;
+    for (var y = -2; y < 2; y++) {
+        for (var x = -2; x < 2; x++) {
+            let offset = clamp(texel + vec2<i32>(x, y), vec2<i32>(0), size - 1);
+            occlusion += textureLoad(t_occlusion, offset, 0).r;
+        }
+    }
+
+    return vec4<f32>(occlusion / 16.0);
+}
@@ -0,0 +1,310 @@
+use cgmath::InnerSpace;
+use wgpu::util::DeviceExt;
+use wgpu::{BindGroup, BindGroupLayout, Device, Queue};
+
+use crate::bind_groups::{create_taa_bind_group, create_taa_bind_group_layout};
+use crate::camera::Camera;
+use crate::graphics_context::create_render_pipeline;
+use crate::hdr::HdrTarget;
+use crate::texture::DepthTexture;
+
+/// Length of the jitter sequence before it repeats.
+const JITTER_SAMPLES: u32 = 8;
+/// Camera movement within a frame beyond which the history is thrown away rather than
+/// reprojected, in world units and as the cosine of the turn angle.
+const CUT_DISTANCE: f32 = 2.0;
+const CUT_COS_ANGLE: f32 = 0.866;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct TaaUniform {
+    current_weight: f32,
+    reset: u32,
+    _padding: [f32; 2],
+}
+
+/// Temporal anti-aliasing: the projection is jittered by a different sub-pixel offset every frame
+/// and each frame is blended into a history reprojected with the motion vectors in `velocity`,
+/// so over a few frames every pixel averages samples from across its area. This resolves the
+/// shimmer of thin specular highlights and minified textures that a single sample per pixel
+/// leaves.
+///
+/// The resolve ping-pongs between two history targets and copies its result back into the HDR
+/// target, so the passes after it read the anti-aliased frame unchanged.
+pub struct Taa {
+    /// Screen space motion of each pixel since the previous frame, written by the scene
+    /// pipelines alongside their color.
+    pub velocity: wgpu::TextureView,
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: BindGroupLayout,
+    sampler: wgpu::Sampler,
+    uniform_buffer: wgpu::Buffer,
+    history: [wgpu::Texture; 2],
+    history_views: [wgpu::TextureView; 2],
+    bind_groups: [BindGroup; 2],
+    size: [u32; 2],
+    current: usize,
+    frame: u32,
+    reset: bool,
+    last_eye: cgmath::Point3<f32>,
+    last_direction: cgmath::Vector3<f32>,
+}
+
+impl Taa {
+    pub const VELOCITY_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rg16Float;
+    /// Weight of the newest frame, lower values smooth more but take longer to converge.
+    const CURRENT_WEIGHT: f32 = 0.1;
+
+    pub fn new(
+        device: &Device,
+        config: &wgpu::SurfaceConfiguration,
+        hdr: &HdrTarget,
+        depth_texture: &DepthTexture,
+    ) -> Taa {
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("TAA Buffer"),
+            contents: bytemuck::cast_slice(&[TaaUniform {
+                current_weight: Self::CURRENT_WEIGHT,
+                reset: 1,
+                _padding: [0.0; 2],
+            }]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("taa_sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let bind_group_layout = create_taa_bind_group_layout(device);
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("TAA Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = create_render_pipeline(
+            device,
+            &layout,
+            &[HdrTarget::FORMAT],
+            None,
+            &[],
+            wgpu::ShaderModuleDescriptor {
+                label: Some("TAA Shader"),
+                source: wgpu::ShaderSource::Wgsl(
+                    concat!(include_str!("fullscreen.wgsl"), include_str!("taa.wgsl")).into(),
+                ),
+            },
+        );
+
+        let (velocity, history, history_views, bind_groups) = create_targets(
+            device,
+            config,
+            hdr,
+            depth_texture,
+            &sampler,
+            &uniform_buffer,
+            &bind_group_layout,
+        );
+
+        Taa {
+            velocity,
+            pipeline,
+            bind_group_layout,
+            sampler,
+            uniform_buffer,
+            history,
+            history_views,
+            bind_groups,
+            size: [config.width, config.height],
+            current: 0,
+            frame: 0,
+            reset: true,
+            last_eye: cgmath::Point3::new(0.0, 0.0, 0.0),
+            last_direction: cgmath::Vector3::unit_z(),
+        }
+    }
+
+    /// Recreates the targets for the new surface size and drops the history, call after
+    /// recreating `hdr` and `depth_texture`.
+    pub fn resize(
+        &mut self,
+        device: &Device,
+        config: &wgpu::SurfaceConfiguration,
+        hdr: &HdrTarget,
+        depth_texture: &DepthTexture,
+    ) {
+        (
+            self.velocity,
+            self.history,
+            self.history_views,
+            self.bind_groups,
+        ) = create_targets(
+            device,
+            config,
+            hdr,
+            depth_texture,
+            &self.sampler,
+            &self.uniform_buffer,
+            &self.bind_group_layout,
+        );
+        self.size = [config.width, config.height];
+        self.reset();
+    }
+
+    /// Discards the history so the next frame starts over from a single sample. Camera jumps
+    /// are detected by `update`, call this for other cuts, like switching scenes or turning the
+    /// resolve back on.
+    pub fn reset(&mut self) {
+        self.reset = true;
+    }
+
+    /// Advances to the next jitter offset, returned in normalized device coordinates for
+    /// `Camera::set_jitter`.
+    pub fn next_jitter(&mut self) -> cgmath::Vector2<f32> {
+        self.frame = (self.frame + 1) % JITTER_SAMPLES;
+        // Halton bases 2 and 3 cover the pixel evenly in any run of consecutive frames.
+        let offset = cgmath::Vector2::new(
+            halton(self.frame + 1, 2) - 0.5,
+            halton(self.frame + 1, 3) - 0.5,
+        );
+        cgmath::Vector2::new(
+            offset.x * 2.0 / self.size[0] as f32,
+            offset.y * 2.0 / self.size[1] as f32,
+        )
+    }
+
+    /// Checks the camera for a cut and uploads this frame's settings.
+    pub fn update(&mut self, queue: &Queue, camera: &Camera) {
+        let eye = camera.eye();
+        let direction = camera.direction();
+        if (eye - self.last_eye).magnitude() > CUT_DISTANCE
+            || direction.dot(self.last_direction) < CUT_COS_ANGLE
+        {
+            self.reset = true;
+        }
+        self.last_eye = eye;
+        self.last_direction = direction;
+
+        let uniform = TaaUniform {
+            current_weight: Self::CURRENT_WEIGHT,
+            reset: self.reset as u32,
+            _padding: [0.0; 2],
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+        self.reset = false;
+        self.current = 1 - self.current;
+    }
+
+    /// Resolves the frame in `hdr` against the history and writes the result back into it.
+    pub fn render(&self, encoder: &mut wgpu::CommandEncoder, hdr: &HdrTarget) {
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("TAA Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &self.history_views[self.current],
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+            render_pass.set_pipeline(&self.pipeline);
+            render_pass.set_bind_group(0, &self.bind_groups[self.current], &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+        encoder.copy_texture_to_texture(
+            self.history[self.current].as_image_copy(),
+            hdr.texture.as_image_copy(),
+            wgpu::Extent3d {
+                width: self.size[0],
+                height: self.size[1],
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+}
+
+// Element `index` of the Halton low discrepancy sequence in `base`, in 0..1.
+fn halton(mut index: u32, base: u32) -> f32 {
+    let mut result = 0.0;
+    let mut fraction = 1.0;
+    while index > 0 {
+        fraction /= base as f32;
+        result += fraction * (index % base) as f32;
+        index /= base;
+    }
+    result
+}
+
+// Returns the velocity target, the two history targets with their views and, for each history
+// target, the bind group resolving into it from the other.
+fn create_targets(
+    device: &Device,
+    config: &wgpu::SurfaceConfiguration,
+    hdr: &HdrTarget,
+    depth_texture: &DepthTexture,
+    sampler: &wgpu::Sampler,
+    uniform_buffer: &wgpu::Buffer,
+    bind_group_layout: &BindGroupLayout,
+) -> (
+    wgpu::TextureView,
+    [wgpu::Texture; 2],
+    [wgpu::TextureView; 2],
+    [BindGroup; 2],
+) {
+    let size = wgpu::Extent3d {
+        width: config.width,
+        height: config.height,
+        depth_or_array_layers: 1,
+    };
+    let velocity = device
+        .create_texture(&wgpu::TextureDescriptor {
+            label: Some("velocity_target"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: Taa::VELOCITY_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        })
+        .create_view(&wgpu::TextureViewDescriptor::default());
+    let history = [0, 1].map(|_| {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("taa_history"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: HdrTarget::FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        })
+    });
+    let history_views = history
+        .each_ref()
+        .map(|texture| texture.create_view(&Default::default()));
+    let bind_groups = [0, 1].map(|index| {
+        create_taa_bind_group(
+            device,
+            &hdr.view,
+            &history_views[1 - index],
+            &velocity,
+            &depth_texture.depth_view,
+            sampler,
+            uniform_buffer,
+            bind_group_layout,
+        )
+    });
+
+    (velocity, history, history_views, bind_groups)
+}
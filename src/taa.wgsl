@@ -0,0 +1,130 @@
+// taa.wgsl
+// Temporal anti-aliasing resolve: blends the jittered frame into the history reprojected along
+// the motion vectors, after clamping the history to the colors around the pixel this frame so
+// stale samples from disoccluded or moving surfaces can't linger.
+
+struct Taa {
+    // Weight of this frame in the blend, the rest comes from the history.
+    current_weight: f32,
+    // Set on the first frame after a camera cut or resize, when the history is meaningless.
+    reset: u32,
+}
+
+@group(0) @binding(0)
+var t_current: texture_2d<f32>;
+@group(0) @binding(1)
+var t_history: texture_2d<f32>;
+@group(0) @binding(2)
+var t_velocity: texture_2d<f32>;
+@group(0) @binding(3)
+var t_depth: texture_depth_2d;
+@group(0) @binding(4)
+var s_taa: sampler;
+@group(0) @binding(5)
+var<uniform> taa: Taa;
+
+fn rgb_to_ycocg(color: vec3<f32>) -> vec3<f32> {
+    return vec3<f32>(
+        dot(color, vec3<f32>(0.25, 0.5, 0.25)),
+        dot(color, vec3<f32>(0.5, 0.0, -0.5)),
+        dot(color, vec3<f32>(-0.25, 0.5, -0.25)),
+    );
+}
+
+fn ycocg_to_rgb(color: vec3<f32>) -> vec3<f32> {
+    return vec3<f32>(
+        color.x + color.y - color.z,
+        color.x + color.z,
+        color.x - color.y - color.z,
+    );
+}
+
+fn luminance(color: vec3<f32>) -> f32 {
+    return dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+}
+
+// Motion of the nearest surface around the pixel, so the silhouettes of moving objects carry
+// their history along with them instead of the background's.
+fn dilated_velocity(pixel: vec2<i32>, size: vec2<i32>) -> vec2<f32> {
+    var closest_depth = 1.0;
+    var closest = pixel;
+    for (var y = -1; y <= 1; y++) {
+        for (var x = -1; x <= 1; x++) {
+            let neighbour = clamp(pixel + vec2<i32>(x, y), vec2<i32>(0), size - 1);
+            let depth = textureLoad(t_depth, neighbour, 0);
+            if (depth < closest_depth) {
+                closest_depth = depth;
+                closest = neighbour;
+            }
+        }
+    }
+    return textureLoad(t_velocity, closest, 0).xy;
+}
+
+fn history_at(uv: vec2<f32>) -> vec3<f32> {
+    return textureSampleLevel(t_history, s_taa, uv, 0.0).rgb;
+}
+
+// Catmull-Rom filtered history from five bilinear taps, after Jimenez's "Filmic SMAA". Plain
+// bilinear reprojection blurs the image a little more every frame.
+fn sample_history(uv: vec2<f32>) -> vec3<f32> {
+    let size = vec2<f32>(textureDimensions(t_history));
+    let position = uv * size;
+    let center = floor(position - 0.5) + 0.5;
+    let f = position - center;
+
+    let w0 = f * (-0.5 + f * (1.0 - 0.5 * f));
+    let w1 = 1.0 + f * f * (-2.5 + 1.5 * f);
+    let w2 = f * (0.5 + f * (2.0 - 1.5 * f));
+    let w3 = f * f * (-0.5 + 0.5 * f);
+    let w12 = w1 + w2;
+
+    let uv0 = (center - 1.0) / size;
+    let uv12 = (center + w2 / w12) / size;
+    let uv3 = (center + 2.0) / size;
+
+    let weights = vec4<f32>(w12.x * w0.y, w0.x * w12.y, w3.x * w12.y, w12.x * w3.y);
+    let center_weight = w12.x * w12.y;
+    let color = history_at(vec2<f32>(uv12.x, uv0.y)) * weights.x
+        + history_at(vec2<f32>(uv0.x, uv12.y)) * weights.y
+        + history_at(uv12) * center_weight
+        + history_at(vec2<f32>(uv3.x, uv12.y)) * weights.z
+        + history_at(vec2<f32>(uv12.x, uv3.y)) * weights.w;
+    let total = center_weight + weights.x + weights.y + weights.z + weights.w;
+
+    // The negative lobes can overshoot below zero next to bright pixels.
+    return max(color / total, vec3<f32>(0.0));
+}
+
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let size = vec2<i32>(textureDimensions(t_current));
+    let pixel = vec2<i32>(in.position.xy);
+    let current = textureLoad(t_current, pixel, 0).rgb;
+
+    let history_uv = in.uv - dilated_velocity(pixel, size);
+    if (taa.reset != 0u || any(history_uv < vec2<f32>(0.0)) || any(history_uv > vec2<f32>(1.0))) {
+        return vec4<f32>(current, 1.0);
+    }
+
+    // Bounding box of this frame's colors around the pixel.
+    var low = vec3<f32>(3.4e38);
+    var high = vec3<f32>(-3.4e38);
+    for (var y = -1; y <= 1; y++) {
+        for (var x = -1; x <= 1; x++) {
+            let neighbour = clamp(pixel + vec2<i32>(x, y), vec2<i32>(0), size - 1);
+            let color = rgb_to_ycocg(textureLoad(t_current, neighbour, 0).rgb);
+            low = min(low, color);
+            high = max(high, color);
+        }
+    }
+    let history = ycocg_to_rgb(clamp(rgb_to_ycocg(sample_history(history_uv)), low, high));
+
+    // Weighting by inverse luminance keeps a single bright sample from dominating the blend, which
+    // would otherwise flicker as the jitter moves across small highlights.
+    let current_weight = taa.current_weight / (1.0 + luminance(current));
+    let history_weight = (1.0 - taa.current_weight) / (1.0 + luminance(history));
+    let color = (current * current_weight + history * history_weight)
+        / (current_weight + history_weight);
+    return vec4<f32>(color, 1.0);
+}
@@ -1,70 +1,872 @@
-use image::{DynamicImage, GenericImageView};
+use std::collections::HashMap;
+use std::sync::{Arc, Weak};
+
+use image::{DynamicImage, GenericImageView, Rgba, Rgba32FImage, RgbaImage};
 use wgpu::{Device, Extent3d, Queue, Sampler, TextureView};
 
+use crate::bind_groups::{create_mipmap_bind_group, create_mipmap_bind_group_layout};
+use crate::compressed::CompressedImage;
+use crate::error::ResourceError;
+use crate::graphics_context::create_render_pipeline;
+use crate::procedural::{Pattern, PatternPipeline};
+
 pub struct Texture {
+    /// Kept alongside the view for copies and readback.
+    pub texture: wgpu::Texture,
+    /// Size of the full resolution level, `depth_or_array_layers` counts the array layers, cube
+    /// faces or volume slices.
+    pub size: Extent3d,
     pub view: TextureView,
-    pub sampler: Sampler,
+    /// Shared with every other texture created with the same sampling options.
+    pub sampler: Arc<Sampler>,
+}
+
+/// How the mip chain of a loaded texture is filled in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Mipmaps {
+    /// Only the full resolution level, for textures that are never minified.
+    None,
+    /// The full chain, downsampled on the GPU when the format can be rendered to and on the CPU
+    /// otherwise.
+    Generate,
+    /// The levels `resources::load_texture` finds pre-baked in `name_mip1.ext`, `name_mip2.ext`,
+    /// ... next to the file, generated as with `Generate` when there are none.
+    Files,
+}
+
+/// How `Texture::from_image` creates a texture and the sampler it is read with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TextureOptions {
+    pub address_mode_u: wgpu::AddressMode,
+    pub address_mode_v: wgpu::AddressMode,
+    pub address_mode_w: wgpu::AddressMode,
+    pub mag_filter: wgpu::FilterMode,
+    pub min_filter: wgpu::FilterMode,
+    pub mipmap_filter: wgpu::FilterMode,
+    /// Maximum anisotropy, 1 turns anisotropic filtering off. Only applies when all three filters
+    /// are linear, and is lowered to what the device supports.
+    pub anisotropy_clamp: u16,
+    /// Store the data as is instead of decoding it from sRGB. Normal maps and other data textures
+    /// hold vectors or scalars, not colors.
+    pub linear: bool,
+    /// Keep floating point images in `Rgba32Float` instead of `Rgba16Float`. Devices can't filter
+    /// 32 bit floats, so this suits lookup tables read with `textureLoad`.
+    pub float32: bool,
+    /// Usages on top of the `TEXTURE_BINDING` and `COPY_DST` every texture has, for textures that
+    /// are also rendered to or copied from.
+    pub usage: wgpu::TextureUsages,
+    pub mipmaps: Mipmaps,
+}
+
+impl Default for TextureOptions {
+    /// Trilinear filtering, clamped to the edges, sRGB and a generated mip chain.
+    fn default() -> Self {
+        Self {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            anisotropy_clamp: 1,
+            linear: false,
+            float32: false,
+            usage: wgpu::TextureUsages::empty(),
+            mipmaps: Mipmaps::Generate,
+        }
+    }
+}
+
+impl TextureOptions {
+    /// Repeats the texture along both axes of a surface, for tiling textures.
+    pub fn repeat(self) -> Self {
+        Self {
+            address_mode_u: wgpu::AddressMode::Repeat,
+            address_mode_v: wgpu::AddressMode::Repeat,
+            ..self
+        }
+    }
+
+    /// The options of the 1x1 textures `Texture::solid_color` creates, for caching them.
+    pub fn solid_color(linear: bool) -> Self {
+        Self {
+            linear,
+            mipmaps: Mipmaps::None,
+            ..Default::default()
+        }
+    }
+
+    /// The options of `Texture::checker`: repeated without mips, and with nearest magnification
+    /// to keep the squares sharp.
+    pub fn checker() -> Self {
+        Self {
+            mag_filter: wgpu::FilterMode::Nearest,
+            mipmaps: Mipmaps::None,
+            ..Default::default()
+        }
+        .repeat()
+    }
+}
+
+/// The samplers and mip chain pipelines shared by the textures created with it, so textures with
+/// the same sampling options or format don't each create their own.
+#[derive(Default)]
+pub struct TextureHelpers {
+    samplers: HashMap<SamplerKey, Arc<Sampler>>,
+    mipmaps: Option<MipmapPipelines>,
+}
+
+// The parts of `TextureOptions` that affect sampling.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct SamplerKey {
+    address_modes: [wgpu::AddressMode; 3],
+    filters: [wgpu::FilterMode; 3],
+    anisotropy_clamp: u16,
+}
+
+impl TextureHelpers {
+    pub fn new() -> TextureHelpers {
+        Self::default()
+    }
+
+    /// The sampler for `options`, creating it the first time it is asked for.
+    pub fn get(&mut self, device: &Device, options: &TextureOptions) -> Arc<Sampler> {
+        let filters = [
+            options.mag_filter,
+            options.min_filter,
+            options.mipmap_filter,
+        ];
+        // Anisotropic filtering is only valid with linear filtering throughout.
+        let anisotropy_clamp = if filters.contains(&wgpu::FilterMode::Nearest) {
+            1
+        } else {
+            options.anisotropy_clamp.max(1)
+        };
+        let key = SamplerKey {
+            address_modes: [
+                options.address_mode_u,
+                options.address_mode_v,
+                options.address_mode_w,
+            ],
+            filters,
+            anisotropy_clamp,
+        };
+
+        self.samplers
+            .entry(key)
+            .or_insert_with(|| {
+                Arc::new(device.create_sampler(&wgpu::SamplerDescriptor {
+                    label: Some("texture_sampler"),
+                    address_mode_u: options.address_mode_u,
+                    address_mode_v: options.address_mode_v,
+                    address_mode_w: options.address_mode_w,
+                    mag_filter: options.mag_filter,
+                    min_filter: options.min_filter,
+                    mipmap_filter: options.mipmap_filter,
+                    anisotropy_clamp,
+                    ..Default::default()
+                }))
+            })
+            .clone()
+    }
+}
+
+/// Textures shared by the materials and models loaded with it, keyed by the file they come from
+/// and the options they were created with. Only weak references are kept, so a texture is evicted
+/// once the last material using it is dropped.
+#[derive(Default)]
+pub struct TextureCache {
+    textures: HashMap<(String, TextureOptions), Weak<Texture>>,
+}
+
+impl TextureCache {
+    pub fn new() -> TextureCache {
+        Self::default()
+    }
+
+    /// The texture loaded from `path` with `options`, if anything still uses it.
+    pub fn get(&self, path: &str, options: &TextureOptions) -> Option<Arc<Texture>> {
+        self.textures.get(&(path.to_string(), *options))?.upgrade()
+    }
+
+    /// Shares `texture` as the one loaded from `path` with `options`, and forgets about the
+    /// textures that were evicted since the last insert.
+    pub fn insert(
+        &mut self,
+        path: &str,
+        options: &TextureOptions,
+        texture: Texture,
+    ) -> Arc<Texture> {
+        self.textures
+            .retain(|_, texture| texture.strong_count() > 0);
+        let texture = Arc::new(texture);
+        self.textures
+            .insert((path.to_string(), *options), Arc::downgrade(&texture));
+        texture
+    }
+
+    /// The texture loaded from `path` with `options`, creating it if nothing uses it yet.
+    pub fn get_or_insert_with(
+        &mut self,
+        path: &str,
+        options: &TextureOptions,
+        create: impl FnOnce() -> Texture,
+    ) -> Arc<Texture> {
+        match self.get(path, options) {
+            Some(texture) => texture,
+            None => self.insert(path, options, create()),
+        }
+    }
+}
+
+/// Decodes an image file in any format the `image` crate reads, OpenEXR included. HDR and EXR
+/// images come back as 32 bit floats and 16 bit PNGs keep their 16 bits.
+pub fn decode_image(bytes: &[u8], path: &str) -> Result<DynamicImage, ResourceError> {
+    image::load_from_memory(bytes).map_err(|error| ResourceError::from_image(path, error))
 }
 
 impl Texture {
     pub fn from_bytes(
         device: &Device,
         queue: &Queue,
+        helpers: &mut TextureHelpers,
         bytes: &[u8],
         label: Option<&str>,
-    ) -> Texture {
-        let img = image::load_from_memory(bytes).unwrap();
-        Self::from_image(device, queue, &img, label)
+        options: &TextureOptions,
+    ) -> Result<Texture, ResourceError> {
+        let img = decode_image(bytes, label.unwrap_or_default())?;
+        Ok(Self::from_image(
+            device, queue, helpers, &img, label, options,
+        ))
     }
 
     pub fn from_image(
         device: &Device,
         queue: &Queue,
+        helpers: &mut TextureHelpers,
         img: &DynamicImage,
         label: Option<&str>,
+        options: &TextureOptions,
     ) -> Texture {
-        let diffuse_rgba = img.to_rgba8();
-        let dimensions = img.dimensions();
+        Self::from_layer_images(
+            device,
+            queue,
+            helpers,
+            std::slice::from_ref(img),
+            wgpu::TextureViewDimension::D2,
+            label,
+            options,
+        )
+    }
 
+    /// Draws `pattern` at `width` by `height` on the CPU and creates the texture from the image.
+    pub fn from_pattern(
+        device: &Device,
+        queue: &Queue,
+        helpers: &mut TextureHelpers,
+        pattern: &Pattern,
+        (width, height): (u32, u32),
+        label: Option<&str>,
+        options: &TextureOptions,
+    ) -> Texture {
+        Self::from_image(
+            device,
+            queue,
+            helpers,
+            &pattern.image(width, height),
+            label,
+            options,
+        )
+    }
+
+    /// Like `from_pattern`, but drawn by a compute shader writing straight into the texture,
+    /// without the pixels passing through the CPU. sRGB formats can't be written as storage
+    /// textures, so unless `options.linear` is set the pattern is drawn into a linear copy first.
+    #[allow(clippy::too_many_arguments)]
+    pub fn generate(
+        device: &Device,
+        queue: &Queue,
+        helpers: &mut TextureHelpers,
+        patterns: &PatternPipeline,
+        pattern: &Pattern,
+        dimensions: (u32, u32),
+        label: Option<&str>,
+        options: &TextureOptions,
+    ) -> Texture {
         let size = get_texture_size(dimensions);
-        let diffuse_texture = create_texture(size, device, label);
-
-        queue.write_texture(
-            wgpu::ImageCopyTexture {
-                texture: &diffuse_texture,
-                mip_level: 0,
-                origin: wgpu::Origin3d::ZERO,
-                aspect: wgpu::TextureAspect::All,
-            },
-            &diffuse_rgba,
-            wgpu::ImageDataLayout {
-                offset: 0,
-                bytes_per_row: Some(4 * size.width),
-                rows_per_image: Some(size.height),
-            },
+        let mip_level_count = match options.mipmaps {
+            Mipmaps::None => 1,
+            Mipmaps::Generate | Mipmaps::Files => mip_level_count(dimensions),
+        };
+        let mut usage =
+            wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST | options.usage;
+        if mip_level_count > 1 {
+            usage |= wgpu::TextureUsages::RENDER_ATTACHMENT;
+        }
+        if options.linear {
+            usage |= wgpu::TextureUsages::STORAGE_BINDING;
+        }
+        let texture = create_texture(
             size,
+            wgpu::TextureDimension::D2,
+            texture_format(options),
+            mip_level_count,
+            usage,
+            device,
+            label,
         );
 
-        let view = diffuse_texture.create_view(&wgpu::TextureViewDescriptor::default());
-        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-            address_mode_u: wgpu::AddressMode::ClampToEdge,
-            address_mode_v: wgpu::AddressMode::ClampToEdge,
-            address_mode_w: wgpu::AddressMode::ClampToEdge,
-            mag_filter: wgpu::FilterMode::Linear,
-            min_filter: wgpu::FilterMode::Nearest,
-            mipmap_filter: wgpu::FilterMode::Nearest,
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Pattern Encoder"),
+        });
+        let storage = (!options.linear).then(|| {
+            create_texture(
+                size,
+                wgpu::TextureDimension::D2,
+                wgpu::TextureFormat::Rgba8Unorm,
+                1,
+                wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::COPY_SRC,
+                device,
+                Some("pattern_storage_texture"),
+            )
+        });
+        let target = storage.as_ref().unwrap_or(&texture);
+        let view = target.create_view(&wgpu::TextureViewDescriptor {
+            mip_level_count: Some(1),
             ..Default::default()
         });
+        pattern.write(
+            device,
+            patterns,
+            &mut encoder,
+            &view,
+            size.width,
+            size.height,
+        );
+        if let Some(storage) = &storage {
+            encoder.copy_texture_to_texture(storage.as_image_copy(), texture.as_image_copy(), size);
+        }
+        queue.submit(std::iter::once(encoder.finish()));
+
+        let texture = Self::from_texture(
+            device,
+            helpers,
+            texture,
+            wgpu::TextureViewDimension::D2,
+            options,
+        );
+        if mip_level_count > 1 {
+            generate_mipmaps(device, queue, helpers, &texture.texture);
+        }
+        texture
+    }
+
+    /// Creates a 2D array texture with one layer per image, all of the same size and color type,
+    /// bound as a `texture_2d_array`.
+    pub fn from_layers(
+        device: &Device,
+        queue: &Queue,
+        helpers: &mut TextureHelpers,
+        layers: &[DynamicImage],
+        label: Option<&str>,
+        options: &TextureOptions,
+    ) -> Result<Texture, ResourceError> {
+        check_images(layers, true, label)?;
+        Ok(Self::from_layer_images(
+            device,
+            queue,
+            helpers,
+            layers,
+            wgpu::TextureViewDimension::D2Array,
+            label,
+            options,
+        ))
+    }
+
+    /// Creates a cube map from six square images of the same size and color type, in +X, -X, +Y,
+    /// -Y, +Z, -Z order.
+    pub fn from_cube_faces(
+        device: &Device,
+        queue: &Queue,
+        helpers: &mut TextureHelpers,
+        faces: &[DynamicImage; 6],
+        label: Option<&str>,
+        options: &TextureOptions,
+    ) -> Result<Texture, ResourceError> {
+        let (width, height) = check_images(faces, true, label)?.dimensions();
+        if width != height {
+            return Err(ResourceError::decode(
+                label.unwrap_or_default(),
+                format!("cube map faces must be square, not {width}x{height}"),
+            ));
+        }
+        Ok(Self::from_layer_images(
+            device,
+            queue,
+            helpers,
+            faces,
+            wgpu::TextureViewDimension::Cube,
+            label,
+            options,
+        ))
+    }
+
+    /// Creates a cube map from an image of its unfolded faces. A horizontal cross is four faces
+    /// wide with -X, +Z, +X, -Z in the middle row, a vertical one three wide with -Z upside down
+    /// below -Y. Both have +Y above and -Y below +Z. `None` when the image is shaped like neither.
+    pub fn from_cube_cross(
+        device: &Device,
+        queue: &Queue,
+        helpers: &mut TextureHelpers,
+        cross: &DynamicImage,
+        label: Option<&str>,
+        options: &TextureOptions,
+    ) -> Option<Texture> {
+        let (width, height) = cross.dimensions();
+        // The column and row of each face, in +X, -X, +Y, -Y, +Z, -Z order.
+        let (size, cells) = if width * 3 == height * 4 {
+            (width / 4, [(2, 1), (0, 1), (1, 0), (1, 2), (1, 1), (3, 1)])
+        } else if width * 4 == height * 3 {
+            (width / 3, [(2, 1), (0, 1), (1, 0), (1, 2), (1, 1), (1, 3)])
+        } else {
+            return None;
+        };
+        if size == 0 {
+            return None;
+        }
+        let faces = cells.map(|(column, row)| {
+            let face = cross.crop_imm(column * size, row * size, size, size);
+            if row == 3 {
+                face.rotate180()
+            } else {
+                face
+            }
+        });
+        // The faces are square crops of one image, so there is nothing to check.
+        Some(Self::from_layer_images(
+            device,
+            queue,
+            helpers,
+            &faces,
+            wgpu::TextureViewDimension::Cube,
+            label,
+            options,
+        ))
+    }
+
+    /// Creates a 3D texture from slices along z of the same size and color type, bound as a
+    /// `texture_3d`. Volumes have no mip chain, `options.mipmaps` doesn't apply.
+    pub fn from_volume(
+        device: &Device,
+        queue: &Queue,
+        helpers: &mut TextureHelpers,
+        slices: &[DynamicImage],
+        label: Option<&str>,
+        options: &TextureOptions,
+    ) -> Result<Texture, ResourceError> {
+        let first = check_images(slices, true, label)?;
+        let format = image_format(device, first, options);
+        let texture = create_texture(
+            Extent3d {
+                depth_or_array_layers: slices.len() as u32,
+                ..get_texture_size(first.dimensions())
+            },
+            wgpu::TextureDimension::D3,
+            format,
+            1,
+            wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST | options.usage,
+            device,
+            label,
+        );
+        for (slice, img) in slices.iter().enumerate() {
+            MipLevel::new(img, format, options).write(queue, &texture, 0, slice as u32, format);
+        }
+
+        Ok(Self::from_texture(
+            device,
+            helpers,
+            texture,
+            wgpu::TextureViewDimension::D3,
+            options,
+        ))
+    }
+
+    // `layers` must hold at least one image and share its size and color type, which the public
+    // constructors check.
+    fn from_layer_images(
+        device: &Device,
+        queue: &Queue,
+        helpers: &mut TextureHelpers,
+        layers: &[DynamicImage],
+        dimension: wgpu::TextureViewDimension,
+        label: Option<&str>,
+        options: &TextureOptions,
+    ) -> Texture {
+        let format = image_format(device, &layers[0], options);
+        let mip_level_count = match options.mipmaps {
+            Mipmaps::None => 1,
+            Mipmaps::Generate | Mipmaps::Files => mip_level_count(layers[0].dimensions()),
+        };
+        let on_gpu = mip_level_count > 1
+            && format
+                .guaranteed_format_features(device.features())
+                .allowed_usages
+                .contains(wgpu::TextureUsages::RENDER_ATTACHMENT)
+            && format
+                .guaranteed_format_features(device.features())
+                .flags
+                .contains(wgpu::TextureFormatFeatureFlags::FILTERABLE);
+
+        let mut usage =
+            wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST | options.usage;
+        if on_gpu {
+            usage |= wgpu::TextureUsages::RENDER_ATTACHMENT;
+        }
+        let texture = create_texture(
+            Extent3d {
+                depth_or_array_layers: layers.len() as u32,
+                ..get_texture_size(layers[0].dimensions())
+            },
+            wgpu::TextureDimension::D2,
+            format,
+            mip_level_count,
+            usage,
+            device,
+            label,
+        );
+
+        for (layer, img) in layers.iter().enumerate() {
+            let mut level = MipLevel::new(img, format, options);
+            let mip_levels = if on_gpu { 1 } else { mip_level_count };
+            for mip_level in 0..mip_levels {
+                if mip_level > 0 {
+                    level = level.downsample(!options.linear);
+                }
+                level.write(queue, &texture, mip_level, layer as u32, format);
+            }
+        }
+
+        let texture = Self::from_texture(device, helpers, texture, dimension, options);
+        if on_gpu {
+            generate_mipmaps(device, queue, helpers, &texture.texture);
+        }
+        texture
+    }
+
+    /// Copies single layer textures into the layers of a new 2D array texture, read with the
+    /// sampler of the first. `None` unless they all share a size, format, mip chain and sampler,
+    /// and were created with `COPY_SRC`.
+    pub fn pack_layers(
+        device: &Device,
+        queue: &Queue,
+        textures: &[Arc<Texture>],
+        label: Option<&str>,
+    ) -> Option<Texture> {
+        let first = textures.first()?;
+        let (format, mip_level_count) = (first.texture.format(), first.texture.mip_level_count());
+        let compatible = textures.iter().all(|texture| {
+            texture.size == first.size
+                && texture.size.depth_or_array_layers == 1
+                && texture.texture.format() == format
+                && texture.texture.mip_level_count() == mip_level_count
+                && Arc::ptr_eq(&texture.sampler, &first.sampler)
+                && texture
+                    .texture
+                    .usage()
+                    .contains(wgpu::TextureUsages::COPY_SRC)
+        });
+        if !compatible {
+            return None;
+        }
+
+        // The GL backend creates single layer textures as plain 2D textures, which can't be viewed
+        // as arrays, so there is always a spare layer.
+        let texture = create_texture(
+            Extent3d {
+                depth_or_array_layers: textures.len().max(2) as u32,
+                ..first.size
+            },
+            wgpu::TextureDimension::D2,
+            format,
+            mip_level_count,
+            wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            device,
+            label,
+        );
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Pack Layers Encoder"),
+        });
+        for (layer, source) in textures.iter().enumerate() {
+            for mip_level in 0..mip_level_count {
+                encoder.copy_texture_to_texture(
+                    wgpu::ImageCopyTexture {
+                        texture: &source.texture,
+                        mip_level,
+                        origin: wgpu::Origin3d::ZERO,
+                        aspect: wgpu::TextureAspect::All,
+                    },
+                    wgpu::ImageCopyTexture {
+                        texture: &texture,
+                        mip_level,
+                        origin: wgpu::Origin3d {
+                            x: 0,
+                            y: 0,
+                            z: layer as u32,
+                        },
+                        aspect: wgpu::TextureAspect::All,
+                    },
+                    first
+                        .size
+                        .mip_level_size(mip_level, wgpu::TextureDimension::D2)
+                        .physical_size(format),
+                );
+            }
+        }
+        queue.submit(std::iter::once(encoder.finish()));
+
+        Some(Texture {
+            view: texture.create_view(&wgpu::TextureViewDescriptor {
+                dimension: Some(wgpu::TextureViewDimension::D2Array),
+                ..Default::default()
+            }),
+            size: texture.size(),
+            texture,
+            sampler: first.sampler.clone(),
+        })
+    }
+
+    /// Creates a texture from a full resolution image followed by its pre-baked mip levels, each
+    /// half the size of the one before, rounded down, and of the same color type. The chain may
+    /// stop short of 1x1 but not go past it.
+    pub fn from_mip_images(
+        device: &Device,
+        queue: &Queue,
+        helpers: &mut TextureHelpers,
+        levels: &[DynamicImage],
+        label: Option<&str>,
+        options: &TextureOptions,
+    ) -> Result<Texture, ResourceError> {
+        let first = check_images(levels, false, label)?;
+        check_mip_sizes(
+            &levels
+                .iter()
+                .map(|level| level.dimensions())
+                .collect::<Vec<_>>(),
+            label,
+        )?;
+        let format = image_format(device, first, options);
+        let texture = create_texture(
+            get_texture_size(first.dimensions()),
+            wgpu::TextureDimension::D2,
+            format,
+            levels.len() as u32,
+            wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST | options.usage,
+            device,
+            label,
+        );
+        for (mip_level, level) in levels.iter().enumerate() {
+            MipLevel::new(level, format, options).write(
+                queue,
+                &texture,
+                mip_level as u32,
+                0,
+                format,
+            );
+        }
+
+        Ok(Self::from_texture(
+            device,
+            helpers,
+            texture,
+            wgpu::TextureViewDimension::D2,
+            options,
+        ))
+    }
+
+    /// Creates a texture from a KTX2 or DDS image in its own GPU format, decoded to RGBA8 on the
+    /// CPU when the device doesn't support that format. The mip chain is the one in the file,
+    /// `options.mipmaps` and `options.linear` don't apply.
+    pub fn from_compressed(
+        device: &Device,
+        queue: &Queue,
+        helpers: &mut TextureHelpers,
+        image: &CompressedImage,
+        label: Option<&str>,
+        options: &TextureOptions,
+    ) -> Result<Texture, ResourceError> {
+        // Block compressed textures must also be a whole number of blocks in size.
+        let supported = device.features().contains(image.format.required_features())
+            && image.size.physical_size(image.format) == image.size;
+        let decompressed;
+        let image = if supported {
+            image
+        } else {
+            decompressed = image
+                .decompress()
+                .ok_or_else(|| ResourceError::UnsupportedFormat {
+                    path: label.unwrap_or_default().to_string(),
+                    format: format!("{:?} on this device", image.format),
+                })?;
+            &decompressed
+        };
+
+        // Files that leave the mip chain to the loader get it rendered, when the format can be.
+        let features = image.format.guaranteed_format_features(device.features());
+        let render_mipmaps = image.generate_mipmaps
+            && features
+                .allowed_usages
+                .contains(wgpu::TextureUsages::RENDER_ATTACHMENT)
+            && features
+                .flags
+                .contains(wgpu::TextureFormatFeatureFlags::FILTERABLE);
+        let (mip_level_count, usage) = if render_mipmaps {
+            (
+                image.size.max_mips(wgpu::TextureDimension::D2),
+                wgpu::TextureUsages::RENDER_ATTACHMENT,
+            )
+        } else {
+            (image.levels.len() as u32, wgpu::TextureUsages::empty())
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label,
+            size: image.size,
+            mip_level_count,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: image.format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_DST
+                | usage
+                | options.usage,
+            view_formats: &[],
+        });
+        let (block_width, block_height) = image.format.block_dimensions();
+        let block_size = image.format.block_size(None).unwrap_or(4);
+        for (mip_level, level) in image.levels.iter().enumerate() {
+            let size = image.level_size(mip_level as u32);
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &texture,
+                    mip_level: mip_level as u32,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                level,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(size.width.div_ceil(block_width) * block_size),
+                    rows_per_image: Some(size.height.div_ceil(block_height)),
+                },
+                size.physical_size(image.format),
+            );
+        }
+        if render_mipmaps {
+            generate_mipmaps(device, queue, helpers, &texture);
+        }
+
+        let dimension = match (image.cube, image.size.depth_or_array_layers) {
+            (true, 6) => wgpu::TextureViewDimension::Cube,
+            (true, _) => wgpu::TextureViewDimension::CubeArray,
+            (false, 1) => wgpu::TextureViewDimension::D2,
+            (false, _) => wgpu::TextureViewDimension::D2Array,
+        };
+        Ok(Self::from_texture(
+            device, helpers, texture, dimension, options,
+        ))
+    }
+
+    fn from_texture(
+        device: &Device,
+        helpers: &mut TextureHelpers,
+        texture: wgpu::Texture,
+        dimension: wgpu::TextureViewDimension,
+        options: &TextureOptions,
+    ) -> Texture {
+        Texture {
+            view: texture.create_view(&wgpu::TextureViewDescriptor {
+                dimension: Some(dimension),
+                ..Default::default()
+            }),
+            size: texture.size(),
+            texture,
+            sampler: helpers.get(device, options),
+        }
+    }
+
+    /// 1x1 texture of a single color, used as a fallback for maps a material doesn't provide.
+    pub fn solid_color(
+        device: &Device,
+        queue: &Queue,
+        helpers: &mut TextureHelpers,
+        color: [u8; 4],
+        label: Option<&str>,
+        linear: bool,
+    ) -> Texture {
+        Self::from_pattern(
+            device,
+            queue,
+            helpers,
+            &Pattern::Solid(color),
+            (1, 1),
+            label,
+            &TextureOptions::solid_color(linear),
+        )
+    }
 
-        Texture { view, sampler }
+    /// 1x1 white texture, leaving the material factor it is multiplied with as it is.
+    pub fn white(
+        device: &Device,
+        queue: &Queue,
+        helpers: &mut TextureHelpers,
+        linear: bool,
+    ) -> Texture {
+        Self::solid_color(
+            device,
+            queue,
+            helpers,
+            [255; 4],
+            Some("white_texture"),
+            linear,
+        )
     }
 
-    pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+    /// 1x1 normal map pointing straight out of the surface, for materials without one.
+    pub fn flat_normal(device: &Device, queue: &Queue, helpers: &mut TextureHelpers) -> Texture {
+        Self::solid_color(
+            device,
+            queue,
+            helpers,
+            [128, 128, 255, 255],
+            Some("flat_normal_texture"),
+            true,
+        )
+    }
+
+    /// Magenta and black checkerboard with 8x8 squares across the texture, standing in for color
+    /// textures that failed to load so they are easy to spot.
+    pub fn checker(device: &Device, queue: &Queue, helpers: &mut TextureHelpers) -> Texture {
+        let pattern = Pattern::Checkerboard {
+            cells: 8,
+            colors: [[255, 0, 255, 255], [0, 0, 0, 255]],
+        };
+        Self::from_pattern(
+            device,
+            queue,
+            helpers,
+            &pattern,
+            (8, 8),
+            Some("checker_texture"),
+            &TextureOptions::checker(),
+        )
+    }
+
+    /// Depth with an 8 bit stencil, the stencil marks the selected instances for their outline.
+    pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth24PlusStencil8;
     pub fn create_depth_texture(
         device: &Device,
         config: &wgpu::SurfaceConfiguration,
         label: &str,
-    ) -> Self {
+    ) -> DepthTexture {
         let size = Extent3d {
             width: config.width,
             height: config.height,
@@ -83,23 +885,23 @@ impl Texture {
         let texture = device.create_texture(&desc);
 
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-            address_mode_u: wgpu::AddressMode::ClampToEdge,
-            address_mode_v: wgpu::AddressMode::ClampToEdge,
-            address_mode_w: wgpu::AddressMode::ClampToEdge,
-            mag_filter: wgpu::FilterMode::Linear,
-            min_filter: wgpu::FilterMode::Linear,
-            mipmap_filter: wgpu::FilterMode::Nearest,
-            compare: Some(wgpu::CompareFunction::LessEqual),
-            lod_min_clamp: 0.0,
-            lod_max_clamp: 100.0,
+        let depth_view = texture.create_view(&wgpu::TextureViewDescriptor {
+            aspect: wgpu::TextureAspect::DepthOnly,
             ..Default::default()
         });
 
-        Self { view, sampler }
+        DepthTexture { view, depth_view }
     }
 }
 
+/// Scene depth and stencil buffer.
+pub struct DepthTexture {
+    /// Both aspects, for use as a render pass attachment.
+    pub view: TextureView,
+    /// The depth aspect alone, for binding to shaders.
+    pub depth_view: TextureView,
+}
+
 fn get_texture_size(dimensions: (u32, u32)) -> Extent3d {
     wgpu::Extent3d {
         width: dimensions.0,
@@ -108,15 +910,639 @@ fn get_texture_size(dimensions: (u32, u32)) -> Extent3d {
     }
 }
 
-fn create_texture(texture_size: Extent3d, device: &Device, label: Option<&str>) -> wgpu::Texture {
+fn create_texture(
+    texture_size: Extent3d,
+    dimension: wgpu::TextureDimension,
+    format: wgpu::TextureFormat,
+    mip_level_count: u32,
+    usage: wgpu::TextureUsages,
+    device: &Device,
+    label: Option<&str>,
+) -> wgpu::Texture {
     device.create_texture(&wgpu::TextureDescriptor {
         size: texture_size,
-        mip_level_count: 1,
+        mip_level_count,
         sample_count: 1,
-        dimension: wgpu::TextureDimension::D2,
-        format: wgpu::TextureFormat::Rgba8UnormSrgb,
-        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        dimension,
+        format,
+        usage,
         label,
         view_formats: &[],
     })
 }
+
+/// The format `img` is stored in: 8 bit images keep 8 bits and 16 bit ones go to `Rgba16Unorm`
+/// when the device supports it. Without `TEXTURE_FORMAT_16BIT_NORM` they fall back to
+/// `Rgba16Float`, which is lossy: its 11 bit mantissa can't hold every 16 bit value, so bright
+/// values get rounded. Floating point images go to `Rgba16Float` too unless `options.float32` asks
+/// for full precision.
+fn image_format(
+    device: &Device,
+    img: &DynamicImage,
+    options: &TextureOptions,
+) -> wgpu::TextureFormat {
+    use DynamicImage::*;
+    match img {
+        ImageLuma16(_) | ImageLumaA16(_) | ImageRgb16(_) | ImageRgba16(_)
+            if device
+                .features()
+                .contains(wgpu::Features::TEXTURE_FORMAT_16BIT_NORM) =>
+        {
+            wgpu::TextureFormat::Rgba16Unorm
+        }
+        ImageRgb32F(_) | ImageRgba32F(_) if options.float32 => wgpu::TextureFormat::Rgba32Float,
+        ImageLuma16(_) | ImageLumaA16(_) | ImageRgb16(_) | ImageRgba16(_) | ImageRgb32F(_)
+        | ImageRgba32F(_) => wgpu::TextureFormat::Rgba16Float,
+        _ => texture_format(options),
+    }
+}
+
+/// The first of `images`, once it's clear there is one and that the others have its color type
+/// and, with `same_size`, its dimensions. `label` names the texture in the error.
+fn check_images<'a>(
+    images: &'a [DynamicImage],
+    same_size: bool,
+    label: Option<&str>,
+) -> Result<&'a DynamicImage, ResourceError> {
+    let path = label.unwrap_or_default();
+    let first = images
+        .first()
+        .ok_or_else(|| ResourceError::decode(path, "no images to make a texture of"))?;
+    for (i, img) in images.iter().enumerate().skip(1) {
+        if img.color() != first.color() {
+            return Err(ResourceError::decode(
+                path,
+                format!(
+                    "image {i} is {:?}, the first is {:?}",
+                    img.color(),
+                    first.color()
+                ),
+            ));
+        }
+        if same_size && img.dimensions() != first.dimensions() {
+            let ((width, height), (first_width, first_height)) =
+                (img.dimensions(), first.dimensions());
+            return Err(ResourceError::decode(
+                path,
+                format!("image {i} is {width}x{height}, the first is {first_width}x{first_height}"),
+            ));
+        }
+    }
+    Ok(first)
+}
+
+// Checks `sizes` are a mip chain: each level half the one before, and no more levels than the
+// full resolution one has.
+fn check_mip_sizes(sizes: &[(u32, u32)], label: Option<&str>) -> Result<(), ResourceError> {
+    let path = label.unwrap_or_default();
+    let Some(&(width, height)) = sizes.first() else {
+        return Ok(());
+    };
+    let full_chain = mip_level_count((width, height));
+    if sizes.len() > full_chain as usize {
+        return Err(ResourceError::decode(
+            path,
+            format!(
+                "{} mip levels is more than the {full_chain} of a {width}x{height} texture",
+                sizes.len()
+            ),
+        ));
+    }
+    for (mip_level, &size) in sizes.iter().enumerate().skip(1) {
+        let expected = ((width >> mip_level).max(1), (height >> mip_level).max(1));
+        if size != expected {
+            return Err(ResourceError::decode(
+                path,
+                format!(
+                    "mip level {mip_level} is {}x{}, not {}x{}",
+                    size.0, size.1, expected.0, expected.1
+                ),
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn texture_format(options: &TextureOptions) -> wgpu::TextureFormat {
+    if options.linear {
+        wgpu::TextureFormat::Rgba8Unorm
+    } else {
+        wgpu::TextureFormat::Rgba8UnormSrgb
+    }
+}
+
+/// Levels in a full mip chain, down to 1x1.
+fn mip_level_count(dimensions: (u32, u32)) -> u32 {
+    32 - dimensions.0.max(dimensions.1).max(1).leading_zeros()
+}
+
+fn write_mip_level(
+    queue: &Queue,
+    texture: &wgpu::Texture,
+    mip_level: u32,
+    layer: u32,
+    dimensions: (u32, u32),
+    format: wgpu::TextureFormat,
+    data: &[u8],
+) {
+    let size = get_texture_size(dimensions);
+    queue.write_texture(
+        wgpu::ImageCopyTexture {
+            texture,
+            mip_level,
+            origin: wgpu::Origin3d {
+                x: 0,
+                y: 0,
+                z: layer,
+            },
+            aspect: wgpu::TextureAspect::All,
+        },
+        data,
+        wgpu::ImageDataLayout {
+            offset: 0,
+            bytes_per_row: Some(format.block_size(None).unwrap() * size.width),
+            rows_per_image: Some(size.height),
+        },
+        size,
+    );
+}
+
+/// One mip level on the CPU, in 8 bits for the RGBA8 formats and as linear floats for the higher
+/// precision ones.
+enum MipLevel {
+    Rgba8(RgbaImage),
+    Float(Rgba32FImage),
+}
+
+impl MipLevel {
+    fn new(img: &DynamicImage, format: wgpu::TextureFormat, options: &TextureOptions) -> MipLevel {
+        if matches!(
+            format,
+            wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb
+        ) {
+            return MipLevel::Rgba8(img.to_rgba8());
+        }
+        let mut texels = img.to_rgba32f();
+        // The higher precision formats have no sRGB variants, so 16 bit colors are decoded here.
+        // Floating point images are linear already.
+        let float = matches!(
+            img,
+            DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_)
+        );
+        if !options.linear && !float {
+            for texel in texels.pixels_mut() {
+                for channel in &mut texel.0[..3] {
+                    *channel = srgb_to_linear(*channel);
+                }
+            }
+        }
+        MipLevel::Float(texels)
+    }
+
+    fn downsample(&self, srgb: bool) -> MipLevel {
+        match self {
+            MipLevel::Rgba8(rgba) => MipLevel::Rgba8(downsample(rgba, srgb)),
+            MipLevel::Float(texels) => MipLevel::Float(downsample_float(texels)),
+        }
+    }
+
+    fn write(
+        &self,
+        queue: &Queue,
+        texture: &wgpu::Texture,
+        mip_level: u32,
+        layer: u32,
+        format: wgpu::TextureFormat,
+    ) {
+        match self {
+            MipLevel::Rgba8(rgba) => write_mip_level(
+                queue,
+                texture,
+                mip_level,
+                layer,
+                rgba.dimensions(),
+                format,
+                rgba,
+            ),
+            MipLevel::Float(texels) => {
+                let values = texels.as_raw().iter();
+                let data: Vec<u8> = match format {
+                    wgpu::TextureFormat::Rgba16Unorm => values
+                        .flat_map(|value| {
+                            ((value.clamp(0.0, 1.0) * 65535.0).round() as u16).to_le_bytes()
+                        })
+                        .collect(),
+                    wgpu::TextureFormat::Rgba16Float => values
+                        .flat_map(|&value| f32_to_f16(value).to_le_bytes())
+                        .collect(),
+                    _ => values.flat_map(|value| value.to_le_bytes()).collect(),
+                };
+                write_mip_level(
+                    queue,
+                    texture,
+                    mip_level,
+                    layer,
+                    texels.dimensions(),
+                    format,
+                    &data,
+                )
+            }
+        }
+    }
+}
+
+// The downsampling pipelines of `generate_mipmaps`, created once per format and kept in the
+// `TextureHelpers` the textures are created with.
+struct MipmapPipelines {
+    bind_group_layout: wgpu::BindGroupLayout,
+    layout: wgpu::PipelineLayout,
+    sampler: Sampler,
+    pipelines: HashMap<wgpu::TextureFormat, wgpu::RenderPipeline>,
+}
+
+impl MipmapPipelines {
+    fn new(device: &Device) -> MipmapPipelines {
+        let bind_group_layout = create_mipmap_bind_group_layout(device);
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Mipmap Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("mipmap_sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        MipmapPipelines {
+            bind_group_layout,
+            layout,
+            sampler,
+            pipelines: HashMap::new(),
+        }
+    }
+}
+
+/// Fills in mip levels 1.. of every layer of `texture` by rendering each level from the one above
+/// it with a bilinear sampler, which averages 2x2 texels when the size halves.
+fn generate_mipmaps(
+    device: &Device,
+    queue: &Queue,
+    helpers: &mut TextureHelpers,
+    texture: &wgpu::Texture,
+) {
+    let format = texture.format();
+    let mip_level_count = texture.mip_level_count();
+    let MipmapPipelines {
+        bind_group_layout,
+        layout,
+        sampler,
+        pipelines,
+    } = helpers
+        .mipmaps
+        .get_or_insert_with(|| MipmapPipelines::new(device));
+    let pipeline = pipelines.entry(format).or_insert_with(|| {
+        create_render_pipeline(
+            device,
+            layout,
+            &[format],
+            None,
+            &[],
+            wgpu::ShaderModuleDescriptor {
+                label: Some("Mipmap Shader"),
+                source: wgpu::ShaderSource::Wgsl(
+                    concat!(include_str!("fullscreen.wgsl"), include_str!("mipmap.wgsl")).into(),
+                ),
+            },
+        )
+    });
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Mipmap Encoder"),
+    });
+    for layer in 0..texture.depth_or_array_layers() {
+        let views = (0..mip_level_count)
+            .map(|mip_level| {
+                texture.create_view(&wgpu::TextureViewDescriptor {
+                    label: Some("mipmap_view"),
+                    dimension: Some(wgpu::TextureViewDimension::D2),
+                    base_mip_level: mip_level,
+                    mip_level_count: Some(1),
+                    base_array_layer: layer,
+                    array_layer_count: Some(1),
+                    ..Default::default()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for target in 1..mip_level_count as usize {
+            let bind_group =
+                create_mipmap_bind_group(device, &views[target - 1], sampler, bind_group_layout);
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Mipmap Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &views[target],
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+            render_pass.set_pipeline(pipeline);
+            render_pass.set_bind_group(0, &bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+    }
+    queue.submit(std::iter::once(encoder.finish()));
+}
+
+/// Halves `image` with a 2x2 box filter, repeating the last row or column of odd sizes. Colors
+/// are averaged in linear space when `srgb` is set, averaging the encoded values would darken
+/// every level.
+fn downsample(image: &RgbaImage, srgb: bool) -> RgbaImage {
+    let (width, height) = image.dimensions();
+    let decode = |value: u8| {
+        let value = value as f32 / 255.0;
+        if srgb {
+            srgb_to_linear(value)
+        } else {
+            value
+        }
+    };
+    let encode = |value: f32| {
+        let value = if srgb { linear_to_srgb(value) } else { value };
+        (value.clamp(0.0, 1.0) * 255.0).round() as u8
+    };
+
+    RgbaImage::from_fn((width / 2).max(1), (height / 2).max(1), |x, y| {
+        let texels = [(0, 0), (1, 0), (0, 1), (1, 1)].map(|(dx, dy)| {
+            image.get_pixel((x * 2 + dx).min(width - 1), (y * 2 + dy).min(height - 1))
+        });
+        Rgba(std::array::from_fn(|channel| {
+            // Alpha is never sRGB encoded.
+            if channel == 3 {
+                let sum: u32 = texels.iter().map(|texel| texel[3] as u32).sum();
+                ((sum + 2) / 4) as u8
+            } else {
+                let sum: f32 = texels.iter().map(|texel| decode(texel[channel])).sum();
+                encode(sum / 4.0)
+            }
+        }))
+    })
+}
+
+/// `downsample` for linear floats.
+fn downsample_float(image: &Rgba32FImage) -> Rgba32FImage {
+    let (width, height) = image.dimensions();
+    Rgba32FImage::from_fn((width / 2).max(1), (height / 2).max(1), |x, y| {
+        let texels = [(0, 0), (1, 0), (0, 1), (1, 1)].map(|(dx, dy)| {
+            image.get_pixel((x * 2 + dx).min(width - 1), (y * 2 + dy).min(height - 1))
+        });
+        Rgba(std::array::from_fn(|channel| {
+            texels.iter().map(|texel| texel[channel]).sum::<f32>() / 4.0
+        }))
+    })
+}
+
+fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Rounds to the nearest half float, overflowing to infinity and flushing values too small for
+/// a subnormal to zero.
+fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = (bits >> 16 & 0x8000) as u16;
+    let exponent = (bits >> 23 & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+    if exponent == 0xff {
+        // Infinity stays infinity, NaN stays NaN.
+        return sign | 0x7c00 | if mantissa != 0 { 0x200 } else { 0 };
+    }
+    let exponent = exponent - 112;
+    if exponent >= 0x1f {
+        return sign | 0x7c00;
+    }
+    if exponent <= 0 {
+        if exponent < -10 {
+            return sign;
+        }
+        // Subnormal, shift the mantissa with its implicit leading 1 into place.
+        let mantissa = mantissa | 0x80_0000;
+        let shift = (14 - exponent) as u32;
+        let half = mantissa >> shift;
+        let round = mantissa >> (shift - 1) & 1;
+        return sign | (half + round) as u16;
+    }
+    // Rounding can carry into the exponent, which is still the right result.
+    let half = (exponent as u32) << 10 | mantissa >> 13;
+    let round = mantissa >> 12 & 1;
+    sign | (half + round) as u16
+}
+
+/// Six-layer texture viewed as a cube, with an optional mip chain.
+pub struct CubeTexture {
+    texture: wgpu::Texture,
+    pub view: TextureView,
+    pub sampler: Sampler,
+}
+
+impl CubeTexture {
+    pub fn new(
+        device: &Device,
+        size: u32,
+        format: wgpu::TextureFormat,
+        mip_level_count: u32,
+        usage: wgpu::TextureUsages,
+        label: Option<&str>,
+    ) -> CubeTexture {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label,
+            size: Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: 6,
+            },
+            mip_level_count,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage,
+            view_formats: &[],
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            label,
+            dimension: Some(wgpu::TextureViewDimension::Cube),
+            ..Default::default()
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label,
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        CubeTexture {
+            texture,
+            view,
+            sampler,
+        }
+    }
+
+    /// The six faces of one mip level as a 2D array, for compute shaders reading or writing
+    /// individual faces.
+    pub fn face_array_view(&self, mip_level: u32) -> TextureView {
+        self.texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("cube_face_array_view"),
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            base_mip_level: mip_level,
+            mip_level_count: Some(1),
+            ..Default::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn encode(img: &DynamicImage, format: image::ImageOutputFormat) -> Vec<u8> {
+        let mut bytes = Cursor::new(Vec::new());
+        img.write_to(&mut bytes, format).unwrap();
+        bytes.into_inner()
+    }
+
+    #[test]
+    fn decodes_exr_as_float() {
+        let texels = Rgba32FImage::from_fn(3, 2, |x, y| {
+            image::Rgba([x as f32 * 4.5, y as f32 * 0.25, 100.0, 1.0])
+        });
+        let bytes = encode(
+            &DynamicImage::ImageRgba32F(texels.clone()),
+            image::ImageOutputFormat::OpenExr,
+        );
+        let decoded = decode_image(&bytes, "test.exr").unwrap();
+        assert_eq!(decoded.to_rgba32f(), texels);
+    }
+
+    #[test]
+    fn decodes_16_bit_png_without_losing_bits() {
+        let texels = image::ImageBuffer::from_fn(2, 2, |x, y| {
+            image::Rgba([x as u16 * 257 + 1, y as u16 * 65534, 12345, u16::MAX])
+        });
+        let bytes = encode(
+            &DynamicImage::ImageRgba16(texels.clone()),
+            image::ImageOutputFormat::Png,
+        );
+        match decode_image(&bytes, "test.png").unwrap() {
+            DynamicImage::ImageRgba16(decoded) => assert_eq!(decoded, texels),
+            other => panic!("decoded as {:?}", other.color()),
+        }
+    }
+
+    #[test]
+    fn checks_layers_match_the_first() {
+        let rgba = |width, height| DynamicImage::new_rgba8(width, height);
+        let layers = [rgba(4, 4), rgba(4, 4)];
+        assert!(std::ptr::eq(
+            check_images(&layers, true, None).unwrap(),
+            &layers[0]
+        ));
+        assert!(check_images(&[rgba(4, 4), rgba(2, 2)], false, None).is_ok());
+        assert!(check_images(&[], false, None).is_err());
+        assert!(check_images(&[rgba(4, 4), rgba(4, 2)], true, None).is_err());
+        assert!(check_images(&[rgba(4, 4), DynamicImage::new_rgba16(4, 4)], false, None).is_err());
+    }
+
+    #[test]
+    fn counts_mip_levels_down_to_1x1() {
+        assert_eq!(mip_level_count((1, 1)), 1);
+        assert_eq!(mip_level_count((256, 256)), 9);
+        assert_eq!(mip_level_count((300, 20)), 9);
+        assert_eq!(mip_level_count((1, 1024)), 11);
+    }
+
+    #[test]
+    fn checks_mip_chains() {
+        assert!(check_mip_sizes(&[(8, 4), (4, 2), (2, 1), (1, 1)], None).is_ok());
+        assert!(check_mip_sizes(&[(7, 5), (3, 2)], None).is_ok());
+        assert!(check_mip_sizes(&[(8, 4), (4, 4)], None).is_err());
+        assert!(check_mip_sizes(&[(8, 4), (2, 1)], None).is_err());
+        assert!(check_mip_sizes(&[(2, 2), (1, 1), (1, 1)], None).is_err());
+    }
+
+    // A device for the tests that create textures, which are ignored unless asked for as not every
+    // machine running the tests has an adapter.
+    fn device() -> (Device, Queue) {
+        pollster::block_on(async {
+            let instance = wgpu::Instance::default();
+            let adapter = instance
+                .request_adapter(&wgpu::RequestAdapterOptions::default())
+                .await
+                .expect("no adapter");
+            adapter
+                .request_device(&wgpu::DeviceDescriptor::default(), None)
+                .await
+                .expect("no device")
+        })
+    }
+
+    #[test]
+    #[ignore = "needs a GPU adapter, run with `cargo test -- --ignored`"]
+    fn texture_cache_evicts_unused_textures() {
+        let (device, queue) = device();
+        let mut helpers = TextureHelpers::new();
+        let mut cache = TextureCache::new();
+        let mut checker = |cache: &mut TextureCache| {
+            cache.get_or_insert_with("checker_texture", &TextureOptions::checker(), || {
+                Texture::checker(&device, &queue, &mut helpers)
+            })
+        };
+
+        let texture = checker(&mut cache);
+        assert!(Arc::ptr_eq(&texture, &checker(&mut cache)));
+        assert!(cache
+            .get("checker_texture", &TextureOptions::default())
+            .is_none());
+
+        drop(texture);
+        assert!(cache
+            .get("checker_texture", &TextureOptions::checker())
+            .is_none());
+        let _white = cache.insert(
+            "white_texture",
+            &TextureOptions::solid_color(false),
+            Texture::white(&device, &queue, &mut helpers, false),
+        );
+        // Inserting forgets the dropped checker.
+        assert_eq!(cache.textures.len(), 1);
+    }
+
+    #[test]
+    fn reports_undecodable_images() {
+        assert!(matches!(
+            decode_image(b"not an image", "broken.png"),
+            Err(ResourceError::Decode { .. }) | Err(ResourceError::UnsupportedFormat { .. })
+        ));
+    }
+}